@@ -23,9 +23,9 @@ fn main() {
     let message: &[u8] = b"sample";
 
     // Sign identical message with two different secret keys
-    let sig_1 = Bn256.sign(&secret_key_1, &message).unwrap();
+    let sig_1 = Bn256.sign(&secret_key_1, message).unwrap();
     println!("Size of the signature: {}", sig_1.len());
-    let sig_2 = Bn256.sign(&secret_key_2, &message).unwrap();
+    let sig_2 = Bn256.sign(&secret_key_2, message).unwrap();
 
     // Aggregate public keys
     let agg_pub_key = Bn256
@@ -36,6 +36,6 @@ fn main() {
     let agg_sig = Bn256.aggregate_signatures(&[&sig_1, &sig_2]).unwrap();
 
     // Check whether the aggregated signature corresponds to the aggregated public key
-    let _ = Bn256.verify(&agg_sig, &message, &agg_pub_key).unwrap();
+    Bn256.verify(&agg_sig, message, &agg_pub_key).unwrap();
     println!("Successful verification");
 }
@@ -73,7 +73,6 @@ fn fixture_key_pair(
 
     let privClass = CKO_PRIVATE_KEY;
     let privKeyType = CKK_RSA;
-    let privLabel = privLabel;
     let privToken = CK_TRUE;
     let privPrivate = CK_TRUE;
     let privSensitive = CK_TRUE;
@@ -99,7 +98,6 @@ fn fixture_key_pair(
 
     let pubClass = CKO_PUBLIC_KEY;
     let pubKeyType = CKK_RSA;
-    let pubLabel = pubLabel;
     let pubToken = CK_TRUE;
     let pubPrivate = CK_TRUE;
     let pubWrap = CK_FALSE;
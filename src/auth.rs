@@ -0,0 +1,196 @@
+use crate::{signature_scheme, Commit, SignatureScheme};
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum AuthError {
+    // The request, or the proposal, was signed with a key that the flock does not authorize.
+    UnknownKey,
+    // The signature does not match the message and the public key of the request, or of the
+    // proposal, or its scheme is not supported.
+    BadSignature,
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::UnknownKey => write!(f, "the signing key is not authorized"),
+            AuthError::BadSignature => write!(f, "the signature is invalid"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+/// The bytes a ground station signs: the timestamp and the nonce followed by the message.
+pub fn request_payload(request: &Commit) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(16 + request.msg.len());
+    payload.extend_from_slice(&request.timestamp_ms.to_be_bytes());
+    payload.extend_from_slice(&request.nonce.to_be_bytes());
+    payload.extend_from_slice(&request.msg);
+    payload
+}
+
+/// Signs a ground request with the private key of the ground station. The timestamp and the nonce
+/// must be set beforehand.
+pub fn sign_request(request: &mut Commit, scheme: &dyn SignatureScheme, private_key: &[u8]) {
+    request.scheme = scheme.id().into();
+    request.public_key = scheme.derive_public_key(private_key).unwrap();
+    request.signature = scheme.sign(private_key, &request_payload(request)).unwrap();
+}
+
+/// Checks that a ground request was signed by one of the authorized ground stations.
+pub fn authenticate_request(
+    request: &Commit,
+    ground_station_keys: &[Vec<u8>],
+) -> Result<(), AuthError> {
+    if !ground_station_keys.contains(&request.public_key) {
+        return Err(AuthError::UnknownKey);
+    }
+
+    signature_scheme(request.scheme)
+        .and_then(|scheme| {
+            scheme.verify(
+                &request.signature,
+                &request_payload(request),
+                &request.public_key,
+            )
+        })
+        .map_err(|_| AuthError::BadSignature)
+}
+
+/// The bytes the space station signs in a proposal: the slot index followed by the Merkle root of
+/// the requests.
+pub fn proposal_payload(proposal: &Commit) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(4 + proposal.msg.len());
+    payload.extend_from_slice(&proposal.i.to_be_bytes());
+    payload.extend_from_slice(&proposal.msg);
+    payload
+}
+
+/// Signs a proposal with the private key of the space station. The slot index and the root must be
+/// set beforehand.
+pub fn sign_proposal(proposal: &mut Commit, scheme: &dyn SignatureScheme, private_key: &[u8]) {
+    proposal.scheme = scheme.id().into();
+    proposal.public_key = scheme.derive_public_key(private_key).unwrap();
+    proposal.signature = scheme
+        .sign(private_key, &proposal_payload(proposal))
+        .unwrap();
+}
+
+/// Checks that a proposal was signed by the space station of the flock.
+pub fn authenticate_proposal(proposal: &Commit, space_station_key: &[u8]) -> Result<(), AuthError> {
+    if proposal.public_key != space_station_key {
+        return Err(AuthError::UnknownKey);
+    }
+
+    signature_scheme(proposal.scheme)
+        .and_then(|scheme| {
+            scheme.verify(
+                &proposal.signature,
+                &proposal_payload(proposal),
+                &proposal.public_key,
+            )
+        })
+        .map_err(|_| AuthError::BadSignature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Bn256Scheme;
+
+    #[test]
+    fn authenticate_request_test() {
+        let private_key = vec![1; 32];
+        let public_key = Bn256Scheme.derive_public_key(&private_key).unwrap();
+        let other_private_key = vec![2; 32];
+
+        let mut request = Commit {
+            msg: b"hello".to_vec(),
+            timestamp_ms: 1000,
+            nonce: 7,
+            ..Default::default()
+        };
+        sign_request(&mut request, &Bn256Scheme, &private_key);
+        assert_eq!(request.public_key, public_key);
+
+        assert_eq!(
+            authenticate_request(&request, std::slice::from_ref(&public_key)),
+            Ok(())
+        );
+        assert_eq!(
+            authenticate_request(&request, &[]),
+            Err(AuthError::UnknownKey)
+        );
+
+        let mut forged = request.clone();
+        forged.msg = b"goodbye".to_vec();
+        assert_eq!(
+            authenticate_request(&forged, std::slice::from_ref(&public_key)),
+            Err(AuthError::BadSignature)
+        );
+
+        // The timestamp and the nonce are covered by the signature too.
+        let mut restamped = request.clone();
+        restamped.timestamp_ms += 1;
+        assert_eq!(
+            authenticate_request(&restamped, std::slice::from_ref(&public_key)),
+            Err(AuthError::BadSignature)
+        );
+        let mut renonced = request.clone();
+        renonced.nonce += 1;
+        assert_eq!(
+            authenticate_request(&renonced, std::slice::from_ref(&public_key)),
+            Err(AuthError::BadSignature)
+        );
+
+        // Signed by someone else on behalf of the authorized key.
+        let mut impostor = request.clone();
+        impostor.signature = Bn256Scheme
+            .sign(&other_private_key, &request_payload(&request))
+            .unwrap();
+        assert_eq!(
+            authenticate_request(&impostor, &[public_key]),
+            Err(AuthError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn authenticate_proposal_test() {
+        let private_key = vec![1; 32];
+        let public_key = Bn256Scheme.derive_public_key(&private_key).unwrap();
+
+        let mut proposal = Commit {
+            i: 3,
+            msg: b"root".to_vec(),
+            ..Default::default()
+        };
+        sign_proposal(&mut proposal, &Bn256Scheme, &private_key);
+        assert_eq!(authenticate_proposal(&proposal, &public_key), Ok(()));
+
+        // Signed by anyone else than the space station.
+        let mut other = proposal.clone();
+        sign_proposal(&mut other, &Bn256Scheme, &[2; 32]);
+        assert_eq!(
+            authenticate_proposal(&other, &public_key),
+            Err(AuthError::UnknownKey)
+        );
+
+        // The slot index and the root are covered by the signature.
+        for forged in [
+            Commit {
+                i: 4,
+                ..proposal.clone()
+            },
+            Commit {
+                msg: b"other root".to_vec(),
+                ..proposal.clone()
+            },
+        ] {
+            assert_eq!(
+                authenticate_proposal(&forged, &public_key),
+                Err(AuthError::BadSignature)
+            );
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use bounce::bounce_peer_server::BouncePeerServer;
+use bounce::{
+    configure_log, configure_log_to_file, load_config, signature_scheme, signer_member, timer,
+    unix_time_ms, Cubesat, FailureMode, FlockControl, GrpcLink, KeySigner, Link, Registry,
+    ReplayCache, SchemeId, SlotSchedule, UdpLink, LMS_SCHEME, SPACE_STATION,
+};
+use clap::{crate_authors, crate_version, App, AppSettings, Arg, SubCommand};
+use log::info;
+use std::fs;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let matches = App::new("A Bounce unit running in its own process")
+        .version(crate_version!())
+        .author(crate_authors!())
+        // The id is only required to run the unit, keygen may take it on its own.
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .arg(
+            Arg::with_name("addr")
+                .short("a")
+                .value_name("ADDRESS")
+                .help("Specify an alternate address to listen on, the port is that of the member.")
+                .default_value("0.0.0.0"),
+        )
+        .arg(
+            Arg::with_name("id")
+                .long("id")
+                .short("i")
+                .value_name("ID")
+                .help("Specify the id of this Bounce unit among the members of the flock.")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("log-to-stdout")
+                .long("log-to-stdout")
+                .help("By default logs are saved to files, if set log only to stdout.")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("log-dir")
+                .long("log-dir")
+                .short("l")
+                .value_name("LOG_DIR")
+                .help("Specify a directory to save logs.")
+                .default_value("log"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .short("c")
+                .value_name("CONFIG")
+                .help("Specify the flock configuration file, e.g. flock.toml.")
+                .default_value("flock.toml"),
+        )
+        .arg(
+            Arg::with_name("key")
+                .long("key")
+                .short("k")
+                .value_name("KEY_FILE")
+                .help("Specify the file holding the hex encoded private key of this Bounce unit.")
+                .default_value("cubesat.key"),
+        )
+        .arg(
+            Arg::with_name("key-state")
+                .long("key-state")
+                .value_name("DIR")
+                .help("Specify the directory in which the state of a stateful LMS key is kept.")
+                .default_value("key-state"),
+        )
+        .arg(
+            Arg::with_name("replay-cache")
+                .long("replay-cache")
+                .value_name("FILE")
+                .help(
+                    "Specify the file in which the ground requests already signed are kept, so \
+                     that they are refused after a restart too. Defaults to cubesat-ID-replay-cache.",
+                ),
+        )
+        .arg(
+            Arg::with_name("genesis")
+                .long("genesis")
+                .short("g")
+                .value_name("GENESIS_MS")
+                .help("Unix timestamp in milliseconds at which slot 0 starts, overrides the configuration."),
+        )
+        .arg(
+            Arg::with_name("transport")
+                .long("transport")
+                .short("t")
+                .value_name("TRANSPORT")
+                .help("Specify how commits are exchanged with the peers.")
+                .possible_values(&["grpc", "udp"])
+                .default_value("grpc"),
+        )
+        .subcommand(
+            SubCommand::with_name("keygen")
+                .about(
+                    "Generates a private key for this Bounce unit and prints the member entry to \
+                     add to the flock configuration.",
+                )
+                .arg(
+                    Arg::with_name("id")
+                        .long("id")
+                        .short("i")
+                        .value_name("ID")
+                        .help("Specify the id of the new member, unless given before keygen."),
+                )
+                .arg(
+                    Arg::with_name("address")
+                        .value_name("ADDRESS")
+                        .help("The address the other units reach this one at, e.g. 10.0.0.7:50061")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("scheme")
+                        .long("scheme")
+                        .short("s")
+                        .value_name("SCHEME")
+                        .help("Specify the signature scheme of the flock.")
+                        .possible_values(&["bn256", "bls12-381", "lms"])
+                        .default_value("bn256"),
+                ),
+        )
+        .get_matches();
+
+    let key_file = matches.value_of("key").unwrap();
+
+    if let Some(keygen) = matches.subcommand_matches("keygen") {
+        let id: u32 = keygen
+            .value_of("id")
+            .or_else(|| matches.value_of("id"))
+            .ok_or("keygen needs the id of the new member, given with --id")?
+            .parse()?;
+        let scheme_id = SchemeId::from_name(keygen.value_of("scheme").unwrap()).unwrap();
+        if scheme_id == SchemeId::Lms {
+            LMS_SCHEME.set_state_dir(matches.value_of("key-state").unwrap())?;
+        }
+        let scheme = signature_scheme(scheme_id.into())?;
+        let private_key = scheme.generate_private_key();
+        let member = signer_member(&KeySigner::new(scheme, private_key.clone())?, id)?;
+        fs::write(key_file, hex::encode(&private_key))?;
+        println!(
+            "[[members]]\nid = {}\npublic_key = \"{}\"\nproof_of_possession = \"{}\"\naddress = \"{}\"",
+            id,
+            hex::encode(&member.public_key),
+            hex::encode(&member.proof_of_possession),
+            keygen.value_of("address").unwrap(),
+        );
+        return Ok(());
+    }
+
+    let id: u32 = matches.value_of("id").unwrap().parse()?;
+    if matches.is_present("log-to-stdout") {
+        configure_log()?;
+    } else {
+        let log_dir = matches.value_of("log-dir").unwrap();
+        configure_log_to_file(log_dir, &format!("cubesat-{}", id))?;
+    }
+
+    let mut bounce_config = load_config(matches.value_of("config").unwrap())?;
+    if let Some(genesis_ms) = matches.value_of("genesis") {
+        bounce_config.genesis_ms = genesis_ms.parse()?;
+    }
+    // Every unit follows its own timer, so slot 0 cannot start whenever each process starts.
+    if bounce_config.genesis_ms == 0 {
+        return Err("units running in their own processes need a common genesis".into());
+    }
+    if bounce_config.space_station_key.is_empty() {
+        return Err(
+            "the flock configuration needs the space_station_key that signs the proposals".into(),
+        );
+    }
+    let member = bounce_config
+        .members
+        .iter()
+        .find(|m| m.id == id)
+        .ok_or_else(|| format!("Bounce Unit {} is not a member of the flock", id))?
+        .clone();
+    let port = member
+        .address
+        .rsplit(':')
+        .next()
+        .and_then(|port| port.parse::<u16>().ok())
+        .ok_or_else(|| format!("member {} has no address to listen on", id))?;
+    let socket_addr: SocketAddr =
+        format!("{}:{}", matches.value_of("addr").unwrap(), port).parse()?;
+
+    if bounce_config.scheme == SchemeId::Lms as i32 {
+        LMS_SCHEME.set_state_dir(matches.value_of("key-state").unwrap())?;
+    }
+    let scheme = signature_scheme(bounce_config.scheme)?;
+    let private_key = hex::decode(fs::read_to_string(key_file)?.trim())?;
+    let signer = KeySigner::new(scheme, private_key)?;
+    let replay_cache = ReplayCache::open(
+        matches
+            .value_of("replay-cache")
+            .map(String::from)
+            .unwrap_or_else(|| format!("cubesat-{}-replay-cache", id)),
+        bounce_config.request_window_ms,
+        bounce_config.replay_cache_size as usize,
+        unix_time_ms(),
+    )?;
+
+    // Commits go to the other members and the space station, which collects the certificates.
+    let mut peers: Vec<(u32, &str)> = bounce_config
+        .members
+        .iter()
+        .filter(|peer| peer.id != id && !peer.address.is_empty())
+        .map(|peer| (peer.id, peer.address.as_str()))
+        .collect();
+    if !bounce_config.space_station.is_empty() {
+        peers.push((SPACE_STATION, &bounce_config.space_station));
+    }
+
+    // Over gRPC, the peer service is served next to the unit, and its failure stops the unit.
+    let transport = matches.value_of("transport").unwrap();
+    let mut server = None;
+    let link: Box<dyn Link> = if transport == "udp" {
+        let mut link = UdpLink::bind(&socket_addr.to_string()).await?;
+        for (peer_id, address) in &peers {
+            link.add_peer(*peer_id, address).await?;
+        }
+        Box::new(link)
+    } else {
+        let (mut link, service) = GrpcLink::new(
+            Registry::from_config(&bounce_config)?,
+            bounce_config.space_station_key.clone(),
+        );
+        for (peer_id, address) in &peers {
+            link.add_peer(*peer_id, address)?;
+        }
+        let listener = TcpListener::bind(socket_addr).await?;
+        server = Some(tokio::spawn(
+            Server::builder()
+                .add_service(BouncePeerServer::new(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        ));
+        Box::new(link)
+    };
+    info!(
+        "Bounce Unit {}\tListening on {} over {} with {} peers",
+        id,
+        socket_addr,
+        transport,
+        peers.len()
+    );
+
+    let (timer_tx, timer_rx) = broadcast::channel(15);
+    let mut cubesat = Cubesat::new(
+        id as usize,
+        &bounce_config,
+        Box::new(signer),
+        link,
+        timer_rx,
+        FailureMode::Honest,
+    )
+    .map_err(|e| format!("the key of Bounce Unit {} is refused: {}", id, e))?
+    .with_replay_cache(replay_cache);
+
+    let schedule = SlotSchedule::from_config(&bounce_config)?;
+    let control = FlockControl::new();
+    let paused_rx = control.subscribe();
+    tokio::spawn(async move {
+        timer(timer_tx, schedule, paused_rx).await;
+    });
+
+    match server {
+        Some(server) => tokio::select! {
+            result = cubesat.run() => result?,
+            result = server => {
+                result??;
+                return Err("the peer service stopped".into());
+            }
+        },
+        None => cubesat.run().await?,
+    }
+    Ok(())
+}
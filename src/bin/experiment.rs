@@ -1,4 +1,48 @@
-use clap::{crate_authors, crate_version, App, Arg};
+use bounce::{signature_scheme, Commit, SchemeId, SignatureScheme, LMS_SCHEME};
+use clap::{crate_authors, crate_version, App, Arg, SubCommand};
+use prost::Message;
+
+// Prints the size of a certificate aggregated from the votes of `signers` units with each scheme.
+fn certificate_sizes(signers: usize) -> Result<(), Box<dyn std::error::Error>> {
+    println!("scheme\tsignature\tpublic key\tcommit");
+    // LMS keys only sign with a directory for their state, that of these keys is thrown away.
+    LMS_SCHEME.set_state_dir(std::env::temp_dir().join("bounce-experiment-key-state"))?;
+    for id in &[SchemeId::Bn256, SchemeId::Bls12381, SchemeId::Lms] {
+        let scheme: &dyn SignatureScheme = signature_scheme((*id).into())?;
+        let msg = [0; 32];
+        let private_keys: Vec<Vec<u8>> = (0..signers)
+            .map(|_| scheme.generate_private_key())
+            .collect();
+        let public_keys = private_keys
+            .iter()
+            .map(|k| scheme.derive_public_key(k))
+            .collect::<Result<Vec<_>, _>>()?;
+        let signatures = private_keys
+            .iter()
+            .map(|k| scheme.sign(k, &msg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let signature_refs: Vec<&[u8]> = signatures.iter().map(|s| s.as_slice()).collect();
+        let public_key_refs: Vec<&[u8]> = public_keys.iter().map(|k| k.as_slice()).collect();
+        let certificate = Commit {
+            aggregated: true,
+            msg: msg.to_vec(),
+            signature: scheme.aggregate_signatures(&signature_refs)?,
+            public_key: scheme.aggregate_public_keys(&public_key_refs)?,
+            scheme: (*id).into(),
+            signers: vec![0xff; signers.div_ceil(8)],
+            ..Default::default()
+        };
+        println!(
+            "{:?}\t{}\t{}\t{}",
+            id,
+            certificate.signature.len(),
+            certificate.public_key.len(),
+            certificate.encoded_len()
+        );
+    }
+    Ok(())
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -19,8 +63,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .help("The port of the ground station to connect to")
                 .default_value("50051"),
         )
+        .subcommand(
+            SubCommand::with_name("certificate-size")
+                .about("Compares the size of certificates with each signature scheme.")
+                .arg(
+                    Arg::with_name("signers")
+                        .short("n")
+                        .value_name("SIGNERS")
+                        .help("The number of votes aggregated in a certificate")
+                        .default_value("5"),
+                ),
+        )
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("certificate-size") {
+        return certificate_sizes(matches.value_of("signers").unwrap().parse()?);
+    }
+
     let _addr = matches.value_of("addr").unwrap();
     let _port = matches.value_of("port").unwrap();
 
@@ -1,12 +1,143 @@
-use bls_signatures_rs::bn256::Bn256;
-use bls_signatures_rs::MultiSignature;
 use bounce::bounce_satellite_client::BounceSatelliteClient;
-use bounce::{commit::CommitType, configure_log, configure_log_to_file, Commit};
-use clap::{crate_authors, crate_version, App, Arg};
-use log::info;
+use bounce::{
+    commit::CommitType, configure_log, configure_log_to_file, load_config, load_tle, next_in_pass,
+    parse_location, predict_passes, sign_request, signature_scheme, unix_time_ms, verify_response,
+    Commit, PauseRequest, Registry, RequestResult, ResumeRequest, SchemeId, Sgp4, SignatureScheme,
+    SlotStatusRequest, SlotStatusResponse, StatusRequest, StatusResponse, SubscribeRequest, Ticket,
+    LMS_SCHEME,
+};
+use chrono::{TimeZone, Utc};
+use clap::{crate_authors, crate_version, App, Arg, SubCommand};
+use log::{info, warn};
 use rand::{thread_rng, Rng};
+use std::fs;
 use std::time::Duration;
-use tokio::time::interval;
+use tokio::time::sleep;
+use tonic::transport::Channel;
+use tonic::Code;
+
+// Time between two requests within a pass.
+const REQUEST_INTERVAL_MS: u64 = 10_000;
+// How far ahead passes are predicted at a time.
+const PREDICTION_MS: u64 = 2 * 86_400_000;
+
+fn format_ms(unix_ms: u64) -> String {
+    Utc.timestamp_millis(unix_ms as i64)
+        .format("%Y-%m-%d %H:%M:%S UTC")
+        .to_string()
+}
+
+fn signed_request(msg: &[u8], scheme: &dyn SignatureScheme, private_key: &[u8]) -> Commit {
+    let mut precommit = Commit {
+        typ: CommitType::Precommit.into(),
+        i: 1,
+        j: 0,
+        msg: msg.to_vec(),
+        public_key: Vec::new(),
+        signature: Vec::new(),
+        aggregated: false,
+        // TODO: FIXME
+        signer_id: 100,
+        requests: Vec::new(),
+        timestamp_ms: unix_time_ms(),
+        nonce: thread_rng().gen(),
+        scheme: scheme.id().into(),
+        signers: Vec::new(),
+    };
+    sign_request(&mut precommit, scheme, private_key);
+    precommit
+}
+
+fn print_slot(status: &SlotStatusResponse) {
+    if !status.known {
+        println!("slot {} is not recorded", status.i);
+        return;
+    }
+    println!(
+        "slot {}\t{:?}\troot {}\tprecommits {:?}\tnoncommits {:?}\trelayed {}\tsuppressed {}",
+        status.i,
+        status.outcome(),
+        hex::encode(&status.root),
+        status.precommits,
+        status.noncommits,
+        status.relayed,
+        status.suppressed
+    );
+}
+
+fn print_status(status: &StatusResponse) {
+    println!(
+        "slot {}\t{:?}\tlast committed {}\tpaused {}\tqueue {}\t{:?} with {} members",
+        status.i,
+        status.phase(),
+        status.j,
+        status.paused,
+        status.queue_length,
+        status.scheme(),
+        status.members.len()
+    );
+    for unit in &status.units {
+        let liveness = if unit.alive { "alive" } else { "silent" };
+        if unit.voted {
+            println!(
+                "unit {}\t{}\tlast vote {:?} in slot {}",
+                unit.id,
+                liveness,
+                unit.last_vote(),
+                unit.last_vote_slot
+            );
+        } else {
+            println!("unit {}\t{}\tno vote yet", unit.id, liveness);
+        }
+    }
+}
+
+// The members whose certificates are trusted: those of the flock configuration if one is given,
+// otherwise those the space station reports.
+async fn flock_registry(
+    client: &mut BounceSatelliteClient<Channel>,
+    config: Option<&str>,
+) -> Result<Registry, Box<dyn std::error::Error>> {
+    if let Some(path) = config {
+        let bounce_config = load_config(path)?;
+        if !bounce_config.members.is_empty() {
+            return Ok(Registry::from_config(&bounce_config)?);
+        }
+    }
+    warn!("Ground Station\tTrusting the members of the flock reported by the space station");
+    let status = client.status(StatusRequest {}).await?.into_inner();
+    Ok(Registry::new(
+        signature_scheme(status.scheme)?,
+        &status.members,
+    )?)
+}
+
+// Prints the result of a request, and with its message and the members of the flock, whether the
+// flock certified it.
+fn print_result(result: &RequestResult, check: Option<(&str, &Registry)>) {
+    if !result.known {
+        println!("ticket {} is not recorded", result.ticket);
+        return;
+    }
+    let certificate = result
+        .response
+        .as_ref()
+        .and_then(|response| response.certificate.as_ref());
+    println!(
+        "ticket {}\t{:?}\tslot {}\tcertificate {:?}",
+        result.ticket,
+        result.state(),
+        result.slot,
+        certificate.map(|c| c.typ())
+    );
+    if let (Some((msg, registry)), Some(response)) = (check, &result.response) {
+        if verify_response(msg.as_bytes(), response, registry) {
+            println!("verified that the message was signed by the flock");
+        } else {
+            println!("the flock did not certify the message");
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -41,6 +172,139 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .help("Specify a directory to save logs.")
                 .default_value("log"),
         )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .short("c")
+                .value_name("CONFIG")
+                .help("Specify the flock configuration file, whose members certify messages. Without members, those reported by the space station are trusted."),
+        )
+        .arg(
+            Arg::with_name("key")
+                .long("key")
+                .short("k")
+                .value_name("KEY_FILE")
+                .help(
+                    "Specify the file holding the hex encoded private key of this ground station.",
+                )
+                .default_value("ground-station.key"),
+        )
+        .arg(
+            Arg::with_name("scheme")
+                .long("scheme")
+                .short("s")
+                .value_name("SCHEME")
+                .help("Specify the signature scheme of the key of this ground station.")
+                .possible_values(&["bn256", "bls12-381", "lms"])
+                .default_value("bn256"),
+        )
+        .arg(
+            Arg::with_name("key-state")
+                .long("key-state")
+                .value_name("DIR")
+                .help("Specify the directory in which the state of a stateful LMS key is kept.")
+                .default_value("key-state"),
+        )
+        .arg(
+            Arg::with_name("tle")
+                .long("tle")
+                .value_name("TLE_FILE")
+                .help("Specify the two-line element set of the space station, requests are then only sent during its passes.")
+                .requires("location"),
+        )
+        .arg(
+            Arg::with_name("location")
+                .long("location")
+                .value_name("LAT,LON[,ALT_M]")
+                .help("Specify the location of this ground station in degrees, and its altitude in meters."),
+        )
+        .arg(
+            Arg::with_name("min-elevation")
+                .long("min-elevation")
+                .value_name("DEGREES")
+                .help("Specify the elevation above which the space station is in contact.")
+                .default_value("10"),
+        )
+        .subcommand(SubCommand::with_name("keygen").about(
+            "Generates a private key for this ground station and prints its public key, to be \
+                 added to ground_station_keys in the flock configuration.",
+        ))
+        .subcommand(
+            SubCommand::with_name("pause")
+                .about("Stops the flock at the next slot boundary, e.g. for a maintenance window."),
+        )
+        .subcommand(
+            SubCommand::with_name("resume")
+                .about("Starts a paused flock again at the next slot boundary."),
+        )
+        .subcommand(
+            SubCommand::with_name("status").about(
+                "Prints the progress of the flock, the liveness of its units and the queue length.",
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("slot")
+                .about("Prints what the space station has recorded of a slot.")
+                .arg(
+                    Arg::with_name("i")
+                        .value_name("SLOT")
+                        .help("The index of the slot")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("certificates")
+                .about("Follows the slots finalized by the flock, printing each as it is finalized.")
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .value_name("SLOT")
+                        .help("Specify the first slot, the finalized slots the space station still has are printed first.")
+                        .default_value("0"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("submit")
+                .about("Submits a message and prints the ticket to collect its result with.")
+                .arg(
+                    Arg::with_name("message")
+                        .value_name("MESSAGE")
+                        .help("The message for the flock to sign")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("result")
+                .about("Prints the result of a submitted message, checked against the message if given.")
+                .arg(
+                    Arg::with_name("ticket")
+                        .value_name("TICKET")
+                        .help("The ticket returned by submit")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("message")
+                        .value_name("MESSAGE")
+                        .help("The submitted message"),
+                )
+                .arg(
+                    Arg::with_name("watch")
+                        .long("watch")
+                        .short("w")
+                        .help("Waits for the slot of the message to be finalized, printing each change."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("passes")
+                .about("Lists the upcoming passes of the space station over this ground station.")
+                .arg(
+                    Arg::with_name("hours")
+                        .long("hours")
+                        .value_name("HOURS")
+                        .help("Specify how many hours ahead to look.")
+                        .default_value("24"),
+                ),
+        )
         .get_matches();
 
     let addr = matches.value_of("addr").unwrap();
@@ -54,57 +318,206 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         configure_log_to_file(log_dir, "space-station")?;
     }
 
+    let key_file = matches.value_of("key").unwrap();
+    let config = matches.value_of("config");
+    let scheme_id = SchemeId::from_name(matches.value_of("scheme").unwrap()).unwrap();
+    let scheme = signature_scheme(scheme_id.into())?;
+    if scheme_id == SchemeId::Lms {
+        LMS_SCHEME.set_state_dir(matches.value_of("key-state").unwrap())?;
+    }
+
+    if matches.subcommand_name() == Some("keygen") {
+        let private_key = scheme.generate_private_key();
+        let public_key = scheme.derive_public_key(&private_key)?;
+        fs::write(key_file, hex::encode(&private_key))?;
+        info!("Ground Station\tSaved a new private key to {}", key_file);
+        println!("{}", hex::encode(&public_key));
+        return Ok(());
+    }
+
+    let min_elevation_deg: f64 = matches.value_of("min-elevation").unwrap().parse()?;
+    let orbit = match matches.value_of("tle") {
+        Some(path) => {
+            let location = parse_location(matches.value_of("location").unwrap())?;
+            Some((Sgp4::new(&load_tle(path)?)?, location))
+        }
+        None => None,
+    };
+
+    if let ("passes", Some(passes_matches)) = matches.subcommand() {
+        let (orbit, location) = orbit
+            .as_ref()
+            .ok_or("listing passes needs the element set given by --tle")?;
+        let hours: u64 = passes_matches.value_of("hours").unwrap().parse()?;
+        let now_ms = unix_time_ms();
+        let passes = predict_passes(
+            orbit,
+            location,
+            min_elevation_deg,
+            now_ms,
+            now_ms + hours * 3_600_000,
+        )?;
+        for pass in &passes {
+            println!(
+                "AOS {}\tTCA {}\tLOS {}\tmax elevation {:.1}°\t{} s",
+                format_ms(pass.aos_ms),
+                format_ms(pass.tca_ms),
+                format_ms(pass.los_ms),
+                pass.max_elevation_deg,
+                (pass.los_ms - pass.aos_ms) / 1000
+            );
+        }
+        if passes.is_empty() {
+            println!("no pass in the next {} hours", hours);
+        }
+        return Ok(());
+    }
+
     let dst = format!("http://{}:{}", addr, port);
 
     let mut client = BounceSatelliteClient::connect(dst).await?;
 
-    let slot_duration = Duration::from_secs(10);
-    let mut slot_ticker = interval(slot_duration);
+    match matches.subcommand() {
+        ("status", _) => {
+            let status = client.status(StatusRequest {}).await?.into_inner();
+            print_status(&status);
+            return Ok(());
+        }
+        ("slot", Some(matches)) => {
+            let i = matches.value_of("i").unwrap().parse()?;
+            let status = client
+                .slot_status(SlotStatusRequest { i })
+                .await?
+                .into_inner();
+            print_slot(&status);
+            return Ok(());
+        }
+        ("certificates", Some(matches)) => {
+            let from_slot = matches.value_of("from").unwrap().parse()?;
+            let mut certificates = client
+                .subscribe_certificates(SubscribeRequest { from_slot })
+                .await?
+                .into_inner();
+            while let Some(status) = certificates.message().await? {
+                print_slot(&status);
+            }
+            return Ok(());
+        }
+        ("pause", _) => {
+            let state = client.pause(PauseRequest {}).await?.into_inner();
+            info!("Ground Station\tPausing the flock in slot {}", state.i);
+            return Ok(());
+        }
+        ("resume", _) => {
+            let state = client.resume(ResumeRequest {}).await?.into_inner();
+            info!("Ground Station\tResuming the flock in slot {}", state.i);
+            return Ok(());
+        }
+        ("submit", Some(matches)) => {
+            let private_key = hex::decode(fs::read_to_string(key_file)?.trim())?;
+            let msg = matches.value_of("message").unwrap();
+            let ticket = client
+                .submit(signed_request(msg.as_bytes(), scheme, &private_key))
+                .await?
+                .into_inner();
+            info!("Ground Station\tSubmitted message: {}", msg);
+            println!(
+                "ticket {}\texpected slot {}",
+                ticket.number, ticket.expected_slot
+            );
+            return Ok(());
+        }
+        ("result", Some(matches)) => {
+            let ticket = Ticket {
+                number: matches.value_of("ticket").unwrap().parse()?,
+                ..Default::default()
+            };
+            let msg = matches.value_of("message");
+            let registry = match msg {
+                Some(_) => Some(flock_registry(&mut client, config).await?),
+                None => None,
+            };
+            let check = msg.zip(registry.as_ref());
+            if matches.is_present("watch") {
+                let mut results = client.watch_result(ticket).await?.into_inner();
+                while let Some(result) = results.message().await? {
+                    print_result(&result, check);
+                }
+            } else {
+                let result = client.get_result(ticket).await?.into_inner();
+                print_result(&result, check);
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let private_key = hex::decode(fs::read_to_string(key_file)?.trim())?;
+    let registry = flock_registry(&mut client, config).await?;
+
+    // Without an element set the space station is taken to be always in contact.
+    let mut passes = Vec::new();
+    let mut send_ms = unix_time_ms();
 
     for _ in 0..10 {
-        tokio::select! {
-            _ = slot_ticker.tick() => {
-                let msg = chrono::Utc::now().to_rfc2822();
-                info!("Ground Station\tSending message: {}", msg);
-
-                let mut rng = thread_rng();
-                let ground_station_private_key: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
-                let ground_station_public_key = Bn256
-                    .derive_public_key(&ground_station_private_key)
-                    .unwrap();
-                let signature = Bn256
-                    .sign(&ground_station_private_key, &msg.as_bytes())
-                    .unwrap();
-
-                let precommit = Commit {
-                    typ: CommitType::Precommit.into(),
-                    i: 1,
-                    j: 0,
-                    msg: msg.as_bytes().to_vec(),
-                    public_key: ground_station_public_key,
-                    signature,
-                    aggregated: false,
-                    // TODO: FIXME
-                    signer_id: 100,
-                };
-
-                let request = tonic::Request::new(precommit);
-
-                let start = chrono::Utc::now();
-
-                let response = client.bounce(request).await?.into_inner();
-
-                let end = chrono::Utc::now();
-
-                let _ = Bn256
-                    .verify(&response.signature, &msg.as_bytes(), &response.public_key)
-                    .unwrap();
+        if let Some((orbit, location)) = &orbit {
+            if next_in_pass(&passes, send_ms).is_none() {
+                passes = predict_passes(
+                    orbit,
+                    location,
+                    min_elevation_deg,
+                    send_ms,
+                    send_ms + PREDICTION_MS,
+                )?;
+            }
+            let pass_ms = next_in_pass(&passes, send_ms)
+                .ok_or("the space station never passes over this ground station")?;
+            if pass_ms > send_ms {
+                info!(
+                    "Ground Station\tWaiting for the next pass at {}",
+                    format_ms(pass_ms)
+                );
+            }
+            send_ms = pass_ms;
+        }
+        let now_ms = unix_time_ms();
+        if send_ms > now_ms {
+            sleep(Duration::from_millis(send_ms - now_ms)).await;
+        }
+        send_ms += REQUEST_INTERVAL_MS;
+
+        let msg = chrono::Utc::now().to_rfc2822();
+        info!("Ground Station\tSending message: {}", msg);
 
+        let request = tonic::Request::new(signed_request(msg.as_bytes(), scheme, &private_key));
+
+        let start = chrono::Utc::now();
+
+        // Outside a pass the space station refuses the request, the next one may go through.
+        let response = match client.bounce(request).await {
+            Ok(response) => response.into_inner(),
+            Err(status) if status.code() == Code::Unavailable => {
                 info!(
-                    "Ground Station\tVerified that the message was signed by the flock in {} ms.",
-                    (end - start).num_milliseconds()
+                    "Ground Station\tNo contact with the space station: {}",
+                    status.message()
                 );
+                continue;
             }
+            Err(status) => return Err(status.into()),
+        };
+
+        let end = chrono::Utc::now();
+
+        if verify_response(msg.as_bytes(), &response, &registry) {
+            info!(
+                "Ground Station\tVerified that the message was signed by the flock in {} ms.",
+                (end - start).num_milliseconds()
+            );
+        } else {
+            info!(
+                "Ground Station\tThe flock did not certify the message, slot ended in {:?}",
+                response.certificate.map(|c| c.typ())
+            );
         }
     }
 
@@ -1,166 +1,642 @@
+use bounce::bounce_peer_server::BouncePeerServer;
 use bounce::bounce_satellite_server::{BounceSatellite, BounceSatelliteServer};
+use bounce::health_check_response::ServingStatus;
+use bounce::health_server::HealthServer;
 use bounce::{
-    configure_log, configure_log_to_file, BounceConfig, Commit, Cubesat, FailureMode, Phase,
+    authenticate_request, configure_log, configure_log_to_file, default_config, load_config,
+    load_scenario, load_tle, parse_location, predict_passes, request_id, signature_scheme,
+    signer_member, timer, unix_time_ms, AdmissionError, AuthError, Bn256Scheme, BounceConfig,
+    BounceResponse, ChannelLink, Commit, ContactPlan, Cubesat, EmulatedLink, FailureMode,
+    FlockControl, FlockState, GrpcLink, HealthService, KeySigner, Ledger, Link, LinkError, Member,
+    Mempool, MempoolConfig, OutsidePolicy, PauseRequest, PeerService, PendingRequest, Phase,
+    Pkcs11KeyStore, Proposer, QueueStatusRequest, QueueStatusResponse, Registry, RegistryError,
+    RelayStrategy, ReplayCache, ReplayError, RequestResult, RequestState, ResumeRequest, Router,
+    SchemeId, Sgp4, SignatureScheme, Signer, SignerError, SlotPhase, SlotSchedule,
+    SlotStatusRequest, SlotStatusResponse, StatusRequest, StatusResponse, SubscribeRequest, Tick,
+    Ticket, TicketBook, UdpLink, LMS_SCHEME, SPACE_STATION,
 };
 use clap::{crate_authors, crate_version, App, Arg};
-// use bounce::Cubesat;
-use log::info;
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::{broadcast, mpsc, Mutex};
-use tokio::time::{interval, interval_at, Instant};
+use tokio::time::sleep;
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{transport::Server, Request, Response, Status};
 
-pub struct CubesatInfo {
-    id: u32,
-    _handle: tokio::task::JoinHandle<()>,
-    request_tx: mpsc::Sender<Commit>,
+// Number of slots the ledger keeps a record of.
+const LEDGER_SLOTS: usize = 1024;
+// Number of ground requests whose results are kept for their submitters to collect.
+const TICKETS: usize = 4096;
+// Number of finalized slots a subscriber may fall behind by before its subscription is ended.
+const CERTIFICATE_BACKLOG: usize = 64;
+// How far ahead the passes of the space station are predicted when it starts. An element set goes
+// stale within weeks, the space station is restarted with a fresh one before then.
+const PASS_HORIZON_MS: u64 = 30 * 24 * 3_600_000;
+// The service name under which the space station reports its health, the empty name stands for
+// the whole server.
+const SATELLITE_SERVICE: &str = "bounce.BounceSatellite";
+
+// Runs the Bounce units in this process, linked to the space station over channels. Their keys are
+// registered in the configuration with their proofs of possession.
+fn spawn_units(
+    bounce_config: &mut BounceConfig,
+    signers: Vec<Box<dyn Signer>>,
+    timer_tx: &broadcast::Sender<Tick>,
+) -> ChannelLink {
+    let (result_tx, result_rx) = mpsc::channel(25);
+    let mut link = ChannelLink::new(result_rx);
+
+    bounce_config.members = signers
+        .iter()
+        .enumerate()
+        .map(|(id, signer)| signer_member(signer.as_ref(), id as u32))
+        .collect::<Result<_, _>>()
+        .expect("every Bounce unit can prove the possession of its key");
+
+    for (id, signer) in (0..bounce_config.num_cubesats).zip(signers) {
+        let timer_rx = timer_tx.subscribe();
+        let (request_tx, request_rx) = mpsc::channel(25);
+        link = link.with_peer(id, request_tx);
+
+        let mut cubesat = Cubesat::new(
+            id as usize,
+            bounce_config,
+            signer,
+            Box::new(ChannelLink::new(request_rx).with_peer(SPACE_STATION, result_tx.clone())),
+            timer_rx,
+            FailureMode::Honest,
+        )
+        .expect("every Bounce unit was registered above");
+        tokio::spawn(async move {
+            if let Err(e) = cubesat.run().await {
+                error!("Space Station\tBounce Unit {} stopped: {}", id, e);
+            }
+        });
+    }
+    link
+}
+
+// Links to the members of the configuration, Bounce units running in their own processes. Over gRPC,
+// they deliver their commits to the returned service, which refuses those of non-members.
+async fn link_units(
+    bounce_config: &BounceConfig,
+    registry: Registry,
+    transport: &str,
+    socket_addr: SocketAddr,
+) -> Result<(Box<dyn Link>, Option<PeerService>), LinkError> {
+    if transport == "udp" {
+        let mut link = UdpLink::bind(&socket_addr.to_string()).await?;
+        for member in &bounce_config.members {
+            link.add_peer(member.id, &member.address).await?;
+        }
+        return Ok((Box::new(link), None));
+    }
+    let (mut link, service) = GrpcLink::new(registry, bounce_config.space_station_key.clone());
+    for member in &bounce_config.members {
+        link.add_peer(member.id, &member.address)?;
+    }
+    Ok((Box::new(link), Some(service)))
 }
 
 pub struct SpaceStation {
-    // A channel to receive responses from Cubesats
-    result_rx: Mutex<mpsc::Receiver<Commit>>,
-    // The last slot index for which this Space station responded.
-    last_slot: Mutex<u32>,
+    // Ground requests waiting to be proposed at the start of the next slot.
+    mempool: Arc<Mutex<Mempool<PendingRequest>>>,
+    // Public keys of the ground stations allowed to submit requests.
+    ground_station_keys: Vec<Vec<u8>>,
+    // Ground requests already proposed, persisted so that they are refused even after a restart.
+    replay_cache: Arc<Mutex<ReplayCache>>,
 
-    cubesat_infos: Vec<CubesatInfo>,
+    control: FlockControl,
+    // What happened in the latest slots.
+    ledger: Arc<Mutex<Ledger>>,
+    // What happened to the latest ground requests.
+    tickets: Arc<Mutex<TicketBook>>,
+    // Every slot finalized by a certificate, sent while the ledger is locked.
+    certificates_tx: broadcast::Sender<SlotStatusResponse>,
+
+    // The members of the flock, and the scheme they sign with.
+    members: Vec<Member>,
+    scheme: i32,
+    genesis_ms: u64,
+    // When the ground stations reach the space station, always without a plan.
+    contact: Option<ContactPlan>,
+    // Whether the Bounce units run in their own processes, on timers this space station cannot
+    // pause.
+    linked: bool,
 }
 
-// Timer thread which brodacsts phase transitions.
-async fn timer(timer_tx: broadcast::Sender<Phase>, bounce_config: BounceConfig) {
-    let slot_duration = Duration::from_secs(bounce_config.slot_duration as u64);
-    let start = Instant::now();
-    let phase2_start = start + Duration::from_secs(bounce_config.phase1_duration as u64);
-    let phase3_start = phase2_start + Duration::from_secs(bounce_config.phase2_duration as u64);
-    let mut slot_ticker = interval(slot_duration);
-    let mut phase2_ticker = interval_at(phase2_start, slot_duration);
-    let mut phase3_ticker = interval_at(phase3_start, slot_duration);
-
-    timer_tx.send(Phase::First).unwrap();
-    loop {
-        tokio::select! {
-            _ = slot_ticker.tick() => {
-                timer_tx.send(Phase::First).unwrap();
-            }
-            _ = phase2_ticker.tick() => {
-                timer_tx.send(Phase::Second).unwrap();
+// Holds a reply until the ground link carries it, and for its latency.
+async fn downlink(contact: Option<&ContactPlan>, genesis_ms: u64) {
+    let plan = match contact {
+        Some(plan) => plan,
+        None => return,
+    };
+    let now_ms = unix_time_ms().saturating_sub(genesis_ms);
+    let contact_ms = plan.next_contact(now_ms).unwrap_or(now_ms);
+    sleep(Duration::from_millis(contact_ms - now_ms + plan.latency_ms)).await;
+}
+
+impl SpaceStation {
+    pub fn new(
+        bounce_config: &BounceConfig,
+        link: Box<dyn Link>,
+        relay_strategy: RelayStrategy,
+        timer_tx: &broadcast::Sender<Tick>,
+        control: FlockControl,
+        private_key: Vec<u8>,
+        replay_cache: ReplayCache,
+    ) -> Result<SpaceStation, RegistryError> {
+        let registry = Registry::from_config(bounce_config)?;
+        let link: Arc<dyn Link> = Arc::from(link);
+
+        let mempool = Arc::new(Mutex::new(Mempool::new(MempoolConfig::default())));
+        let batches = Arc::new(Mutex::new(HashMap::new()));
+        let ledger = Arc::new(Mutex::new(Ledger::new(LEDGER_SLOTS)));
+        let tickets = Arc::new(Mutex::new(TicketBook::new(TICKETS)));
+        let replay_cache = Arc::new(Mutex::new(replay_cache));
+        let (certificates_tx, _) = broadcast::channel(CERTIFICATE_BACKLOG);
+
+        tokio::spawn(
+            Proposer::new(
+                link.clone(),
+                ledger.clone(),
+                mempool.clone(),
+                replay_cache.clone(),
+                batches.clone(),
+                tickets.clone(),
+                private_key,
+            )
+            .run(timer_tx.subscribe()),
+        );
+        tokio::spawn(
+            Router::new(
+                link,
+                registry,
+                relay_strategy,
+                ledger.clone(),
+                batches,
+                tickets.clone(),
+                certificates_tx.clone(),
+            )
+            .run(),
+        );
+
+        Ok(Self {
+            mempool,
+            ground_station_keys: bounce_config.ground_station_keys.clone(),
+            replay_cache,
+            control,
+            ledger,
+            tickets,
+            certificates_tx,
+            members: bounce_config.members.clone(),
+            scheme: bounce_config.scheme,
+            genesis_ms: bounce_config.genesis_ms,
+            contact: None,
+            linked: false,
+        })
+    }
+
+    /// Models the ground link with `plan`, the ground stations only reach the space station during
+    /// its passes.
+    pub fn with_contact_plan(mut self, plan: ContactPlan) -> Self {
+        self.contact = Some(plan);
+        self
+    }
+
+    /// Takes the Bounce units to run in their own processes, which keep signing while the space
+    /// station would be paused, so that pausing is refused.
+    pub fn with_linked_units(mut self) -> Self {
+        self.linked = true;
+        self
+    }
+
+    // Waits until the ground link carries a message, and for its latency. A message that arrives
+    // outside a pass is refused, unless the plan holds it until the next one.
+    async fn uplink(&self) -> Result<(), Status> {
+        let plan = match &self.contact {
+            Some(plan) => plan,
+            None => return Ok(()),
+        };
+        let now_ms = unix_time_ms().saturating_sub(self.genesis_ms);
+        match plan.next_contact(now_ms) {
+            None => Err(Status::unavailable("no pass of the space station is left")),
+            Some(contact_ms) if contact_ms > now_ms && plan.outside == OutsidePolicy::Fail => {
+                info!("Space Station\tRefused a request outside a pass");
+                Err(Status::unavailable(format!(
+                    "no contact with the space station for {} ms",
+                    contact_ms - now_ms
+                )))
             }
-            _ = phase3_ticker.tick() => {
-                timer_tx.send(Phase::Third).unwrap();
+            Some(contact_ms) => {
+                sleep(Duration::from_millis(contact_ms - now_ms + plan.latency_ms)).await;
+                Ok(())
             }
         }
     }
-}
 
-impl SpaceStation {
-    pub fn new(num_cubesats: u32, timer_tx: &broadcast::Sender<Phase>) -> SpaceStation {
-        let (result_tx, result_rx) = mpsc::channel(25);
-
-        let result_rx = Mutex::new(result_rx);
-
-        let mut cubesat_infos = Vec::new();
-
-        for id in 0..num_cubesats {
-            let timer_rx = timer_tx.subscribe();
-            let (request_tx, request_rx) = mpsc::channel(25);
-
-            let result_tx = result_tx.clone();
-            let handle = tokio::spawn(async move {
-                let mut cubesat = Cubesat::new(
-                    id as usize,
-                    num_cubesats,
-                    result_tx,
-                    request_rx,
-                    timer_rx,
-                    FailureMode::Honest,
-                );
-                cubesat.run().await;
+    async fn downlink(&self) {
+        downlink(self.contact.as_ref(), self.genesis_ms).await;
+    }
+
+    // Admits a ground request into the mempool, and returns the ticket to collect its result with.
+    async fn admit(&self, commit: Commit) -> Result<Ticket, Status> {
+        info!("Space Station\tReceived a request: {:?}", commit);
+
+        if self.ledger.lock().await.is_stopped() {
+            return Err(Status::unavailable(
+                "the flock is paused, retry once it is resumed",
+            ));
+        }
+
+        // Cubesats verify every request again before signing, this only keeps requests that would
+        // make the slot end in a noncommit out of the mempool.
+        if let Err(e) = authenticate_request(&commit, &self.ground_station_keys) {
+            info!("Space Station\tRefused a request: {}", e);
+            return Err(match e {
+                AuthError::UnknownKey => Status::permission_denied(e.to_string()),
+                AuthError::BadSignature => Status::unauthenticated(e.to_string()),
             });
+        }
 
-            cubesat_infos.push(CubesatInfo {
-                id,
-                _handle: handle,
-                request_tx,
+        // The request is only remembered once it is proposed, a pending request is refused again
+        // by the mempool. The replay cache is held until the request is in the mempool, so that
+        // it is not proposed in the meantime.
+        let now_ms = unix_time_ms();
+        let replay_cache = self.replay_cache.lock().await;
+        if let Err(e) = replay_cache.check(&commit, now_ms) {
+            info!("Space Station\tRefused a request: {}", e);
+            return Err(match e {
+                ReplayError::Stale => Status::failed_precondition(e.to_string()),
+                ReplayError::Replayed => Status::already_exists(e.to_string()),
+                ReplayError::Persist(_) => Status::internal(e.to_string()),
             });
         }
 
-        let last_slot = Mutex::new(0);
+        let submitter = commit.public_key.clone();
+        // The same message may be requested again, only the same request is a duplicate.
+        let id = request_id(&commit);
+
+        let ticket = self.tickets.lock().await.issue();
+        let request = PendingRequest {
+            commit: commit.clone(),
+            ticket,
+        };
+        let next_slot = self.flock_state().await.i + 1;
+        let mut mempool = self.mempool.lock().await;
+        if let Err(e) = mempool.admit(&submitter, id.clone(), request) {
+            drop(mempool);
+            self.tickets.lock().await.forget(ticket);
+            info!("Space Station\tRefused a request: {}", e);
+            return Err(match e {
+                AdmissionError::Duplicate => Status::already_exists(e.to_string()),
+                AdmissionError::Full | AdmissionError::SubmitterLimit => {
+                    Status::resource_exhausted(e.to_string())
+                }
+            });
+        }
+        let position = mempool.position(&id).unwrap_or(0);
+        let expected_slot = next_slot + (position / mempool.config().max_batch_size) as u32;
+        drop(mempool);
+        drop(replay_cache);
+
+        Ok(Ticket {
+            number: ticket,
+            id,
+            expected_slot,
+        })
+    }
+
+    // Waits until the request of `ticket` is finalized or given up.
+    async fn settled(&self, ticket: u64) -> RequestResult {
+        let watched = self.tickets.lock().await.watch(ticket);
+        let mut result_rx = match watched {
+            Some(result_rx) => result_rx,
+            None => return self.tickets.lock().await.result(ticket),
+        };
+        loop {
+            let result = result_rx.borrow().clone();
+            if result.is_settled() || result_rx.changed().await.is_err() {
+                return result;
+            }
+        }
+    }
 
-        Self {
-            result_rx,
-            last_slot,
-            cubesat_infos,
+    async fn flock_state(&self) -> FlockState {
+        let ledger = self.ledger.lock().await;
+        FlockState {
+            paused: self.control.is_paused() || ledger.is_stopped(),
+            i: ledger.slot_info().i,
         }
     }
 }
 
 #[tonic::async_trait]
 impl BounceSatellite for SpaceStation {
-    // The bounce function is marked async, so whenever this function is called, we should broadcast
-    // the message to sign to cubesats.
-    // broadcast channel here: SpaceStation -> Cubesats, each cubesat needs to see messages in order
-    //  without any loss.
+    // Ground requests are not sent to the cubesats right away. All requests received during a slot
+    // are proposed together at the start of the next slot, under a Merkle root which is the only
+    // message the cubesats sign.
     //
-    // Whenever the cubesat receive such request, then the cubesat signs and then sends back
-    // the signature (either aggregated or single) to the comms hub.
-    // Multi-producer, single consumer channel here, cubesats to SpaceStation
-    //  and the comms hub needs to check whether the signature is aggregated, if it is aggregated
-    //  then it needs to send it back to the ground station.
+    // Cubesats send their single signatures back to the space station, which relays them to the
+    // other cubesats, and eventually an aggregated signature of a supermajority, after which every
+    // request of the slot is answered with the certificate and its own inclusion proof.
+    async fn bounce(&self, request: Request<Commit>) -> Result<Response<BounceResponse>, Status> {
+        self.uplink().await?;
+        let response = match self.admit(request.into_inner()).await {
+            Ok(ticket) => {
+                let result = self.settled(ticket.number).await;
+                match (result.state(), result.response) {
+                    (RequestState::Finalized, Some(response)) => Ok(response),
+                    _ => Err(Status::deadline_exceeded(
+                        "the slot in which the request was proposed was not finalized",
+                    )),
+                }
+            }
+            Err(status) => Err(status),
+        };
+        self.downlink().await;
+        response.map(Response::new)
+    }
 
-    // Sending back can also be managed by a separate thread.
-    async fn bounce(&self, request: Request<Commit>) -> Result<Response<Commit>, Status> {
-        info!("Space Station\tReceived a request: {:?}", request);
+    async fn submit(&self, request: Request<Commit>) -> Result<Response<Ticket>, Status> {
+        self.uplink().await?;
+        let ticket = self.admit(request.into_inner()).await;
+        self.downlink().await;
+        ticket.map(Response::new)
+    }
 
-        let commit: Commit = request.into_inner();
+    async fn get_result(
+        &self,
+        request: Request<Ticket>,
+    ) -> Result<Response<RequestResult>, Status> {
+        self.uplink().await?;
+        let result = self
+            .tickets
+            .lock()
+            .await
+            .result(request.into_inner().number);
+        self.downlink().await;
+        Ok(Response::new(result))
+    }
 
-        for cubesat_info in &self.cubesat_infos {
-            if cubesat_info.request_tx.send(commit.clone()).await.is_err() {
-                info!(
-                    "Space Station\tFailed to send a request to Bounce Unit {}",
-                    cubesat_info.id
-                );
+    type WatchResultStream = ReceiverStream<Result<RequestResult, Status>>;
+
+    async fn watch_result(
+        &self,
+        request: Request<Ticket>,
+    ) -> Result<Response<Self::WatchResultStream>, Status> {
+        self.uplink().await?;
+        let ticket = request.into_inner().number;
+        let (tickets, contact, genesis_ms) =
+            (self.tickets.clone(), self.contact.clone(), self.genesis_ms);
+        let (update_tx, update_rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            let watched = tickets.lock().await.watch(ticket);
+            let mut result_rx = match watched {
+                Some(result_rx) => result_rx,
+                None => {
+                    let result = tickets.lock().await.result(ticket);
+                    downlink(contact.as_ref(), genesis_ms).await;
+                    let _ = update_tx.send(Ok(result)).await;
+                    return;
+                }
+            };
+            loop {
+                let result = result_rx.borrow().clone();
+                let settled = result.is_settled();
+                downlink(contact.as_ref(), genesis_ms).await;
+                // The ground station hung up, or the request will not change anymore.
+                if update_tx.send(Ok(result)).await.is_err()
+                    || settled
+                    || result_rx.changed().await.is_err()
+                {
+                    return;
+                }
             }
+        });
+        Ok(Response::new(ReceiverStream::new(update_rx)))
+    }
+
+    async fn queue_status(
+        &self,
+        request: Request<QueueStatusRequest>,
+    ) -> Result<Response<QueueStatusResponse>, Status> {
+        self.uplink().await?;
+        let id = request.into_inner().id;
+        // The ledger is locked before the mempool, in the order the proposer takes them.
+        let next_slot = self.flock_state().await.i + 1;
+        let mempool = self.mempool.lock().await;
+
+        let response = match mempool.position(&id) {
+            Some(position) => QueueStatusResponse {
+                queued: true,
+                position: position as u32,
+                queue_length: mempool.len() as u32,
+                expected_slot: next_slot + (position / mempool.config().max_batch_size) as u32,
+            },
+            None => QueueStatusResponse {
+                queued: false,
+                position: 0,
+                queue_length: mempool.len() as u32,
+                expected_slot: 0,
+            },
+        };
+        drop(mempool);
+        self.downlink().await;
+        Ok(Response::new(response))
+    }
+
+    async fn pause(&self, _request: Request<PauseRequest>) -> Result<Response<FlockState>, Status> {
+        self.uplink().await?;
+        if self.linked {
+            info!(
+                "Space Station\tRefused to pause the Bounce units running in their own processes"
+            );
+            self.downlink().await;
+            return Err(Status::failed_precondition(
+                "the Bounce units run in their own processes, on timers the space station cannot pause",
+            ));
         }
+        info!("Space Station\tPausing the flock at the next slot boundary");
+        self.control.pause();
+        let state = self.flock_state().await;
+        self.downlink().await;
+        Ok(Response::new(state))
+    }
 
-        let mut receiver = self.result_rx.lock().await;
+    async fn resume(
+        &self,
+        _request: Request<ResumeRequest>,
+    ) -> Result<Response<FlockState>, Status> {
+        self.uplink().await?;
+        info!("Space Station\tResuming the flock at the next slot boundary");
+        self.control.resume();
+        let state = self.flock_state().await;
+        self.downlink().await;
+        Ok(Response::new(state))
+    }
 
-        loop {
-            match receiver.recv().await {
-                Some(commit) => {
-                    if commit.aggregated {
-                        info!(
-                            "Space Station\tReceived an aggregated signature from Bounce Unit {}",
-                            commit.signer_id
-                        );
-                        // TODO: Change this to use SlotInfo instead of this variable. It turns
-                        // out that this information has to be kept somewhere in the
-                        // space station too, in addition to among cubesats.
-                        let mut idx = self.last_slot.lock().await;
-                        if *idx < commit.i {
-                            *idx = commit.i;
-                            return Ok(Response::new(commit));
-                        }
-                    } else {
-                        info!(
-                            "Space Station\tReceived a single signature from Bounce Unit {}",
-                            commit.signer_id
-                        );
-                        // TODO: Do not send to the cubesat that has sent this precommit.
-                        for cubesat_info in &self.cubesat_infos {
-                            if cubesat_info.request_tx.send(commit.clone()).await.is_err() {
-                                info!(
-                                    "Space Station\tFailed to send a request to Bounce Unit {}",
-                                    cubesat_info.id
-                                );
-                            }
-                        }
-                    }
+    async fn slot_status(
+        &self,
+        request: Request<SlotStatusRequest>,
+    ) -> Result<Response<SlotStatusResponse>, Status> {
+        self.uplink().await?;
+        let i = request.into_inner().i;
+        let status = self.ledger.lock().await.status(i);
+        self.downlink().await;
+        Ok(Response::new(status))
+    }
+
+    type SubscribeCertificatesStream = ReceiverStream<Result<SlotStatusResponse, Status>>;
+
+    async fn subscribe_certificates(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeCertificatesStream>, Status> {
+        self.uplink().await?;
+        let from_slot = request.into_inner().from_slot;
+        info!(
+            "Space Station\tSubscribed to the certificates from slot {}",
+            from_slot
+        );
+        // Subscribing while the ledger is locked, no slot is finalized between the backlog and the
+        // first certificate received.
+        let (backlog, mut certificates_rx) = {
+            let ledger = self.ledger.lock().await;
+            (
+                ledger.finalized_since(from_slot),
+                self.certificates_tx.subscribe(),
+            )
+        };
+        let (contact, genesis_ms) = (self.contact.clone(), self.genesis_ms);
+        let (update_tx, update_rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            let mut next_slot = from_slot;
+            for status in backlog {
+                next_slot = next_slot.max(status.i + 1);
+                downlink(contact.as_ref(), genesis_ms).await;
+                if update_tx.send(Ok(status)).await.is_err() {
+                    return;
                 }
-                _ => {
-                    panic!("something didn't work out");
+            }
+            loop {
+                let status = match certificates_rx.recv().await {
+                    Ok(status) if status.i < from_slot => continue,
+                    Ok(status) => status,
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        let _ = update_tx
+                            .send(Err(Status::aborted(format!(
+                                "fell behind the flock, subscribe again from slot {}",
+                                next_slot
+                            ))))
+                            .await;
+                        return;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                next_slot = next_slot.max(status.i + 1);
+                downlink(contact.as_ref(), genesis_ms).await;
+                if update_tx.send(Ok(status)).await.is_err() {
+                    return;
                 }
             }
-        }
+        });
+        Ok(Response::new(ReceiverStream::new(update_rx)))
+    }
+
+    async fn status(
+        &self,
+        _request: Request<StatusRequest>,
+    ) -> Result<Response<StatusResponse>, Status> {
+        self.uplink().await?;
+        let queue_length = self.mempool.lock().await.len() as u32;
+        let ledger = self.ledger.lock().await;
+        let slot_info = ledger.slot_info();
+        let phase = match slot_info.phase {
+            Phase::Stop => SlotPhase::Stopped,
+            Phase::First => SlotPhase::First,
+            Phase::Second => SlotPhase::Second,
+            Phase::Third => SlotPhase::Third,
+        };
+        let status = StatusResponse {
+            i: slot_info.i,
+            phase: phase.into(),
+            j: slot_info.j,
+            paused: self.control.is_paused() || ledger.is_stopped(),
+            members: self.members.clone(),
+            scheme: self.scheme,
+            units: self
+                .members
+                .iter()
+                .map(|member| ledger.unit_status(member.id))
+                .collect(),
+            queue_length,
+        };
+        drop(ledger);
+        self.downlink().await;
+        Ok(Response::new(status))
+    }
+}
+
+// Reports the space station as serving while the flock runs, and as not serving once the timer
+// stopped it, e.g. when it was paused.
+async fn report_health(health: HealthService, mut timer_rx: broadcast::Receiver<Tick>) {
+    let mut started = false;
+    loop {
+        let tick = match timer_rx.recv().await {
+            Ok(tick) => tick,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let status = if tick.phase == Phase::Stop && started {
+            ServingStatus::NotServing
+        } else {
+            started = true;
+            ServingStatus::Serving
+        };
+        health.set_status("", status);
+        health.set_status(SATELLITE_SERVICE, status);
+    }
+}
+
+// Gives each Bounce unit a fresh key, or with a token the key labeled after the unit, which is
+// generated and stored the first time the unit runs.
+fn unit_signers(
+    bounce_config: &BounceConfig,
+    token: Option<&Pkcs11KeyStore>,
+) -> Result<Vec<Box<dyn Signer>>, Box<dyn std::error::Error>> {
+    let scheme = signature_scheme(bounce_config.scheme)?;
+    let mut signers: Vec<Box<dyn Signer>> = Vec::new();
+    for id in 0..bounce_config.num_cubesats {
+        let token = match token {
+            Some(token) => token,
+            None => {
+                signers.push(Box::new(KeySigner::new(
+                    scheme,
+                    scheme.generate_private_key(),
+                )?));
+                continue;
+            }
+        };
+        let label = format!("bounce-unit-{}", id);
+        let signer = match token.signer(scheme, &label) {
+            Err(SignerError::KeyNotFound(_)) => {
+                info!(
+                    "Space Station\tStoring a new key for Bounce Unit {} in the token",
+                    id
+                );
+                token.import_key(&label, &scheme.generate_private_key())?;
+                token.signer(scheme, &label)?
+            }
+            signer => signer?,
+        };
+        signers.push(Box::new(signer));
     }
+    Ok(signers)
 }
 
 #[tokio::main]
@@ -196,6 +672,106 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .help("Specify a directory to save logs.")
                 .default_value("log"),
         )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .short("c")
+                .value_name("CONFIG")
+                .help("Specify the flock configuration file, e.g. flock.toml."),
+        )
+        .arg(
+            Arg::with_name("key")
+                .long("key")
+                .short("k")
+                .value_name("KEY_FILE")
+                .help("Specify the file holding the hex encoded BN256 private key the space station signs its proposals with, for members running in their own processes.")
+                .default_value("space-station.key"),
+        )
+        .arg(
+            Arg::with_name("replay-cache")
+                .long("replay-cache")
+                .value_name("FILE")
+                .help("Specify the file in which proposed ground requests are remembered.")
+                .default_value("replay-cache"),
+        )
+        .arg(
+            Arg::with_name("key-state")
+                .long("key-state")
+                .value_name("DIR")
+                .help("Specify the directory in which the state of stateful LMS keys is kept.")
+                .default_value("key-state"),
+        )
+        .arg(
+            Arg::with_name("relay")
+                .long("relay")
+                .value_name("STRATEGY")
+                .help(
+                    "Specify how votes are relayed between the Bounce units, by default targeted, \
+                     or direct for members running in their own processes.",
+                )
+                .possible_values(&["broadcast", "targeted", "direct"]),
+        )
+        .arg(
+            Arg::with_name("scenario")
+                .long("scenario")
+                .value_name("FILE")
+                .help("Emulate latency, loss and partitions on the links of the Bounce units, and passes over the ground stations, e.g. scenario.toml."),
+        )
+        .arg(
+            Arg::with_name("tle")
+                .long("tle")
+                .value_name("TLE_FILE")
+                .help("Specify the two-line element set of the space station, the ground stations then only reach it during its passes.")
+                .requires("location"),
+        )
+        .arg(
+            Arg::with_name("location")
+                .long("location")
+                .value_name("LAT,LON[,ALT_M]")
+                .help("Specify the location of the ground stations in degrees, and their altitude in meters."),
+        )
+        .arg(
+            Arg::with_name("min-elevation")
+                .long("min-elevation")
+                .value_name("DEGREES")
+                .help("Specify the elevation above which the space station is in contact.")
+                .default_value("10"),
+        )
+        .arg(
+            Arg::with_name("transport")
+                .long("transport")
+                .short("t")
+                .value_name("TRANSPORT")
+                .help("Specify how commits are exchanged with members running in their own processes.")
+                .possible_values(&["grpc", "udp"])
+                .default_value("grpc"),
+        )
+        .arg(
+            Arg::with_name("pkcs11-module")
+                .long("pkcs11-module")
+                .value_name("MODULE")
+                .help("Store the keys of the Bounce units in a token of this PKCS#11 module, which keeps them at rest but signs nothing itself."),
+        )
+        .arg(
+            Arg::with_name("token")
+                .long("token")
+                .value_name("LABEL")
+                .help("Specify the label of the PKCS#11 token.")
+                .default_value("bounce"),
+        )
+        .arg(
+            Arg::with_name("pin")
+                .long("pin")
+                .value_name("PIN")
+                .help("Specify the user PIN of the PKCS#11 token."),
+        )
+        .arg(
+            Arg::with_name("genesis")
+                .long("genesis")
+                .short("g")
+                .value_name("GENESIS_MS")
+                .help("Unix timestamp in milliseconds at which slot 0 starts, defaults to now."),
+        )
         .get_matches();
 
     let addr = matches.value_of("addr").unwrap();
@@ -211,20 +787,171 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let socket_addr = format!("{}:{}", addr, port).parse()?;
 
-    let bounce_config = BounceConfig {
-        num_cubesats: 5,
-        slot_duration: 10,
-        phase1_duration: 4,
-        phase2_duration: 4,
+    let mut bounce_config = match matches.value_of("config") {
+        Some(path) => load_config(path)?,
+        None => default_config(),
+    };
+    // Members run in their own processes, without members the space station runs the units.
+    let linked = !bounce_config.members.is_empty();
+    if linked && bounce_config.members.iter().any(|m| m.address.is_empty()) {
+        return Err("every member of the flock needs the address it receives commits at".into());
+    }
+    if bounce_config.ground_station_keys.is_empty() {
+        warn!("Space Station\tNo ground station is authorized, every request will be refused");
+    }
+
+    // A genesis on the command line overrides the configuration, and without either slot 0 starts
+    // now.
+    if let Some(genesis_ms) = matches.value_of("genesis") {
+        bounce_config.genesis_ms = genesis_ms.parse()?;
+    } else if bounce_config.genesis_ms == 0 {
+        // Units in their own processes follow their own timers, which must agree on slot 0.
+        if linked {
+            return Err("members running in their own processes need a common genesis".into());
+        }
+        bounce_config.genesis_ms = unix_time_ms();
+    }
+
+    // The units only sign the proposals of the space station. Members check them against the key in
+    // the configuration, the units run by the space station are given a new key.
+    let private_key = if linked {
+        let private_key =
+            hex::decode(fs::read_to_string(matches.value_of("key").unwrap())?.trim())?;
+        if Bn256Scheme.derive_public_key(&private_key)? != bounce_config.space_station_key {
+            return Err(
+                "the key of the space station does not match space_station_key in the configuration".into(),
+            );
+        }
+        private_key
+    } else {
+        let private_key = Bn256Scheme.generate_private_key();
+        bounce_config.space_station_key = Bn256Scheme.derive_public_key(&private_key)?;
+        private_key
+    };
+
+    let relay_strategy = match matches.value_of("relay") {
+        Some(name) => RelayStrategy::from_name(name).unwrap(),
+        None if linked => RelayStrategy::Direct,
+        None => RelayStrategy::Targeted,
     };
+    if relay_strategy == RelayStrategy::Direct && !linked {
+        return Err("the units the space station runs have no links of their own".into());
+    }
+
+    if bounce_config.scheme == SchemeId::Lms as i32 {
+        LMS_SCHEME.set_state_dir(matches.value_of("key-state").unwrap())?;
+    }
+
+    let replay_cache = ReplayCache::open(
+        matches.value_of("replay-cache").unwrap(),
+        bounce_config.request_window_ms,
+        bounce_config.replay_cache_size as usize,
+        unix_time_ms(),
+    )?;
+    let schedule = SlotSchedule::from_config(&bounce_config)?;
 
     // Initialized to Stop
     let (timer_tx, _timer_rx) = broadcast::channel(15);
 
-    let comms_hub = SpaceStation::new(bounce_config.num_cubesats, &timer_tx);
+    let control = FlockControl::new();
+    let paused_rx = control.subscribe();
+
+    let token = match matches.value_of("pkcs11-module") {
+        Some(module) => Some(Pkcs11KeyStore::open(
+            module,
+            matches.value_of("token").unwrap(),
+            matches.value_of("pin").unwrap_or_default(),
+        )?),
+        None => None,
+    };
+    let (link, peer_service) = if linked {
+        let transport = matches.value_of("transport").unwrap();
+        let registry = Registry::from_config(&bounce_config)?;
+        let (link, peer_service) =
+            link_units(&bounce_config, registry, transport, socket_addr).await?;
+        (link, peer_service.map(BouncePeerServer::new))
+    } else {
+        let signers = unit_signers(&bounce_config, token.as_ref())?;
+        let link: Box<dyn Link> = Box::new(spawn_units(&mut bounce_config, signers, &timer_tx));
+        (link, None)
+    };
+    let scenario = match matches.value_of("scenario") {
+        Some(path) => {
+            info!(
+                "Space Station\tEmulating the links of the flock from {}",
+                path
+            );
+            Some(load_scenario(path)?)
+        }
+        None => None,
+    };
+    let link: Box<dyn Link> = match &scenario {
+        Some(scenario) => Box::new(EmulatedLink::new(
+            link,
+            SPACE_STATION,
+            bounce_config.members.iter().map(|m| m.id).collect(),
+            scenario.clone(),
+            bounce_config.genesis_ms,
+        )),
+        None => link,
+    };
+
+    let comms_hub = SpaceStation::new(
+        &bounce_config,
+        link,
+        relay_strategy,
+        &timer_tx,
+        control,
+        private_key,
+        replay_cache,
+    )?;
+    // Passes predicted from the orbit of the space station replace those of the scenario, which
+    // only emulate them, and keep how the scenario sets up the link.
+    let ground = scenario.and_then(|scenario| scenario.ground);
+    let contact = match matches.value_of("tle") {
+        Some(path) => {
+            let orbit = Sgp4::new(&load_tle(path)?)?;
+            let location = parse_location(matches.value_of("location").unwrap())?;
+            let min_elevation_deg: f64 = matches.value_of("min-elevation").unwrap().parse()?;
+            let now_ms = unix_time_ms();
+            let passes = predict_passes(
+                &orbit,
+                &location,
+                min_elevation_deg,
+                now_ms,
+                now_ms + PASS_HORIZON_MS,
+            )?;
+            let plan = ground
+                .unwrap_or_default()
+                .with_passes(&passes, bounce_config.genesis_ms);
+            if plan.windows.is_empty() {
+                return Err("the space station does not pass over the ground stations".into());
+            }
+            info!(
+                "Space Station\tPredicted {} passes over the ground stations",
+                plan.windows.len()
+            );
+            Some(plan)
+        }
+        None => ground,
+    };
+    let comms_hub = match contact {
+        Some(plan) => comms_hub.with_contact_plan(plan),
+        None => comms_hub,
+    };
+    let comms_hub = if linked {
+        comms_hub.with_linked_units()
+    } else {
+        comms_hub
+    };
+
+    let health = HealthService::new();
+    health.set_status("", ServingStatus::Serving);
+    health.set_status(SATELLITE_SERVICE, ServingStatus::Serving);
+    tokio::spawn(report_health(health.clone(), timer_tx.subscribe()));
 
     tokio::spawn(async move {
-        timer(timer_tx, bounce_config).await;
+        timer(timer_tx, schedule, paused_rx).await;
     });
 
     // This installs a BounceSatelliteServer service.
@@ -232,6 +959,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // ISS and the Earth?
     Server::builder()
         .add_service(BounceSatelliteServer::new(comms_hub))
+        .add_service(HealthServer::new(health))
+        .add_optional_service(peer_service)
         .serve(socket_addr)
         .await?;
 
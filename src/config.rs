@@ -0,0 +1,292 @@
+use crate::{BounceConfig, Member, Registry, RegistryError, SchemeId};
+use serde::Deserialize;
+use std::{fmt, fs, io};
+
+// The flock configuration file, in TOML. Keys are hex encoded.
+#[derive(Deserialize)]
+struct FlockConfigFile {
+    num_cubesats: u32,
+    // Left out, or 0, to start slot 0 when the space station starts.
+    #[serde(default)]
+    genesis_ms: u64,
+    slot_duration_ms: u32,
+    phase1_duration_ms: u32,
+    phase2_duration_ms: u32,
+    #[serde(default)]
+    ground_station_keys: Vec<String>,
+    #[serde(default = "default_request_window_ms")]
+    request_window_ms: u64,
+    #[serde(default = "default_replay_cache_size")]
+    replay_cache_size: u32,
+    // "bn256" or "bls12-381".
+    #[serde(default = "default_scheme")]
+    scheme: String,
+    #[serde(default)]
+    members: Vec<MemberEntry>,
+    // Where the space station receives the commits of members running in their own processes.
+    #[serde(default)]
+    space_station: String,
+    // The BN256 public key the space station signs its proposals with.
+    #[serde(default)]
+    space_station_key: String,
+}
+
+#[derive(Deserialize)]
+struct MemberEntry {
+    id: u32,
+    public_key: String,
+    proof_of_possession: String,
+    // Where the member receives commits, e.g. "10.0.0.7:50061", when it runs in its own process.
+    #[serde(default)]
+    address: String,
+}
+
+fn default_request_window_ms() -> u64 {
+    60_000
+}
+
+fn default_replay_cache_size() -> u32 {
+    65_536
+}
+
+fn default_scheme() -> String {
+    String::from("bn256")
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Key(hex::FromHexError),
+    Scheme(String),
+    Registry(RegistryError),
+    MemberCount(u32, usize),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "failed to read the flock configuration: {}", e),
+            ConfigError::Parse(e) => write!(f, "malformed flock configuration: {}", e),
+            ConfigError::Key(e) => write!(f, "malformed key in the flock configuration: {}", e),
+            ConfigError::Scheme(name) => write!(f, "unknown signature scheme {}", name),
+            ConfigError::Registry(e) => write!(f, "invalid flock member: {}", e),
+            ConfigError::MemberCount(num_cubesats, members) => write!(
+                f,
+                "the flock has {} Bounce units but {} members",
+                num_cubesats, members
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The configuration used when no configuration file is given. No ground station is authorized.
+pub fn default_config() -> BounceConfig {
+    BounceConfig {
+        num_cubesats: 5,
+        genesis_ms: 0,
+        slot_duration_ms: 10_000,
+        phase1_duration_ms: 4_000,
+        phase2_duration_ms: 4_000,
+        ground_station_keys: Vec::new(),
+        request_window_ms: default_request_window_ms(),
+        replay_cache_size: default_replay_cache_size(),
+        members: Vec::new(),
+        scheme: SchemeId::Bn256.into(),
+        space_station: String::new(),
+        space_station_key: Vec::new(),
+    }
+}
+
+pub fn parse_config(config: &str) -> Result<BounceConfig, ConfigError> {
+    let file: FlockConfigFile = toml::from_str(config).map_err(ConfigError::Parse)?;
+
+    let ground_station_keys = file
+        .ground_station_keys
+        .iter()
+        .map(hex::decode)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ConfigError::Key)?;
+
+    let members = file
+        .members
+        .iter()
+        .map(|member| {
+            Ok(Member {
+                id: member.id,
+                public_key: hex::decode(&member.public_key)?,
+                proof_of_possession: hex::decode(&member.proof_of_possession)?,
+                address: member.address.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ConfigError::Key)?;
+    // Members run in their own processes, each one of the Bounce units of the flock.
+    if !members.is_empty() && members.len() != file.num_cubesats as usize {
+        return Err(ConfigError::MemberCount(file.num_cubesats, members.len()));
+    }
+    let space_station_key = hex::decode(&file.space_station_key).map_err(ConfigError::Key)?;
+    let scheme = SchemeId::from_name(&file.scheme)
+        .ok_or_else(|| ConfigError::Scheme(file.scheme.clone()))?;
+
+    let config = BounceConfig {
+        num_cubesats: file.num_cubesats,
+        genesis_ms: file.genesis_ms,
+        slot_duration_ms: file.slot_duration_ms,
+        phase1_duration_ms: file.phase1_duration_ms,
+        phase2_duration_ms: file.phase2_duration_ms,
+        ground_station_keys,
+        request_window_ms: file.request_window_ms,
+        replay_cache_size: file.replay_cache_size,
+        members,
+        scheme: scheme.into(),
+        space_station: file.space_station,
+        space_station_key,
+    };
+    // Member keys are only accepted with a valid proof of possession.
+    Registry::from_config(&config).map_err(ConfigError::Registry)?;
+    Ok(config)
+}
+
+pub fn load_config(path: &str) -> Result<BounceConfig, ConfigError> {
+    parse_config(&fs::read_to_string(path).map_err(ConfigError::Io)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{new_member, Bls12381Scheme, Bn256Scheme};
+
+    #[test]
+    fn parse_config_test() {
+        let config = parse_config(
+            r#"
+            num_cubesats = 7
+            slot_duration_ms = 2000
+            phase1_duration_ms = 800
+            phase2_duration_ms = 600
+            ground_station_keys = ["00ff", "abcd"]
+            request_window_ms = 30000
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.num_cubesats, 7);
+        assert_eq!(config.genesis_ms, 0);
+        assert_eq!(config.slot_duration_ms, 2000);
+        assert_eq!(config.phase1_duration_ms, 800);
+        assert_eq!(config.phase2_duration_ms, 600);
+        assert_eq!(
+            config.ground_station_keys,
+            vec![vec![0x00, 0xff], vec![0xab, 0xcd]]
+        );
+        assert_eq!(config.request_window_ms, 30_000);
+        assert_eq!(config.replay_cache_size, 65_536);
+        assert_eq!(config.scheme, i32::from(SchemeId::Bn256));
+    }
+
+    #[test]
+    fn parse_scheme_test() {
+        let member = new_member(&Bls12381Scheme, 0, &[1; 32]);
+        let config = format!(
+            r#"
+            num_cubesats = 1
+            slot_duration_ms = 2000
+            phase1_duration_ms = 800
+            phase2_duration_ms = 600
+            scheme = "bls12-381"
+            [[members]]
+            id = 0
+            public_key = "{}"
+            proof_of_possession = "{}"
+            "#,
+            hex::encode(&member.public_key),
+            hex::encode(&member.proof_of_possession),
+        );
+        let parsed = parse_config(&config).unwrap();
+        assert_eq!(parsed.scheme, i32::from(SchemeId::Bls12381));
+        assert_eq!(parsed.members, vec![member]);
+
+        // Members are verified with the scheme of the flock.
+        assert!(matches!(
+            parse_config(&config.replace("bls12-381", "bn256")),
+            Err(ConfigError::Registry(RegistryError::InvalidProof(0)))
+        ));
+        assert!(matches!(
+            parse_config(&config.replace("bls12-381", "ed25519")),
+            Err(ConfigError::Scheme(_))
+        ));
+    }
+
+    #[test]
+    fn parse_members_test() {
+        let members: Vec<Member> = (0..2)
+            .map(|id| Member {
+                address: format!("127.0.0.1:5006{}", id),
+                ..new_member(&Bn256Scheme, id, &[id as u8 + 1; 32])
+            })
+            .collect();
+        let mut config = String::from(
+            r#"
+            num_cubesats = 2
+            slot_duration_ms = 2000
+            phase1_duration_ms = 800
+            phase2_duration_ms = 600
+            space_station = "127.0.0.1:50051"
+            space_station_key = "00ff"
+            "#,
+        );
+        for member in &members {
+            config.push_str(&format!(
+                "[[members]]\nid = {}\npublic_key = \"{}\"\nproof_of_possession = \"{}\"\naddress = \"{}\"\n",
+                member.id,
+                hex::encode(&member.public_key),
+                hex::encode(&member.proof_of_possession),
+                member.address,
+            ));
+        }
+        let parsed = parse_config(&config).unwrap();
+        assert_eq!(parsed.members, members);
+        assert_eq!(parsed.space_station, "127.0.0.1:50051");
+        assert_eq!(parsed.space_station_key, vec![0x00, 0xff]);
+
+        // Every Bounce unit of a flock with members is a member.
+        assert!(matches!(
+            parse_config(&config.replace("num_cubesats = 2", "num_cubesats = 3")),
+            Err(ConfigError::MemberCount(3, 2))
+        ));
+
+        // A key registered with the proof of another key is refused.
+        let rogue = config.replacen(
+            &hex::encode(&members[1].public_key),
+            &hex::encode(&new_member(&Bn256Scheme, 1, &[9; 32]).public_key),
+            1,
+        );
+        assert!(matches!(
+            parse_config(&rogue),
+            Err(ConfigError::Registry(RegistryError::InvalidProof(1)))
+        ));
+    }
+
+    #[test]
+    fn parse_config_error_test() {
+        assert!(matches!(
+            parse_config("num_cubesats = 7"),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            parse_config(
+                r#"
+                num_cubesats = 7
+                slot_duration_ms = 2000
+                phase1_duration_ms = 800
+                phase2_duration_ms = 600
+                ground_station_keys = ["not hex"]
+                "#
+            ),
+            Err(ConfigError::Key(_))
+        ));
+    }
+}
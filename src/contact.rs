@@ -0,0 +1,236 @@
+use crate::Pass;
+use serde::Deserialize;
+
+/// What the space station does with a ground request that arrives outside a pass.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutsidePolicy {
+    // Refuses it, the ground station tries again during a pass.
+    #[default]
+    Fail,
+    // Holds it until the next pass.
+    Queue,
+}
+
+/// A pass of the space station over the ground stations, in ms after genesis.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ContactWindow {
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+/// When the ground stations can reach the space station. Passes are listed, or repeat every
+/// `period_ms` for `duration_ms` from `offset_ms`, or both. Without either there is always contact.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct ContactPlan {
+    #[serde(default)]
+    pub windows: Vec<ContactWindow>,
+    #[serde(default)]
+    pub period_ms: u64,
+    #[serde(default)]
+    pub duration_ms: u64,
+    #[serde(default)]
+    pub offset_ms: u64,
+    // Time to acquire the signal at the start of each pass, before any message goes through.
+    #[serde(default)]
+    pub setup_ms: u64,
+    // One way latency of the ground link.
+    #[serde(default)]
+    pub latency_ms: u64,
+    #[serde(default)]
+    pub outside: OutsidePolicy,
+}
+
+impl ContactPlan {
+    /// Checks that every pass is longer than the time to set it up.
+    pub fn check(&self) -> Result<(), String> {
+        if self.period_ms > 0 && self.duration_ms > self.period_ms {
+            return Err("passes last longer than their period".to_string());
+        }
+        let durations = self
+            .windows
+            .iter()
+            .map(|w| w.end_ms.saturating_sub(w.start_ms))
+            .chain((self.period_ms > 0).then_some(self.duration_ms));
+        for duration in durations {
+            if duration <= self.setup_ms {
+                return Err(format!(
+                    "a pass of {} ms is over before the link is set up",
+                    duration
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the passes of this plan with `passes` predicted from the orbit of the space station,
+    /// keeping how the link is set up in each. Passes that are over before the link is set up are
+    /// left out.
+    pub fn with_passes(mut self, passes: &[Pass], genesis_ms: u64) -> Self {
+        let setup_ms = self.setup_ms;
+        self.windows = passes
+            .iter()
+            .map(|pass| ContactWindow {
+                start_ms: pass.aos_ms.saturating_sub(genesis_ms),
+                end_ms: pass.los_ms.saturating_sub(genesis_ms),
+            })
+            .filter(|w| w.end_ms - w.start_ms > setup_ms)
+            .collect();
+        self.period_ms = 0;
+        self.duration_ms = 0;
+        self.offset_ms = 0;
+        self
+    }
+
+    fn is_always(&self) -> bool {
+        self.windows.is_empty() && self.period_ms == 0
+    }
+
+    // The first time from `now_ms` at which the pass [start_ms, end_ms) carries messages.
+    fn in_pass(&self, start_ms: u64, end_ms: u64, now_ms: u64) -> Option<u64> {
+        let usable_ms = start_ms + self.setup_ms;
+        if now_ms >= end_ms || usable_ms >= end_ms {
+            None
+        } else {
+            Some(now_ms.max(usable_ms))
+        }
+    }
+
+    /// The first time from `now_ms` at which a message goes through, in ms after genesis, or None
+    /// if no pass is left.
+    pub fn next_contact(&self, now_ms: u64) -> Option<u64> {
+        if self.is_always() {
+            return Some(now_ms);
+        }
+        let listed = self
+            .windows
+            .iter()
+            .filter_map(|w| self.in_pass(w.start_ms, w.end_ms, now_ms));
+        // The current pass, or the one that started last, and the next one.
+        let periodic = now_ms
+            .saturating_sub(self.offset_ms)
+            .checked_div(self.period_ms)
+            .and_then(|k| {
+                (k..k + 2)
+                    .filter_map(|k| {
+                        let start_ms = self.offset_ms + k * self.period_ms;
+                        self.in_pass(start_ms, start_ms + self.duration_ms, now_ms)
+                    })
+                    .min()
+            });
+        listed.chain(periodic).min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn always() -> ContactPlan {
+        ContactPlan {
+            windows: Vec::new(),
+            period_ms: 0,
+            duration_ms: 0,
+            offset_ms: 0,
+            setup_ms: 0,
+            latency_ms: 0,
+            outside: OutsidePolicy::Fail,
+        }
+    }
+
+    #[test]
+    fn periodic_passes_test() {
+        // A 10 minute pass every 90 minutes, usable after 30 s.
+        let plan = ContactPlan {
+            period_ms: 5_400_000,
+            duration_ms: 600_000,
+            offset_ms: 1_000_000,
+            setup_ms: 30_000,
+            ..always()
+        };
+        plan.check().unwrap();
+
+        assert_eq!(plan.next_contact(0), Some(1_030_000));
+        assert_eq!(plan.next_contact(1_010_000), Some(1_030_000));
+        assert_eq!(plan.next_contact(1_200_000), Some(1_200_000));
+        assert_eq!(plan.next_contact(1_600_000), Some(6_430_000));
+        assert_eq!(plan.next_contact(6_500_000), Some(6_500_000));
+    }
+
+    #[test]
+    fn listed_passes_test() {
+        let plan = ContactPlan {
+            windows: vec![
+                ContactWindow {
+                    start_ms: 5000,
+                    end_ms: 8000,
+                },
+                ContactWindow {
+                    start_ms: 1000,
+                    end_ms: 2000,
+                },
+            ],
+            setup_ms: 500,
+            ..always()
+        };
+        assert_eq!(plan.next_contact(0), Some(1500));
+        assert_eq!(plan.next_contact(1700), Some(1700));
+        assert_eq!(plan.next_contact(2000), Some(5500));
+        assert_eq!(plan.next_contact(8000), None);
+
+        // Without passes, the ground link is always up.
+        assert_eq!(always().next_contact(42), Some(42));
+    }
+
+    #[test]
+    fn predicted_passes_test() {
+        let pass = |aos_ms, los_ms| Pass {
+            aos_ms,
+            tca_ms: (aos_ms + los_ms) / 2,
+            los_ms,
+            max_elevation_deg: 30.0,
+        };
+        let plan = ContactPlan {
+            period_ms: 60_000,
+            duration_ms: 20_000,
+            setup_ms: 2_000,
+            ..always()
+        }
+        .with_passes(
+            &[
+                pass(1_010_000, 1_300_000),
+                pass(6_000_000, 6_001_000),
+                pass(7_000_000, 7_400_000),
+            ],
+            1_000_000,
+        );
+        plan.check().unwrap();
+
+        // The passes are taken after genesis, and the periodic ones of the scenario are dropped,
+        // as is a pass too short to set up the link.
+        assert_eq!(plan.windows.len(), 2);
+        assert_eq!(plan.next_contact(0), Some(12_000));
+        assert_eq!(plan.next_contact(300_000), Some(6_002_000));
+        assert_eq!(plan.next_contact(6_400_000), None);
+    }
+
+    #[test]
+    fn contact_plan_check_test() {
+        let plan = ContactPlan {
+            windows: vec![ContactWindow {
+                start_ms: 1000,
+                end_ms: 1200,
+            }],
+            setup_ms: 200,
+            ..always()
+        };
+        assert!(plan.check().is_err());
+        assert!(ContactPlan {
+            period_ms: 100,
+            duration_ms: 200,
+            ..always()
+        }
+        .check()
+        .is_err());
+    }
+}
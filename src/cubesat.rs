@@ -1,10 +1,104 @@
 use crate::commit::CommitType;
-use crate::{supermajority, Commit, Phase, SlotInfo};
-use bls_signatures_rs::bn256::Bn256;
-use bls_signatures_rs::MultiSignature;
-use log::info;
+use crate::{
+    authenticate_proposal, authenticate_request, request_id, supermajority, unix_time_ms,
+    BounceConfig, CertificateError, Commit, Link, LinkError, MerkleTree, Metrics, Phase, Registry,
+    RegistryError, ReplayCache, ReplayError, SchemeError, Signer, SignerError, SlotInfo, Tick,
+    VoteAggregate, VoteError,
+};
+use log::{error, info, warn};
 use rand::{thread_rng, Rng};
-use tokio::sync::{broadcast, mpsc};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+#[derive(Debug, PartialEq)]
+pub enum BounceError {
+    // A vote that cannot be aggregated, with the id its signer claims.
+    Vote(u32, VoteError),
+    // An aggregated commit that does not certify a supermajority, with the id its sender claims.
+    Certificate(u32, CertificateError),
+    // A proposal that the space station did not sign, whose root does not match its requests, or
+    // with a request this unit refuses.
+    InvalidProposal(String),
+    // The requests of a proposal could not be remembered by the replay cache.
+    ReplayCache(ReplayError),
+    // This Bounce unit could not sign.
+    Sign(SignerError),
+    // The space station no longer receives the commits of this Bounce unit.
+    ChannelClosed,
+    // A commit could not be sent to the peers.
+    Link(LinkError),
+}
+
+/// What a Bounce unit does about an error.
+#[derive(Debug, PartialEq)]
+pub enum ErrorPolicy {
+    // Drops the message that caused it and carries on.
+    Drop,
+    // Drops the message and marks the peer that sent it as suspect.
+    Suspect,
+    // Stops the Bounce unit.
+    Shutdown,
+}
+
+impl BounceError {
+    pub fn policy(&self) -> ErrorPolicy {
+        match self {
+            // Honest units relay votes more than once, and may sign noncommits for different last
+            // committed slots.
+            BounceError::Vote(_, VoteError::Duplicate)
+            | BounceError::Vote(_, VoteError::MessageMismatch) => ErrorPolicy::Drop,
+            BounceError::Vote(..) | BounceError::Certificate(..) => ErrorPolicy::Suspect,
+            BounceError::InvalidProposal(_) => ErrorPolicy::Drop,
+            // The proposal is not signed, so this unit signs a noncommit in the third phase.
+            BounceError::ReplayCache(_) => ErrorPolicy::Drop,
+            // A used up key, or one whose state is lost, must never sign again.
+            BounceError::Sign(SignerError::Scheme(SchemeError::Exhausted))
+            | BounceError::Sign(SignerError::Scheme(SchemeError::KeyState(_))) => {
+                ErrorPolicy::Shutdown
+            }
+            BounceError::Sign(_) => ErrorPolicy::Drop,
+            BounceError::ChannelClosed => ErrorPolicy::Shutdown,
+            // The commit is lost as it would be over a lossy link.
+            BounceError::Link(_) => ErrorPolicy::Drop,
+        }
+    }
+}
+
+impl fmt::Display for BounceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BounceError::Vote(signer_id, e) => {
+                write!(f, "invalid vote from Bounce Unit {}: {}", signer_id, e)
+            }
+            BounceError::Certificate(signer_id, e) => {
+                write!(
+                    f,
+                    "invalid certificate from Bounce Unit {}: {}",
+                    signer_id, e
+                )
+            }
+            BounceError::InvalidProposal(e) => write!(f, "invalid proposal: {}", e),
+            BounceError::ReplayCache(e) => write!(f, "the replay cache refused a request: {}", e),
+            BounceError::Sign(e) => write!(f, "failed to sign: {}", e),
+            BounceError::ChannelClosed => write!(f, "the space station is gone"),
+            BounceError::Link(e) => write!(f, "failed to send: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BounceError {}
+
+impl From<LinkError> for BounceError {
+    fn from(e: LinkError) -> Self {
+        match e {
+            LinkError::Closed => BounceError::ChannelClosed,
+            e => BounceError::Link(e),
+        }
+    }
+}
 
 pub enum FailureMode {
     // Follows the protocol and has no impostor.
@@ -17,11 +111,11 @@ pub enum FailureMode {
 
 /// Bounce Unit invariants
 /// 1. A Bounce unit will never send a precommit or non-commit if it has already sent a precommit
-/// or non-commit
+///    or non-commit
 /// 2. A Bounce unit will never send a precommit or non-commit if it has already received an
-/// aggregated precommit or non-commit or has sent one.
-/// 3. A Bounce unit will never send an aggregated precommit or non-commit if it has either received
-/// an aggregated precommit or non-commit or has already sent one.
+///    aggregated precommit or non-commit or has sent one.
+/// 3. A Bounce unit will never send an aggregated precommit or non-commit if it has either
+///    received an aggregated precommit or non-commit or has already sent one.
 pub struct Cubesat {
     id: usize,
 
@@ -29,60 +123,158 @@ pub struct Cubesat {
     num_cubesats: u32,
     slot_info: SlotInfo,
 
+    // Members of the flock whose votes are aggregated.
+    registry: Registry,
+    // Public keys of the ground stations whose requests this Bounce unit signs.
+    ground_station_keys: Vec<Vec<u8>>,
+    // Public key of the space station, the only sender of proposals.
+    space_station_key: Vec<u8>,
+    // Ground requests this Bounce unit has signed, so that it never signs one of them again.
+    replay_cache: ReplayCache,
+    // Ids claimed by peers that sent invalid votes.
+    suspects: HashSet<u32>,
+
     public_key: Vec<u8>,
-    private_key: Vec<u8>,
+    // Holds the private key of this Bounce unit.
+    signer: Box<dyn Signer>,
 
-    // sender to send to communications hub
-    result_tx: mpsc::Sender<Commit>,
-    // receiver to receive Commits from the communications hub
-    request_rx: mpsc::Receiver<Commit>,
+    // Carries commits to and from the space station and the other Bounce units.
+    link: Box<dyn Link>,
 
     // Receiver for phase transitions.
-    timer_rx: broadcast::Receiver<Phase>,
+    timer_rx: broadcast::Receiver<Tick>,
 
     failure_mode: FailureMode,
+
+    metrics: Arc<Metrics>,
 }
 
 impl Cubesat {
     pub fn new(
         id: usize,
-        num_cubesats: u32,
-        result_tx: mpsc::Sender<Commit>,
-        request_rx: mpsc::Receiver<Commit>,
-        timer_rx: broadcast::Receiver<Phase>,
+        bounce_config: &BounceConfig,
+        signer: Box<dyn Signer>,
+        link: Box<dyn Link>,
+        timer_rx: broadcast::Receiver<Tick>,
         failure_mode: FailureMode,
-    ) -> Self {
-        let mut rng = thread_rng();
-
-        // generate public and private key pairs.
-        let private_key: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
-        let public_key = Bn256.derive_public_key(&private_key).unwrap();
+    ) -> Result<Self, RegistryError> {
+        let registry = Registry::from_config(bounce_config)?;
+        let public_key = signer.public_key().to_vec();
+        if !registry.contains(&public_key) {
+            return Err(RegistryError::NotAMember);
+        }
         let slot_info = SlotInfo::new();
 
-        Cubesat {
+        Ok(Cubesat {
             id,
-            num_cubesats,
+            num_cubesats: bounce_config.num_cubesats,
             slot_info,
+            registry,
+            ground_station_keys: bounce_config.ground_station_keys.clone(),
+            space_station_key: bounce_config.space_station_key.clone(),
+            replay_cache: ReplayCache::new(
+                bounce_config.request_window_ms,
+                bounce_config.replay_cache_size as usize,
+            ),
+            suspects: HashSet::new(),
             public_key,
-            private_key,
-            result_tx,
-            request_rx,
+            signer,
+            link,
             timer_rx,
             failure_mode,
+            metrics: Arc::new(Metrics::new()),
+        })
+    }
+
+    /// Remembers the ground requests this unit signs in `replay_cache`, e.g. one persisted to a file
+    /// so that they are still refused after a restart.
+    pub fn with_replay_cache(mut self, replay_cache: ReplayCache) -> Self {
+        self.replay_cache = replay_cache;
+        self
+    }
+
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    pub fn is_suspect(&self, signer_id: u32) -> bool {
+        self.suspects.contains(&signer_id)
+    }
+
+    // Applies the policy of the error, and gives it back if this Bounce unit must stop.
+    fn handle_error(&mut self, e: BounceError) -> Result<(), BounceError> {
+        match e.policy() {
+            ErrorPolicy::Drop => {
+                info!(
+                    "Slot {}\tBounce Unit {}\tDropping a message: {}",
+                    self.slot_info.i, self.id, e,
+                );
+                self.metrics.add_dropped_messages(1);
+            }
+            ErrorPolicy::Suspect => {
+                warn!(
+                    "Slot {}\tBounce Unit {}\tDropping a message: {}",
+                    self.slot_info.i, self.id, e,
+                );
+                self.metrics.add_dropped_messages(1);
+                if let BounceError::Vote(signer_id, _) | BounceError::Certificate(signer_id, _) = e
+                {
+                    if self.suspects.insert(signer_id) {
+                        self.metrics.add_suspects(1);
+                    }
+                }
+            }
+            ErrorPolicy::Shutdown => {
+                error!(
+                    "Slot {}\tBounce Unit {}\tShutting down: {}",
+                    self.slot_info.i, self.id, e,
+                );
+                return Err(e);
+            }
         }
+        Ok(())
     }
 
-    fn aggregate(commits: &[Commit]) -> (Vec<u8>, Vec<u8>) {
-        let sig_refs: Vec<&[u8]> = commits.iter().map(|c| c.signature.as_slice()).collect();
-        let aggregate_signature = Bn256.aggregate_signatures(&sig_refs).unwrap();
+    fn is_valid_proposal(commit: &Commit) -> bool {
+        let msgs: Vec<&[u8]> = commit.requests.iter().map(|r| r.msg.as_slice()).collect();
+        MerkleTree::new(&msgs).root() == commit.msg
+    }
 
-        let public_key_refs: Vec<&[u8]> = commits.iter().map(|c| c.public_key.as_slice()).collect();
-        let aggregate_public_key = Bn256.aggregate_public_keys(&public_key_refs).unwrap();
+    /// Checks that every request of a proposal was signed by an authorized ground station, is
+    /// fresh and was never signed before.
+    fn check_proposal(&self, commit: &Commit, now_ms: u64) -> Result<(), BounceError> {
+        let mut ids = HashSet::new();
+        for request in &commit.requests {
+            let rejection = if let Err(e) = authenticate_request(request, &self.ground_station_keys)
+            {
+                e.to_string()
+            } else if let Err(e) = self.replay_cache.check(request, now_ms) {
+                e.to_string()
+            } else if !ids.insert(request_id(request)) {
+                "the request appears twice in the batch".to_string()
+            } else {
+                continue;
+            };
+            return Err(BounceError::InvalidProposal(rejection));
+        }
+        Ok(())
+    }
 
-        (aggregate_signature, aggregate_public_key)
+    // The message of the noncommit this Bounce unit signs in the current slot.
+    fn noncommit_msg(&self) -> Vec<u8> {
+        format!("noncommit({}, {})", self.slot_info.j + 1, self.slot_info.i).into_bytes()
     }
 
-    fn get_commits(&self, commit_type: CommitType) -> &[Commit] {
+    // Whether this Bounce unit may sign the message of a vote of another unit: the root of the
+    // proposal it checked, or the noncommit it would sign itself.
+    fn endorses(&self, vote: &Commit) -> bool {
+        match vote.typ() {
+            CommitType::Precommit => self.slot_info.proposal.as_ref() == Some(&vote.msg),
+            CommitType::Noncommit => vote.msg == self.noncommit_msg(),
+        }
+    }
+
+    fn votes(&self, commit_type: CommitType) -> &VoteAggregate {
         if commit_type == CommitType::Precommit {
             &self.slot_info.precommits
         } else {
@@ -90,18 +282,32 @@ impl Cubesat {
         }
     }
 
-    async fn aggregate_and_broadcast(&mut self, mut commit: Commit) {
-        let (aggregate_signature, aggregate_public_key) =
-            Cubesat::aggregate(self.get_commits(commit.typ()));
+    fn record_vote(&mut self, commit: &Commit) -> Result<(), BounceError> {
+        let votes = if commit.typ() == CommitType::Precommit {
+            &mut self.slot_info.precommits
+        } else {
+            &mut self.slot_info.noncommits
+        };
+        votes
+            .add(commit, &self.registry)
+            .map_err(|e| BounceError::Vote(commit.signer_id, e))
+    }
 
-        commit.signature = aggregate_signature;
-        commit.public_key = aggregate_public_key;
+    async fn aggregate_and_broadcast(&mut self, mut commit: Commit) -> Result<(), BounceError> {
+        let votes = self.votes(commit.typ());
+        commit.msg = votes.msg().to_vec();
+        commit.signature = votes.signature().to_vec();
+        commit.public_key = votes.public_key().to_vec();
+        commit.signers = votes.signer_bitmap();
+        commit.scheme = self.registry.scheme().id().into();
         commit.aggregated = true;
         commit.i = self.slot_info.i;
         commit.signer_id = self.id as u32;
 
         self.slot_info.aggregated = true;
         if commit.typ() == CommitType::Precommit {
+            // Units that receive the aggregate take their last committed slot from it.
+            commit.j = commit.i;
             self.slot_info.j = commit.i;
         }
         info!(
@@ -110,18 +316,18 @@ impl Cubesat {
             self.id,
             commit.typ(),
         );
-        self.result_tx.send(commit).await.unwrap();
+        Ok(self.link.broadcast(commit).await?)
     }
 
-    async fn sign_and_broadcast(&mut self, mut commit: Commit) -> Commit {
-        let signature = Bn256.sign(&self.private_key, &commit.msg).unwrap();
-        commit.signature = signature;
+    async fn sign_and_broadcast(&mut self, mut commit: Commit) -> Result<Commit, BounceError> {
+        commit.signature = self.signer.sign(&commit.msg).map_err(BounceError::Sign)?;
+        commit.scheme = self.registry.scheme().id().into();
         commit.public_key = self.public_key.to_vec();
         commit.i = self.slot_info.i;
         commit.signer_id = self.id as u32;
 
         self.slot_info.signed = true;
-        self.result_tx.send(commit.clone()).await.unwrap();
+        self.link.broadcast(commit.clone()).await?;
 
         info!(
             "Slot {}\tBounce Unit {}\tCommit Type {:?}\tsign and broadcast",
@@ -130,31 +336,55 @@ impl Cubesat {
             commit.typ(),
         );
 
-        commit
+        Ok(commit)
     }
 
-    async fn process(&mut self, commit: Commit) {
+    async fn process(&mut self, commit: Commit) -> Result<(), BounceError> {
         // Ignore the commit that was signed by itself.
         if self.public_key == commit.public_key {
-            return;
+            return Ok(());
         }
 
         if self.slot_info.phase == Phase::Stop {
-            return;
+            return Ok(());
+        }
+
+        // A proposal from the space station is not a vote by itself, it is only there to be signed
+        // while this Bounce unit has not signed anything in the first two phases.
+        if !commit.requests.is_empty() {
+            if self.slot_info.signed || self.slot_info.phase == Phase::Third {
+                return Ok(());
+            }
+            if let Err(e) = authenticate_proposal(&commit, &self.space_station_key) {
+                return Err(BounceError::InvalidProposal(format!(
+                    "not from the space station, {}",
+                    e
+                )));
+            }
+            if !Cubesat::is_valid_proposal(&commit) {
+                return Err(BounceError::InvalidProposal(
+                    "the root does not match the requests".to_string(),
+                ));
+            }
         }
 
         // If thie Bounce unit has already aggregated or received an aggregate signature, then just
         // return.
         if self.slot_info.aggregated {
-            return;
+            return Ok(());
         }
 
-        // If the commit is an aggregate signature, then we note that this slot is aggregated and
-        // update the last committed slot and current slot information.
-        if commit.aggregated && commit.i == self.slot_info.i {
-            self.slot_info.aggregated = true;
-            self.slot_info.j = commit.j;
-            return;
+        // If the commit is an aggregate signature of a supermajority, then we note that this slot
+        // is aggregated and update the last committed slot and current slot information.
+        if commit.aggregated {
+            if commit.i == self.slot_info.i {
+                self.registry
+                    .verify_certificate(&commit)
+                    .map_err(|e| BounceError::Certificate(commit.signer_id, e))?;
+                self.slot_info.aggregated = true;
+                self.slot_info.j = commit.j;
+            }
+            return Ok(());
         }
 
         match self.failure_mode {
@@ -164,7 +394,7 @@ impl Cubesat {
         }
     }
 
-    async fn process_fail_arbitrary(&mut self, mut commit: Commit) {
+    async fn process_fail_arbitrary(&mut self, mut commit: Commit) -> Result<(), BounceError> {
         // Flip a coin to determine whether to send precommit or a noncommit.
         let typ = if thread_rng().gen::<f32>() < 0.5 {
             CommitType::Precommit
@@ -174,124 +404,194 @@ impl Cubesat {
 
         // Overwrite the commit type.
         commit.set_typ(typ);
+        commit.requests.clear();
 
         if !self.slot_info.signed {
-            commit = self.sign_and_broadcast(commit).await;
+            commit = self.sign_and_broadcast(commit).await?;
         }
 
         // Even though this is fail arbitrary, it will still follow the rest of the protocol, i.e.
         // keeping track of the number of precommits or noncommits.
         // TODO(taegyunk): Come up with a more reasonable scenario for this.
-        if typ == CommitType::Precommit {
-            self.slot_info.precommits.push(commit.clone());
-        } else {
-            self.slot_info.noncommits.push(commit.clone());
-        }
+        self.record_vote(&commit)?;
 
-        if self.slot_info.precommits.len() >= supermajority(self.num_cubesats as usize) {
-            self.aggregate_and_broadcast(commit).await;
-        } else if self.slot_info.noncommits.len() >= supermajority(self.num_cubesats as usize) {
-            self.aggregate_and_broadcast(commit).await;
+        let supermajority = supermajority(self.num_cubesats as usize);
+        if self.slot_info.precommits.len() >= supermajority
+            || self.slot_info.noncommits.len() >= supermajority
+        {
+            self.aggregate_and_broadcast(commit).await?;
         }
 
         // TODO(taegyunk): Update to send the commit at a random time.
+        Ok(())
     }
 
-    async fn process_fail_stop(&mut self, _commit: Commit) {
+    async fn process_fail_stop(&mut self, _commit: Commit) -> Result<(), BounceError> {
         // Does nothing
+        Ok(())
     }
 
-    async fn process_honest(&mut self, mut commit: Commit) {
-        match self.slot_info.phase {
-            Phase::First => {
-                // Phase 1 only handles precommits
-                if commit.typ() == CommitType::Precommit {
-                    if !self.slot_info.signed {
-                        commit = self.sign_and_broadcast(commit).await;
-                    }
-
-                    // Now, the precommit is the one signed by me or other cubesats.
-                    self.slot_info.precommits.push(commit.clone());
-                }
+    async fn process_honest(&mut self, commit: Commit) -> Result<(), BounceError> {
+        if !commit.requests.is_empty() {
+            // Only sign a proposal if every request in it comes from an authorized ground station
+            // and is not a replay, otherwise this unit signs a noncommit when the third phase
+            // begins. Proposals only get here in the first two phases, before this unit signed.
+            let now_ms = unix_time_ms();
+            self.check_proposal(&commit, now_ms)?;
+            for request in &commit.requests {
+                self.replay_cache
+                    .admit(request, now_ms)
+                    .map_err(BounceError::ReplayCache)?;
             }
-            Phase::Second => {
-                // Sign
-                if !self.slot_info.signed {
-                    commit = self.sign_and_broadcast(commit).await;
-                }
-
-                if commit.typ() == CommitType::Precommit {
-                    self.slot_info.precommits.push(commit.clone());
-                } else if commit.typ() == CommitType::Noncommit {
-                    self.slot_info.noncommits.push(commit.clone());
-                }
+            self.slot_info.proposal = Some(commit.msg.clone());
+            let vote = self
+                .sign_and_broadcast(Commit {
+                    requests: Vec::new(),
+                    ..commit
+                })
+                .await?;
+            self.record_vote(&vote)?;
+        } else {
+            // Phase 1 only handles precommits
+            if self.slot_info.phase == Phase::First && commit.typ() == CommitType::Noncommit {
+                return Ok(());
             }
-            Phase::Third => {
-                // At the beginning of the Phase 3, this Bounce unit has signed and broadcast
-                // a noncommit, so it will only listen to others' commits.
-                if commit.typ() == CommitType::Precommit {
-                    self.slot_info.precommits.push(commit.clone());
-                } else if commit.typ() == CommitType::Noncommit {
-                    self.slot_info.noncommits.push(commit.clone());
-                }
+            // The vote of another unit is verified before anything else, and only makes this unit
+            // sign in the first two phases if it endorses the message. At the beginning of the
+            // third phase, this unit has signed and broadcast a noncommit.
+            self.record_vote(&commit)?;
+            if !self.slot_info.signed
+                && self.slot_info.phase != Phase::Third
+                && self.endorses(&commit)
+            {
+                let vote = self
+                    .sign_and_broadcast(Commit {
+                        j: self.slot_info.j,
+                        signers: Vec::new(),
+                        ..commit
+                    })
+                    .await?;
+                self.record_vote(&vote)?;
             }
-            Phase::Stop => {
-                unreachable!("Handled Stop phase earlier in the function.");
+        }
+
+        let supermajority = supermajority(self.num_cubesats as usize);
+        let typ = if self.slot_info.precommits.len() >= supermajority {
+            CommitType::Precommit
+        } else if self.slot_info.noncommits.len() >= supermajority {
+            CommitType::Noncommit
+        } else {
+            return Ok(());
+        };
+        self.aggregate_and_broadcast(Commit {
+            typ: typ.into(),
+            j: self.slot_info.j,
+            ..Default::default()
+        })
+        .await
+    }
+
+    async fn handle_tick(&mut self, tick: Tick) -> Result<(), BounceError> {
+        let current = Tick {
+            i: self.slot_info.i,
+            phase: self.slot_info.phase.clone(),
+        };
+        if let (Some(current), Some(next)) = (current.ordinal(), tick.ordinal()) {
+            if next <= current {
+                // This transition has already been applied.
+                return Ok(());
             }
+
+            let missed = next - current - 1;
+            if missed > 0 {
+                warn!(
+                    "Slot {}\tBounce Unit {}\tMissed {} phase transitions, moving to slot {} {:?} phase",
+                    self.slot_info.i, self.id, missed, tick.i, tick.phase,
+                );
+                self.metrics.add_missed_ticks(missed);
+            }
+        }
+
+        // Either a new slot starts as scheduled, or this Bounce unit fell behind and jumps
+        // straight into the middle of the current slot.
+        if tick.phase == Phase::First || tick.i != self.slot_info.i {
+            self.slot_info.advance_to(tick.i);
+        }
+        if tick.phase == Phase::First {
+            info!(
+                "Slot {}\tBounce Unit {}\tFirst Phase Starts",
+                self.slot_info.i, self.id,
+            );
         }
 
-        if self.slot_info.precommits.len() >= supermajority(self.num_cubesats as usize) {
-            self.aggregate_and_broadcast(commit).await;
-        } else if self.slot_info.noncommits.len() >= supermajority(self.num_cubesats as usize) {
-            self.aggregate_and_broadcast(commit).await;
+        if tick.phase == Phase::Third && !self.slot_info.signed {
+            // Sign and broadcast noncommit for (j+1, i)
+            let noncommit = Commit {
+                typ: CommitType::Noncommit.into(),
+                i: self.slot_info.i,
+                j: self.slot_info.j,
+                msg: self.noncommit_msg(),
+                public_key: self.public_key.clone(),
+                signature: Vec::new(),
+                aggregated: false,
+                signer_id: self.id as u32,
+                requests: Vec::new(),
+                timestamp_ms: 0,
+                nonce: 0,
+                scheme: self.registry.scheme().id().into(),
+                signers: Vec::new(),
+            };
+            // The phase changes even if this Bounce unit fails to sign.
+            self.slot_info.phase = tick.phase;
+            let noncommit = self.sign_and_broadcast(noncommit).await?;
+            return self.record_vote(&noncommit);
         }
+
+        self.slot_info.phase = tick.phase;
+        Ok(())
+    }
+
+    fn warn_lagged(&self, n: u64) {
+        // The next tick received tells which slot and phase it is now.
+        warn!(
+            "Slot {}\tBounce Unit {}\tLagged behind the timer by {} phase transitions",
+            self.slot_info.i, self.id, n,
+        );
     }
 
-    pub async fn run(&mut self) {
+    /// Runs the Bounce unit until the timer stops, or until an error stops it.
+    pub async fn run(&mut self) -> Result<(), BounceError> {
         loop {
             tokio::select! {
-                Ok(phase) = self.timer_rx.recv() => {
-                    match phase {
-                        Phase::First => {
-                            self.slot_info.next();
-                            info!(
-                                "Slot {}\tBounce Unit {}\tFirst Phase Starts",
-                                self.slot_info.i,
-                                self.id,
-                            );
-                        }
-                        Phase::Second => {
-                        }
-                        Phase::Third => {
-                            if !self.slot_info.signed {
-                                // Sign and broadcast noncommit for (j+1, i)
-
-                                let msg = format!("noncommit({}, {})", self.slot_info.j + 1, self.slot_info.i);
-
-                                let noncommit = Commit {
-                                    typ: CommitType::Noncommit.into(),
-                                    i: self.slot_info.i,
-                                    j: self.slot_info.j,
-                                    msg: msg.clone().into_bytes(),
-                                    public_key: self.public_key.clone(),
-                                    signature: Bn256.sign(&self.private_key, &msg.as_bytes()).unwrap(),
-                                    aggregated: false,
-                                    signer_id: self.id as u32,
-                                };
-                                self.sign_and_broadcast(
-                                    noncommit.clone()
-                                ).await;
-                                self.slot_info.noncommits.push(noncommit);
+                tick = self.timer_rx.recv() => {
+                    match tick {
+                        Ok(tick) => {
+                            if let Err(e) = self.handle_tick(tick).await {
+                                self.handle_error(e)?;
                             }
                         }
-                        Phase::Stop => {
-
-                        }
+                        Err(RecvError::Lagged(n)) => self.warn_lagged(n),
+                        Err(RecvError::Closed) => return Ok(()),
                     }
-                    self.slot_info.phase = phase;
                 }
-                Some(commit) = self.request_rx.recv() => {
-                    self.process(commit).await;
+                Some(commit) = self.link.recv() => {
+                    // Apply phase transitions that are already due first, so that a commit sent
+                    // right after a transition, e.g. a proposal for the new slot, is never
+                    // processed in the previous phase.
+                    loop {
+                        match self.timer_rx.try_recv() {
+                            Ok(tick) => {
+                                if let Err(e) = self.handle_tick(tick).await {
+                                    self.handle_error(e)?;
+                                }
+                            }
+                            Err(TryRecvError::Lagged(n)) => self.warn_lagged(n),
+                            Err(_) => break,
+                        }
+                    }
+                    if let Err(e) = self.process(commit).await {
+                        self.handle_error(e)?;
+                    }
                 }
             }
         }
@@ -301,69 +601,115 @@ impl Cubesat {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bls_signatures_rs::MultiSignature;
+    use crate::{
+        new_lms_private_key, new_member, sign_proposal, sign_request, timer, unix_time_ms,
+        verify_response, Bls12381Scheme, Bn256Scheme, BounceResponse, ChannelLink, FlockControl,
+        KeySigner, SchemeId, SignatureScheme, SlotSchedule, LMS_SCHEME, LMS_SHA256_M32_H5,
+        SPACE_STATION,
+    };
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+    use tokio::sync::mpsc;
+
+    const GROUND_STATION_PRIVATE_KEY: [u8; 32] = [9; 32];
+    const SPACE_STATION_PRIVATE_KEY: [u8; 32] = [8; 32];
+
+    // Private key of the Bounce unit k of the flock in tests.
+    fn member_key(k: u32) -> Vec<u8> {
+        vec![k as u8 + 1; 32]
+    }
+
+    fn member_signer(k: u32) -> Box<dyn Signer> {
+        Box::new(KeySigner::new(&Bn256Scheme, member_key(k)).unwrap())
+    }
+
+    // The link of a Bounce unit run by a space station reading `result_rx` and writing `request_tx`.
+    fn channel_link(
+        result_tx: mpsc::Sender<Commit>,
+        request_rx: mpsc::Receiver<Commit>,
+    ) -> Box<dyn Link> {
+        Box::new(ChannelLink::new(request_rx).with_peer(SPACE_STATION, result_tx))
+    }
+
+    fn config(num_cubesats: u32) -> BounceConfig {
+        BounceConfig {
+            num_cubesats,
+            members: (0..num_cubesats)
+                .map(|k| new_member(&Bn256Scheme, k, &member_key(k)))
+                .collect(),
+            ground_station_keys: vec![Bn256Scheme
+                .derive_public_key(&GROUND_STATION_PRIVATE_KEY)
+                .unwrap()],
+            request_window_ms: 60_000,
+            replay_cache_size: 100,
+            space_station_key: Bn256Scheme
+                .derive_public_key(&SPACE_STATION_PRIVATE_KEY)
+                .unwrap(),
+            ..Default::default()
+        }
+    }
 
     #[tokio::test]
-    async fn cubesat_sign_aggregate() {
-        let (result_tx, mut result_rx) = mpsc::channel(1);
-        let (request_tx, request_rx) = mpsc::channel(15);
+    async fn unauthenticated_precommit_is_not_signed() {
+        let (result_tx, mut result_rx) = mpsc::channel(5);
+        let (_request_tx, request_rx) = mpsc::channel(15);
         let (_timer_tx, _timer_rx) = broadcast::channel(15);
 
-        let mut c = Cubesat::new(0, 1, result_tx, request_rx, _timer_rx, FailureMode::Honest);
+        let mut c = Cubesat::new(
+            0,
+            &config(3),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
         c.slot_info.phase = Phase::First;
 
-        tokio::spawn(async move {
-            c.run().await;
-        });
-
         let msg = "hello".as_bytes().to_vec();
-
-        let mut rng = thread_rng();
-        let ground_station_private_key: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
-        let ground_station_public_key = Bn256
-            .derive_public_key(&ground_station_private_key)
-            .unwrap();
-        let signature = Bn256.sign(&ground_station_private_key, &msg).unwrap();
-
-        let precommit = Commit {
+        let precommit = |private_key: &[u8]| Commit {
             typ: CommitType::Precommit.into(),
-            i: 0,
-            j: 0,
             msg: msg.clone(),
-            public_key: ground_station_public_key,
-            signature,
-            aggregated: false,
-            signer_id: 0,
+            public_key: Bn256Scheme.derive_public_key(private_key).unwrap(),
+            signature: Bn256Scheme.sign(private_key, &msg).unwrap(),
+            scheme: SchemeId::Bn256.into(),
+            ..Default::default()
         };
 
-        tokio::spawn(async move {
-            request_tx
-                .send(precommit)
-                .await
-                .expect("failed to send precommit");
-        });
-
-        let result_opt = result_rx.recv().await;
-        assert!(result_opt.is_some());
-        let commit = result_opt.unwrap();
-
-        assert_eq!(commit.typ(), CommitType::Precommit);
-        assert_eq!(commit.i, 0);
-        assert_eq!(commit.msg, msg);
-        assert!(!commit.aggregated);
-
-        let result_opt = result_rx.recv().await;
-        assert!(result_opt.is_some());
-        let commit = result_opt.unwrap();
+        // A bare precommit signed by a key outside the flock, e.g. a ground station that is not on
+        // the allow-list, is refused before anything is signed.
+        let mut rng = thread_rng();
+        let outsider: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
+        assert_eq!(
+            c.process(precommit(&outsider)).await,
+            Err(BounceError::Vote(0, VoteError::UnknownSigner))
+        );
 
-        assert_eq!(commit.typ(), CommitType::Precommit);
-        assert_eq!(commit.i, 0);
-        assert_eq!(commit.msg, msg);
-        assert!(commit.aggregated);
+        // The precommit of a member is counted, but this unit only signs a root it checked the
+        // proposal of.
+        c.process(precommit(&member_key(1))).await.unwrap();
+        assert_eq!(c.slot_info.precommits.len(), 1);
+        assert!(!c.slot_info.signed);
+        assert!(!c.slot_info.aggregated);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), result_rx.recv())
+                .await
+                .is_err()
+        );
 
-        let _ = Bn256
-            .verify(&commit.signature, &msg, &commit.public_key)
-            .unwrap();
+        // Neither is a certificate that does not come from a supermajority of the members taken
+        // for the slot.
+        let mut certificate = precommit(&member_key(1));
+        certificate.aggregated = true;
+        certificate.signers = vec![0b010];
+        assert_eq!(
+            c.process(certificate).await,
+            Err(BounceError::Certificate(
+                0,
+                CertificateError::NoSupermajority(1)
+            ))
+        );
+        assert!(!c.slot_info.aggregated);
     }
 
     #[tokio::test]
@@ -372,7 +718,15 @@ mod tests {
         let (_request_tx, request_rx) = mpsc::channel(15);
         let (_timer_tx, _timer_rx) = broadcast::channel(15);
 
-        let mut c = Cubesat::new(0, 1, result_tx, request_rx, _timer_rx, FailureMode::Honest);
+        let mut c = Cubesat::new(
+            0,
+            &config(1),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
 
         c.slot_info.phase = Phase::First;
 
@@ -388,9 +742,14 @@ mod tests {
             msg: Vec::new(),
             signature: Vec::new(),
             signer_id: 0,
+            requests: Vec::new(),
+            timestamp_ms: 0,
+            nonce: 0,
+            scheme: SchemeId::Bn256.into(),
+            signers: Vec::new(),
         };
 
-        c.process(noncommit).await;
+        c.process(noncommit).await.unwrap();
 
         assert!(!c.slot_info.signed);
         assert!(!c.slot_info.aggregated);
@@ -398,7 +757,7 @@ mod tests {
 
     #[tokio::test]
     async fn phase2_commit_noncommit() {
-        // Phase 2, first send commit, then noncommit. Then the Bounce unit should sign the commit
+        // Phase 2, first a proposal, then a noncommit. Then the Bounce unit should sign the root
         // and broadcast. If it receives the noncommit right after, then bounce unit should not sign
         // the noncommit and it only needs to keep track that it has received a noncommit.
 
@@ -406,7 +765,15 @@ mod tests {
         let (_request_tx, request_rx) = mpsc::channel(15);
         let (_timer_tx, _timer_rx) = broadcast::channel(15);
 
-        let mut c = Cubesat::new(0, 3, result_tx, request_rx, _timer_rx, FailureMode::Honest);
+        let mut c = Cubesat::new(
+            0,
+            &config(3),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
 
         c.slot_info.phase = Phase::Second;
 
@@ -414,25 +781,10 @@ mod tests {
         assert!(!c.slot_info.aggregated);
         assert!(c.slot_info.precommits.is_empty());
 
-        let msg = "hello".as_bytes().to_vec();
+        let proposal = proposal(0, &["hello"]);
+        let root = proposal.msg.clone();
 
-        let mut rng = thread_rng();
-        let cubesat1_private_key: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
-        let cubesat1_public_key = Bn256.derive_public_key(&cubesat1_private_key).unwrap();
-        let signature = Bn256.sign(&cubesat1_private_key, &msg).unwrap();
-
-        let precommit = Commit {
-            typ: CommitType::Precommit.into(),
-            i: 0,
-            j: 0,
-            msg: msg.clone(),
-            public_key: cubesat1_public_key,
-            signature,
-            aggregated: false,
-            signer_id: 0,
-        };
-
-        c.process(precommit).await;
+        c.process(proposal).await.unwrap();
         assert!(c.slot_info.signed);
         assert!(!c.slot_info.aggregated);
         assert_eq!(c.slot_info.precommits.len(), 1);
@@ -442,26 +794,39 @@ mod tests {
         let commit = result_opt.unwrap();
         assert_eq!(commit.typ(), CommitType::Precommit);
         assert_eq!(commit.i, 0);
-        assert_eq!(commit.msg, msg);
+        assert_eq!(commit.msg, root);
         assert_eq!(commit.public_key, c.public_key);
 
-        let cubesat2_private_key: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
-        let cubesat2_public_key = Bn256.derive_public_key(&cubesat2_private_key).unwrap();
-        let signature = Bn256.sign(&cubesat2_private_key, &msg).unwrap();
+        let msg = c.noncommit_msg();
+        let cubesat2_private_key = member_key(2);
+        let cubesat2_public_key = Bn256Scheme
+            .derive_public_key(&cubesat2_private_key)
+            .unwrap();
+        let signature = Bn256Scheme.sign(&cubesat2_private_key, &msg).unwrap();
 
         let noncommit = Commit {
             typ: CommitType::Noncommit.into(),
-            i: 1,
+            i: 0,
             j: 0,
             msg: msg.clone(),
             public_key: cubesat2_public_key,
             signature,
             aggregated: false,
-            signer_id: 0,
+            signer_id: 2,
+            requests: Vec::new(),
+            timestamp_ms: 0,
+            nonce: 0,
+            scheme: SchemeId::Bn256.into(),
+            signers: Vec::new(),
         };
 
-        c.process(noncommit).await;
+        c.process(noncommit).await.unwrap();
         assert_eq!(1, c.slot_info.noncommits.len());
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), result_rx.recv())
+                .await
+                .is_err()
+        );
     }
 
     #[tokio::test]
@@ -473,7 +838,15 @@ mod tests {
         let (_request_tx, request_rx) = mpsc::channel(15);
         let (_timer_tx, _timer_rx) = broadcast::channel(15);
 
-        let mut c = Cubesat::new(0, 3, result_tx, request_rx, _timer_rx, FailureMode::Honest);
+        let mut c = Cubesat::new(
+            0,
+            &config(4),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
 
         c.slot_info.phase = Phase::Second;
 
@@ -481,28 +854,34 @@ mod tests {
         assert!(!c.slot_info.aggregated);
         assert!(c.slot_info.precommits.is_empty());
 
-        let msg = "hello".as_bytes().to_vec();
+        let msg = c.noncommit_msg();
 
-        let mut rng = thread_rng();
-        let cubesat1_private_key: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
-        let cubesat1_public_key = Bn256.derive_public_key(&cubesat1_private_key).unwrap();
-        let signature = Bn256.sign(&cubesat1_private_key, &msg).unwrap();
+        let cubesat1_private_key = member_key(1);
+        let cubesat1_public_key = Bn256Scheme
+            .derive_public_key(&cubesat1_private_key)
+            .unwrap();
+        let signature = Bn256Scheme.sign(&cubesat1_private_key, &msg).unwrap();
 
         let noncommit = Commit {
             typ: CommitType::Noncommit.into(),
-            i: 1,
+            i: 0,
             j: 0,
             msg: msg.clone(),
             public_key: cubesat1_public_key,
             signature,
             aggregated: false,
-            signer_id: 0,
+            signer_id: 1,
+            requests: Vec::new(),
+            timestamp_ms: 0,
+            nonce: 0,
+            scheme: SchemeId::Bn256.into(),
+            signers: Vec::new(),
         };
 
-        c.process(noncommit).await;
+        c.process(noncommit).await.unwrap();
         assert!(c.slot_info.signed);
         assert!(!c.slot_info.aggregated);
-        assert_eq!(c.slot_info.noncommits.len(), 1);
+        assert_eq!(c.slot_info.noncommits.len(), 2);
 
         let result_opt = result_rx.recv().await;
         assert!(result_opt.is_some());
@@ -512,23 +891,32 @@ mod tests {
         assert_eq!(commit.msg, msg);
         assert_eq!(commit.public_key, c.public_key);
 
-        let cubesat2_private_key: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
-        let cubesat2_public_key = Bn256.derive_public_key(&cubesat2_private_key).unwrap();
-        let signature = Bn256.sign(&cubesat2_private_key, &msg).unwrap();
+        let msg = "hello".as_bytes().to_vec();
+        let cubesat2_private_key = member_key(2);
+        let cubesat2_public_key = Bn256Scheme
+            .derive_public_key(&cubesat2_private_key)
+            .unwrap();
+        let signature = Bn256Scheme.sign(&cubesat2_private_key, &msg).unwrap();
 
         let precommit = Commit {
             typ: CommitType::Precommit.into(),
-            i: 1,
+            i: 0,
             j: 0,
             msg: msg.clone(),
             public_key: cubesat2_public_key,
             signature,
             aggregated: false,
-            signer_id: 0,
+            signer_id: 2,
+            requests: Vec::new(),
+            timestamp_ms: 0,
+            nonce: 0,
+            scheme: SchemeId::Bn256.into(),
+            signers: Vec::new(),
         };
 
-        c.process(precommit).await;
-        assert_eq!(1, c.slot_info.noncommits.len());
+        c.process(precommit).await.unwrap();
+        assert_eq!(1, c.slot_info.precommits.len());
+        assert_eq!(2, c.slot_info.noncommits.len());
     }
 
     #[tokio::test]
@@ -538,7 +926,15 @@ mod tests {
         let (_request_tx, request_rx) = mpsc::channel(15);
         let (_timer_tx, _timer_rx) = broadcast::channel(15);
 
-        let mut c = Cubesat::new(0, 1, result_tx, request_rx, _timer_rx, FailureMode::Honest);
+        let mut c = Cubesat::new(
+            0,
+            &config(1),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
 
         c.slot_info.phase = Phase::Second;
 
@@ -546,25 +942,7 @@ mod tests {
         assert!(!c.slot_info.aggregated);
         assert!(c.slot_info.precommits.is_empty());
 
-        let msg = "hello".as_bytes().to_vec();
-
-        let mut rng = thread_rng();
-        let cubesat1_private_key: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
-        let cubesat1_public_key = Bn256.derive_public_key(&cubesat1_private_key).unwrap();
-        let signature = Bn256.sign(&cubesat1_private_key, &msg).unwrap();
-
-        let precommit = Commit {
-            typ: CommitType::Precommit.into(),
-            i: 1,
-            j: 0,
-            msg: msg.clone(),
-            public_key: cubesat1_public_key,
-            signature,
-            aggregated: false,
-            signer_id: 0,
-        };
-
-        c.process(precommit).await;
+        c.process(proposal(0, &["hello"])).await.unwrap();
         assert!(c.slot_info.signed);
         assert!(c.slot_info.aggregated);
         assert_eq!(c.slot_info.precommits.len(), 1);
@@ -577,7 +955,15 @@ mod tests {
         let (_request_tx, request_rx) = mpsc::channel(15);
         let (_timer_tx, _timer_rx) = broadcast::channel(15);
 
-        let mut c = Cubesat::new(0, 1, result_tx, request_rx, _timer_rx, FailureMode::Honest);
+        let mut c = Cubesat::new(
+            0,
+            &config(2),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
 
         c.slot_info.phase = Phase::Second;
 
@@ -585,28 +971,34 @@ mod tests {
         assert!(!c.slot_info.aggregated);
         assert!(c.slot_info.noncommits.is_empty());
 
-        let msg = "hello".as_bytes().to_vec();
+        let msg = c.noncommit_msg();
 
-        let mut rng = thread_rng();
-        let cubesat1_private_key: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
-        let cubesat1_public_key = Bn256.derive_public_key(&cubesat1_private_key).unwrap();
-        let signature = Bn256.sign(&cubesat1_private_key, &msg).unwrap();
+        let cubesat1_private_key = member_key(1);
+        let cubesat1_public_key = Bn256Scheme
+            .derive_public_key(&cubesat1_private_key)
+            .unwrap();
+        let signature = Bn256Scheme.sign(&cubesat1_private_key, &msg).unwrap();
 
         let noncommit = Commit {
             typ: CommitType::Noncommit.into(),
-            i: 1,
+            i: 0,
             j: 0,
             msg: msg.clone(),
             public_key: cubesat1_public_key,
             signature,
             aggregated: false,
-            signer_id: 0,
+            signer_id: 1,
+            requests: Vec::new(),
+            timestamp_ms: 0,
+            nonce: 0,
+            scheme: SchemeId::Bn256.into(),
+            signers: Vec::new(),
         };
 
-        c.process(noncommit).await;
+        c.process(noncommit).await.unwrap();
         assert!(c.slot_info.signed);
         assert!(c.slot_info.aggregated);
-        assert_eq!(c.slot_info.noncommits.len(), 1);
+        assert_eq!(c.slot_info.noncommits.len(), 2);
     }
 
     #[tokio::test]
@@ -615,7 +1007,15 @@ mod tests {
         let (_request_tx, request_rx) = mpsc::channel(15);
         let (_timer_tx, _timer_rx) = broadcast::channel(15);
 
-        let mut c = Cubesat::new(0, 3, result_tx, request_rx, _timer_rx, FailureMode::Honest);
+        let mut c = Cubesat::new(
+            0,
+            &config(3),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
         // Assume that this Bounce unit has entered into the third phase, and signed a noncommit.
         c.slot_info.phase = Phase::Third;
         let msg = format!("noncommit({}, {})", c.slot_info.j + 1, c.slot_info.i);
@@ -625,21 +1025,27 @@ mod tests {
             j: c.slot_info.j,
             msg: msg.clone().into_bytes(),
             public_key: c.public_key.clone(),
-            signature: Bn256.sign(&c.private_key, &msg.as_bytes()).unwrap(),
+            signature: c.signer.sign(msg.as_bytes()).unwrap(),
             aggregated: false,
             signer_id: 0,
+            requests: Vec::new(),
+            timestamp_ms: 0,
+            nonce: 0,
+            scheme: SchemeId::Bn256.into(),
+            signers: Vec::new(),
         };
 
-        c.sign_and_broadcast(noncommit.clone()).await;
-        c.slot_info.noncommits.push(noncommit);
+        c.sign_and_broadcast(noncommit.clone()).await.unwrap();
+        c.slot_info.noncommits.add(&noncommit, &c.registry).unwrap();
 
         let msg = "hello".as_bytes().to_vec();
 
         // Then another Bounce unit sends it precommit, and the Bounce unit just keeps track of it.
-        let mut rng = thread_rng();
-        let cubesat1_private_key: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
-        let cubesat1_public_key = Bn256.derive_public_key(&cubesat1_private_key).unwrap();
-        let signature = Bn256.sign(&cubesat1_private_key, &msg).unwrap();
+        let cubesat1_private_key = member_key(1);
+        let cubesat1_public_key = Bn256Scheme
+            .derive_public_key(&cubesat1_private_key)
+            .unwrap();
+        let signature = Bn256Scheme.sign(&cubesat1_private_key, &msg).unwrap();
 
         let precommit = Commit {
             typ: CommitType::Precommit.into(),
@@ -650,9 +1056,14 @@ mod tests {
             signature,
             aggregated: false,
             signer_id: 0,
+            requests: Vec::new(),
+            timestamp_ms: 0,
+            nonce: 0,
+            scheme: SchemeId::Bn256.into(),
+            signers: Vec::new(),
         };
 
-        c.process(precommit).await;
+        c.process(precommit).await.unwrap();
 
         assert!(!c.slot_info.aggregated);
         assert_eq!(c.slot_info.precommits.len(), 1);
@@ -665,7 +1076,15 @@ mod tests {
         let (_request_tx, request_rx) = mpsc::channel(15);
         let (_timer_tx, _timer_rx) = broadcast::channel(15);
 
-        let mut c = Cubesat::new(0, 3, result_tx, request_rx, _timer_rx, FailureMode::Honest);
+        let mut c = Cubesat::new(
+            0,
+            &config(3),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
 
         // Assume that this Bounce unit has entered into the third phase, and signed a noncommit.
         c.slot_info.phase = Phase::Third;
@@ -676,23 +1095,31 @@ mod tests {
             j: c.slot_info.j,
             msg: msg.clone().into_bytes(),
             public_key: c.public_key.clone(),
-            signature: Bn256.sign(&c.private_key, &msg.as_bytes()).unwrap(),
+            signature: c.signer.sign(msg.as_bytes()).unwrap(),
             aggregated: false,
             signer_id: 0,
+            requests: Vec::new(),
+            timestamp_ms: 0,
+            nonce: 0,
+            scheme: SchemeId::Bn256.into(),
+            signers: Vec::new(),
         };
 
-        c.sign_and_broadcast(noncommit.clone()).await;
-        c.slot_info.noncommits.push(noncommit);
+        c.sign_and_broadcast(noncommit.clone()).await.unwrap();
+        c.slot_info.noncommits.add(&noncommit, &c.registry).unwrap();
 
         assert!(c.slot_info.signed);
         assert!(!c.slot_info.aggregated);
         assert_eq!(c.slot_info.noncommits.len(), 1);
 
         // Then another Bounce unit sends it noncommit, which results in aggregation.
-        let mut rng = thread_rng();
-        let cubesat1_private_key: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
-        let cubesat1_public_key = Bn256.derive_public_key(&cubesat1_private_key).unwrap();
-        let signature = Bn256.sign(&cubesat1_private_key, &msg.as_bytes()).unwrap();
+        let cubesat1_private_key = member_key(1);
+        let cubesat1_public_key = Bn256Scheme
+            .derive_public_key(&cubesat1_private_key)
+            .unwrap();
+        let signature = Bn256Scheme
+            .sign(&cubesat1_private_key, msg.as_bytes())
+            .unwrap();
 
         let noncommit = Commit {
             typ: CommitType::Noncommit.into(),
@@ -703,11 +1130,776 @@ mod tests {
             signature,
             aggregated: false,
             signer_id: 0,
+            requests: Vec::new(),
+            timestamp_ms: 0,
+            nonce: 0,
+            scheme: SchemeId::Bn256.into(),
+            signers: Vec::new(),
         };
 
-        c.process(noncommit).await;
+        c.process(noncommit).await.unwrap();
         assert!(c.slot_info.signed);
         assert!(c.slot_info.aggregated);
         assert_eq!(c.slot_info.noncommits.len(), 2);
     }
+
+    #[tokio::test]
+    async fn missed_tick_in_current_slot() {
+        let (result_tx, mut result_rx) = mpsc::channel(5);
+        let (_request_tx, request_rx) = mpsc::channel(15);
+        let (_timer_tx, _timer_rx) = broadcast::channel(15);
+
+        let mut c = Cubesat::new(
+            0,
+            &config(3),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        c.slot_info.advance_to(2);
+
+        // The transition to the second phase is lost, and the unit directly observes the third
+        // phase, in which it still has to send its noncommit.
+        c.handle_tick(Tick {
+            i: 2,
+            phase: Phase::Third,
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(c.metrics().missed_ticks(), 1);
+        assert_eq!(c.slot_info.i, 2);
+        assert_eq!(c.slot_info.phase, Phase::Third);
+        assert!(c.slot_info.signed);
+
+        let commit = result_rx.recv().await.unwrap();
+        assert_eq!(commit.typ(), CommitType::Noncommit);
+        assert_eq!(commit.i, 2);
+
+        // A transition that has already been applied is ignored.
+        c.handle_tick(Tick {
+            i: 2,
+            phase: Phase::Second,
+        })
+        .await
+        .unwrap();
+        assert_eq!(c.slot_info.phase, Phase::Third);
+        assert_eq!(c.metrics().missed_ticks(), 1);
+    }
+
+    #[tokio::test]
+    async fn lagged_timer_moves_to_current_slot() {
+        tokio::time::pause();
+
+        let (result_tx, mut result_rx) = mpsc::channel(5);
+        let (_request_tx, request_rx) = mpsc::channel(15);
+        // Only the two most recent phase transitions are retained for a unit that falls behind.
+        let (timer_tx, timer_rx) = broadcast::channel(2);
+
+        let mut c = Cubesat::new(
+            0,
+            &config(3),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        let metrics = c.metrics();
+
+        let schedule = SlotSchedule::new(
+            SystemTime::now(),
+            Duration::from_millis(1000),
+            Duration::from_millis(400),
+            Duration::from_millis(300),
+        )
+        .unwrap();
+        let control = FlockControl::new();
+        let paused_rx = control.subscribe();
+        tokio::spawn(async move {
+            timer(timer_tx, schedule, paused_rx).await;
+        });
+
+        // The unit is not running while the timer goes through slots 0 to 2 and the first two
+        // phases of slot 3.
+        tokio::time::sleep(Duration::from_millis(3_500)).await;
+        c.slot_info.advance_to(0);
+
+        tokio::spawn(async move {
+            c.run().await.unwrap();
+        });
+
+        // Once it runs, the unit catches up with slot 3 and sends its noncommit when the third
+        // phase begins.
+        let commit = result_rx.recv().await.unwrap();
+        assert_eq!(commit.typ(), CommitType::Noncommit);
+        assert_eq!(commit.i, 3);
+        assert_eq!(commit.msg, "noncommit(1, 3)".as_bytes());
+        // The last two phases of slot 0 and all phases of slots 1 and 2 were never observed.
+        assert_eq!(metrics.missed_ticks(), 8);
+    }
+
+    #[tokio::test]
+    async fn stop_and_resume() {
+        let (result_tx, _result_rx) = mpsc::channel(5);
+        let (_request_tx, request_rx) = mpsc::channel(15);
+        let (_timer_tx, _timer_rx) = broadcast::channel(15);
+
+        let mut c = Cubesat::new(
+            0,
+            &config(1),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        c.slot_info.advance_to(2);
+
+        c.handle_tick(Tick {
+            i: 3,
+            phase: Phase::Stop,
+        })
+        .await
+        .unwrap();
+        assert_eq!(c.slot_info.phase, Phase::Stop);
+
+        // Commits are ignored while the flock is stopped.
+        let precommit = proposal(7, &["hello"]);
+        c.process(precommit.clone()).await.unwrap();
+        assert!(!c.slot_info.signed);
+        assert!(c.slot_info.precommits.is_empty());
+
+        // Slots that passed while stopped are not counted as missed.
+        c.handle_tick(Tick {
+            i: 7,
+            phase: Phase::First,
+        })
+        .await
+        .unwrap();
+        assert_eq!(c.slot_info.i, 7);
+        assert_eq!(c.slot_info.phase, Phase::First);
+        assert_eq!(c.metrics().missed_ticks(), 0);
+
+        c.process(precommit).await.unwrap();
+        assert!(c.slot_info.signed);
+        assert!(c.slot_info.aggregated);
+    }
+
+    fn proposal(i: u32, msgs: &[&str]) -> Commit {
+        let requests: Vec<Commit> = msgs
+            .iter()
+            .map(|msg| {
+                let mut request = Commit {
+                    typ: CommitType::Precommit.into(),
+                    msg: msg.as_bytes().to_vec(),
+                    timestamp_ms: unix_time_ms(),
+                    nonce: thread_rng().gen(),
+                    ..Default::default()
+                };
+                sign_request(&mut request, &Bn256Scheme, &GROUND_STATION_PRIVATE_KEY);
+                request
+            })
+            .collect();
+        let leaves: Vec<&[u8]> = requests.iter().map(|r| r.msg.as_slice()).collect();
+
+        let mut proposal = Commit {
+            typ: CommitType::Precommit.into(),
+            i,
+            j: 0,
+            msg: MerkleTree::new(&leaves).root(),
+            public_key: Vec::new(),
+            signature: Vec::new(),
+            aggregated: false,
+            signer_id: 0,
+            requests,
+            timestamp_ms: 0,
+            nonce: 0,
+            scheme: SchemeId::Bn256.into(),
+            signers: Vec::new(),
+        };
+        sign_proposal(&mut proposal, &Bn256Scheme, &SPACE_STATION_PRIVATE_KEY);
+        proposal
+    }
+
+    #[tokio::test]
+    async fn proposal_is_signed_but_not_counted() {
+        let (result_tx, mut result_rx) = mpsc::channel(5);
+        let (_request_tx, request_rx) = mpsc::channel(15);
+        let (_timer_tx, _timer_rx) = broadcast::channel(15);
+
+        let mut c = Cubesat::new(
+            0,
+            &config(3),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        c.slot_info.phase = Phase::First;
+
+        let proposal = proposal(0, &["hello", "world"]);
+        let root = proposal.msg.clone();
+        c.process(proposal.clone()).await.unwrap();
+
+        assert!(c.slot_info.signed);
+        assert_eq!(c.slot_info.precommits.len(), 1);
+
+        // Only the root is signed, and the batch is not sent along with the vote.
+        let commit = result_rx.recv().await.unwrap();
+        assert_eq!(commit.typ(), CommitType::Precommit);
+        assert_eq!(commit.msg, root);
+        assert_eq!(commit.public_key, c.public_key);
+        assert!(commit.requests.is_empty());
+        Bn256Scheme
+            .verify(&commit.signature, &root, &commit.public_key)
+            .unwrap();
+
+        // A proposal received again does not count as a vote of another unit.
+        c.process(proposal).await.unwrap();
+        assert_eq!(c.slot_info.precommits.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn forged_proposal_is_ignored() {
+        let (result_tx, _result_rx) = mpsc::channel(5);
+        let (_request_tx, request_rx) = mpsc::channel(15);
+        let (_timer_tx, _timer_rx) = broadcast::channel(15);
+
+        let mut c = Cubesat::new(
+            0,
+            &config(1),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        c.slot_info.phase = Phase::First;
+
+        // Only the space station may propose, even a batch of authorized requests.
+        let mut forged = proposal(0, &["hello", "world"]);
+        sign_proposal(&mut forged, &Bn256Scheme, &GROUND_STATION_PRIVATE_KEY);
+        let mut unsigned = proposal(0, &["hello", "world"]);
+        unsigned.signature.clear();
+        for proposal in [forged, unsigned] {
+            assert!(matches!(
+                c.process(proposal).await,
+                Err(BounceError::InvalidProposal(_))
+            ));
+        }
+
+        assert!(!c.slot_info.signed);
+        assert!(c.slot_info.precommits.is_empty());
+    }
+
+    #[tokio::test]
+    async fn proposal_with_wrong_root_is_ignored() {
+        let (result_tx, _result_rx) = mpsc::channel(5);
+        let (_request_tx, request_rx) = mpsc::channel(15);
+        let (_timer_tx, _timer_rx) = broadcast::channel(15);
+
+        let mut c = Cubesat::new(
+            0,
+            &config(1),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        c.slot_info.phase = Phase::First;
+
+        let mut proposal = proposal(0, &["hello", "world"]);
+        proposal.requests.pop();
+        assert!(matches!(
+            c.process(proposal).await,
+            Err(BounceError::InvalidProposal(_))
+        ));
+
+        assert!(!c.slot_info.signed);
+        assert!(c.slot_info.precommits.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unauthorized_proposal_ends_in_noncommit() {
+        let (result_tx, mut result_rx) = mpsc::channel(5);
+        let (_request_tx, request_rx) = mpsc::channel(15);
+        let (_timer_tx, _timer_rx) = broadcast::channel(15);
+
+        let mut c = Cubesat::new(
+            0,
+            &config(1),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        c.slot_info.phase = Phase::First;
+
+        // One request of the batch was signed by a ground station that is not on the allow-list.
+        let mut proposal = proposal(0, &["hello", "world"]);
+        sign_request(&mut proposal.requests[1], &Bn256Scheme, &[3; 32]);
+        assert!(matches!(
+            c.process(proposal.clone()).await,
+            Err(BounceError::InvalidProposal(_))
+        ));
+        assert!(!c.slot_info.signed);
+        assert!(c.slot_info.precommits.is_empty());
+
+        // A request whose signature does not match its message is rejected as well.
+        let mut forged = proposal;
+        sign_request(
+            &mut forged.requests[1],
+            &Bn256Scheme,
+            &GROUND_STATION_PRIVATE_KEY,
+        );
+        forged.requests[1].signature = forged.requests[0].signature.clone();
+        assert!(matches!(
+            c.process(forged).await,
+            Err(BounceError::InvalidProposal(_))
+        ));
+        assert!(!c.slot_info.signed);
+
+        c.handle_tick(Tick {
+            i: 0,
+            phase: Phase::Third,
+        })
+        .await
+        .unwrap();
+        let commit = result_rx.recv().await.unwrap();
+        assert_eq!(commit.typ(), CommitType::Noncommit);
+        assert_eq!(commit.public_key, c.public_key);
+    }
+
+    #[tokio::test]
+    async fn replayed_request_is_not_signed_again() {
+        let (result_tx, _result_rx) = mpsc::channel(5);
+        let (_request_tx, request_rx) = mpsc::channel(15);
+        let (_timer_tx, _timer_rx) = broadcast::channel(15);
+
+        let mut c = Cubesat::new(
+            0,
+            &config(3),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        c.slot_info.phase = Phase::First;
+
+        let proposal = proposal(0, &["hello", "world"]);
+        c.process(proposal.clone()).await.unwrap();
+        assert!(c.slot_info.signed);
+
+        // The same signed requests proposed again in a later slot are refused.
+        c.slot_info.advance_to(1);
+        let mut replay = proposal.clone();
+        replay.i = 1;
+        sign_proposal(&mut replay, &Bn256Scheme, &SPACE_STATION_PRIVATE_KEY);
+        assert!(matches!(
+            c.process(replay).await,
+            Err(BounceError::InvalidProposal(_))
+        ));
+        assert!(!c.slot_info.signed);
+
+        // So is a request whose timestamp is outside the freshness window.
+        let mut stale = proposal;
+        stale.i = 1;
+        sign_proposal(&mut stale, &Bn256Scheme, &SPACE_STATION_PRIVATE_KEY);
+        for request in &mut stale.requests {
+            request.timestamp_ms -= 120_000;
+            sign_request(request, &Bn256Scheme, &GROUND_STATION_PRIVATE_KEY);
+        }
+        assert!(matches!(
+            c.process(stale).await,
+            Err(BounceError::InvalidProposal(_))
+        ));
+        assert!(!c.slot_info.signed);
+    }
+
+    #[tokio::test]
+    async fn replay_cache_survives_restart() {
+        let dir =
+            std::env::temp_dir().join(format!("bounce-cubesat-{}", thread_rng().gen::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("replay-cache");
+        let bounce_config = config(3);
+        let restart = || {
+            let (result_tx, result_rx) = mpsc::channel(5);
+            let replay_cache = ReplayCache::open(
+                &path,
+                bounce_config.request_window_ms,
+                bounce_config.replay_cache_size as usize,
+                unix_time_ms(),
+            )
+            .unwrap();
+            let mut c = Cubesat::new(
+                0,
+                &bounce_config,
+                member_signer(0),
+                channel_link(result_tx, mpsc::channel(1).1),
+                broadcast::channel(1).1,
+                FailureMode::Honest,
+            )
+            .unwrap()
+            .with_replay_cache(replay_cache);
+            c.slot_info.phase = Phase::First;
+            (c, result_rx)
+        };
+
+        let proposal = proposal(0, &["hello"]);
+        let (mut c, _result_rx) = restart();
+        c.process(proposal.clone()).await.unwrap();
+        assert!(c.slot_info.signed);
+        drop(c);
+
+        // After a restart, the unit still refuses to sign the same request again.
+        let (mut c, _result_rx) = restart();
+        c.slot_info.advance_to(1);
+        let mut replay = proposal;
+        replay.i = 1;
+        sign_proposal(&mut replay, &Bn256Scheme, &SPACE_STATION_PRIVATE_KEY);
+        assert!(matches!(
+            c.process(replay).await,
+            Err(BounceError::InvalidProposal(_))
+        ));
+        assert!(!c.slot_info.signed);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unsaved_request_is_not_signed() {
+        let dir =
+            std::env::temp_dir().join(format!("bounce-cubesat-{}", thread_rng().gen::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        let bounce_config = BounceConfig {
+            replay_cache_size: 1,
+            ..config(3)
+        };
+        let replay_cache = ReplayCache::open(
+            dir.join("replay-cache"),
+            bounce_config.request_window_ms,
+            bounce_config.replay_cache_size as usize,
+            unix_time_ms(),
+        )
+        .unwrap();
+        let (result_tx, mut result_rx) = mpsc::channel(5);
+        let mut c = Cubesat::new(
+            0,
+            &bounce_config,
+            member_signer(0),
+            channel_link(result_tx, mpsc::channel(1).1),
+            broadcast::channel(1).1,
+            FailureMode::Honest,
+        )
+        .unwrap()
+        .with_replay_cache(replay_cache);
+        c.slot_info.phase = Phase::First;
+
+        // The file of the cache is compacted on the third request, which fails without its
+        // directory.
+        fs::remove_dir_all(&dir).unwrap();
+        let mut proposal = proposal(0, &["a", "b", "c"]);
+        let now_ms = unix_time_ms();
+        for (k, request) in proposal.requests.iter_mut().enumerate() {
+            request.timestamp_ms = now_ms + k as u64;
+            sign_request(request, &Bn256Scheme, &GROUND_STATION_PRIVATE_KEY);
+        }
+        assert!(matches!(
+            c.process(proposal).await,
+            Err(BounceError::ReplayCache(ReplayError::Persist(_)))
+        ));
+        assert!(!c.slot_info.signed);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), result_rx.recv())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn only_members_are_aggregated() {
+        let (result_tx, _result_rx) = mpsc::channel(5);
+        let (_request_tx, request_rx) = mpsc::channel(15);
+        let (_timer_tx, _timer_rx) = broadcast::channel(15);
+
+        assert!(matches!(
+            Cubesat::new(
+                0,
+                &config(3),
+                Box::new(KeySigner::new(&Bn256Scheme, vec![50; 32]).unwrap()),
+                channel_link(result_tx.clone(), mpsc::channel(1).1),
+                _timer_tx.subscribe(),
+                FailureMode::Honest,
+            ),
+            Err(RegistryError::NotAMember)
+        ));
+
+        let mut c = Cubesat::new(
+            0,
+            &config(3),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        c.slot_info.phase = Phase::Third;
+        c.slot_info.signed = true;
+
+        let msg = b"hello".to_vec();
+        let vote = |private_key: &[u8]| Commit {
+            typ: CommitType::Precommit.into(),
+            msg: msg.clone(),
+            public_key: Bn256Scheme.derive_public_key(private_key).unwrap(),
+            signature: Bn256Scheme.sign(private_key, &msg).unwrap(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            c.process(vote(&[50; 32])).await,
+            Err(BounceError::Vote(0, VoteError::UnknownSigner))
+        );
+        assert!(c.slot_info.precommits.is_empty());
+
+        c.process(vote(&member_key(1))).await.unwrap();
+        assert_eq!(c.slot_info.precommits.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn malformed_votes_do_not_stop_the_unit() {
+        let (result_tx, mut result_rx) = mpsc::channel(5);
+        let (request_tx, request_rx) = mpsc::channel(15);
+        let (_timer_tx, _timer_rx) = broadcast::channel(15);
+
+        let mut c = Cubesat::new(
+            0,
+            &config(3),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        c.slot_info.phase = Phase::Third;
+        c.slot_info.signed = true;
+        let metrics = c.metrics();
+
+        let msg = b"hello".to_vec();
+        let vote = |k: u32| Commit {
+            typ: CommitType::Precommit.into(),
+            msg: msg.clone(),
+            public_key: Bn256Scheme.derive_public_key(&member_key(k)).unwrap(),
+            signature: Bn256Scheme.sign(&member_key(k), &msg).unwrap(),
+            signer_id: k,
+            ..Default::default()
+        };
+        let mut malformed_key = vote(1);
+        malformed_key.public_key = vec![7; 5];
+        let mut malformed_signature = vote(2);
+        malformed_signature.signature = vec![7; 5];
+        let mut truncated_signature = vote(2);
+        truncated_signature.signature.pop();
+
+        assert_eq!(
+            c.process(malformed_key.clone()).await,
+            Err(BounceError::Vote(1, VoteError::UnknownSigner))
+        );
+        assert_eq!(
+            c.process(malformed_signature.clone()).await,
+            Err(BounceError::Vote(2, VoteError::BadSignature))
+        );
+        assert!(c.slot_info.precommits.is_empty());
+
+        tokio::spawn(async move {
+            c.run().await.unwrap();
+        });
+        for commit in [
+            malformed_key,
+            malformed_signature,
+            truncated_signature,
+            vote(1),
+            vote(1),
+            vote(2),
+        ] {
+            request_tx.send(commit).await.unwrap();
+        }
+
+        // The unit drops the invalid votes and still aggregates the valid ones.
+        let commit = result_rx.recv().await.unwrap();
+        assert!(commit.aggregated);
+        assert_eq!(commit.signers, vec![0b0000_0110]);
+        assert_eq!(metrics.dropped_messages(), 4);
+        assert_eq!(metrics.suspects(), 2);
+    }
+
+    #[tokio::test]
+    async fn closed_channel_shuts_down_unit() {
+        let (result_tx, result_rx) = mpsc::channel(5);
+        let (_request_tx, request_rx) = mpsc::channel(15);
+        let (timer_tx, timer_rx) = broadcast::channel(15);
+
+        let mut c = Cubesat::new(
+            0,
+            &config(3),
+            member_signer(0),
+            channel_link(result_tx, request_rx),
+            timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        c.slot_info.phase = Phase::Second;
+
+        drop(result_rx);
+        timer_tx
+            .send(Tick {
+                i: 0,
+                phase: Phase::Third,
+            })
+            .unwrap();
+        assert_eq!(c.run().await, Err(BounceError::ChannelClosed));
+    }
+
+    #[test]
+    fn error_policy_test() {
+        assert_eq!(
+            BounceError::Vote(1, VoteError::Duplicate).policy(),
+            ErrorPolicy::Drop
+        );
+        assert_eq!(
+            BounceError::Vote(1, VoteError::BadSignature).policy(),
+            ErrorPolicy::Suspect
+        );
+        assert_eq!(
+            BounceError::Certificate(1, CertificateError::BadSignature).policy(),
+            ErrorPolicy::Suspect
+        );
+        assert_eq!(
+            BounceError::Sign(SignerError::Scheme(SchemeError::Exhausted)).policy(),
+            ErrorPolicy::Shutdown
+        );
+        assert_eq!(
+            BounceError::Sign(SignerError::Token("device removed".to_string())).policy(),
+            ErrorPolicy::Drop
+        );
+        assert_eq!(
+            BounceError::from(LinkError::Congested(2)).policy(),
+            ErrorPolicy::Drop
+        );
+        assert_eq!(
+            BounceError::from(LinkError::Closed),
+            BounceError::ChannelClosed
+        );
+    }
+
+    #[tokio::test]
+    async fn bls12_381_flock_certifies_proposal() {
+        let (result_tx, mut result_rx) = mpsc::channel(5);
+        let (_request_tx, request_rx) = mpsc::channel(15);
+        let (_timer_tx, _timer_rx) = broadcast::channel(15);
+
+        let mut config = config(1);
+        config.scheme = SchemeId::Bls12381.into();
+        config.members = vec![new_member(&Bls12381Scheme, 0, &member_key(0))];
+
+        let mut c = Cubesat::new(
+            0,
+            &config,
+            Box::new(KeySigner::new(&Bls12381Scheme, member_key(0)).unwrap()),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        c.slot_info.phase = Phase::First;
+
+        // Ground requests are still signed with BN256, only the flock signs with BLS12-381.
+        let proposal = proposal(0, &["hello", "world"]);
+        let tree = MerkleTree::new(&[b"hello", b"world"]);
+        c.process(proposal).await.unwrap();
+
+        let vote = result_rx.recv().await.unwrap();
+        assert_eq!(vote.scheme, i32::from(SchemeId::Bls12381));
+        Bls12381Scheme
+            .verify(&vote.signature, &tree.root(), &vote.public_key)
+            .unwrap();
+
+        let certificate = result_rx.recv().await.unwrap();
+        assert!(certificate.aggregated);
+        assert_eq!(certificate.scheme, i32::from(SchemeId::Bls12381));
+        let response = BounceResponse {
+            certificate: Some(certificate),
+            proof: Some(tree.proof(1)),
+        };
+        assert!(verify_response(b"world", &response, &c.registry));
+    }
+
+    #[tokio::test]
+    async fn lms_flock_certifies_with_multi_signature() {
+        let (result_tx, mut result_rx) = mpsc::channel(5);
+        let (_request_tx, request_rx) = mpsc::channel(15);
+        let (_timer_tx, _timer_rx) = broadcast::channel(15);
+
+        LMS_SCHEME
+            .set_state_dir(std::env::temp_dir().join("bounce-lms-state"))
+            .unwrap();
+        let private_keys: Vec<Vec<u8>> = (0..2)
+            .map(|_| new_lms_private_key(&[LMS_SHA256_M32_H5]))
+            .collect();
+        let mut config = config(2);
+        config.scheme = SchemeId::Lms.into();
+        config.members = (0..2)
+            .map(|k| new_member(&LMS_SCHEME, k, &private_keys[k as usize]))
+            .collect();
+
+        let mut c = Cubesat::new(
+            1,
+            &config,
+            Box::new(KeySigner::new(&LMS_SCHEME, private_keys[1].clone()).unwrap()),
+            channel_link(result_tx, request_rx),
+            _timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        c.slot_info.phase = Phase::Second;
+
+        // This unit signs the proposal first, and the vote of the other unit arrives after.
+        let proposal = proposal(0, &["hello"]);
+        let msg = proposal.msg.clone();
+        c.process(proposal).await.unwrap();
+        let own_vote = result_rx.recv().await.unwrap();
+
+        let vote = Commit {
+            typ: CommitType::Precommit.into(),
+            msg: msg.clone(),
+            public_key: config.members[0].public_key.clone(),
+            signature: LMS_SCHEME.sign(&private_keys[0], &msg).unwrap(),
+            signer_id: 0,
+            scheme: SchemeId::Lms.into(),
+            ..Default::default()
+        };
+        c.process(vote.clone()).await.unwrap();
+
+        let certificate = result_rx.recv().await.unwrap();
+        assert!(certificate.aggregated);
+        assert_eq!(certificate.signers, vec![0b11]);
+        // The certificate is the concatenation of both votes.
+        assert_eq!(
+            certificate.signature.len(),
+            vote.signature.len() + own_vote.signature.len() - 4
+        );
+        LMS_SCHEME
+            .verify(&certificate.signature, &msg, &certificate.public_key)
+            .unwrap();
+        // The votes are concatenated in the order of the ids of their signers, whatever the order
+        // they arrived in, so the certificate checks against the members of its bitmap.
+        c.registry.verify_certificate(&certificate).unwrap();
+    }
 }
@@ -0,0 +1,504 @@
+use crate::link::send_to_all;
+use crate::{unix_time_ms, Commit, ContactPlan, Link, LinkError, SPACE_STATION};
+use prost::Message;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fmt, fs, io};
+use tokio::sync::{mpsc, Mutex};
+
+// Commits waiting to be received from an emulated link.
+const INBOX_SIZE: usize = 64;
+
+/// How the latency of a link is drawn around `latency_ms`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Distribution {
+    // Always `latency_ms`.
+    Constant,
+    // Uniformly within `jitter_ms` of `latency_ms`.
+    Uniform,
+    // `latency_ms` plus an exponential tail with mean `jitter_ms`.
+    Exponential,
+}
+
+fn default_distribution() -> Distribution {
+    Distribution::Uniform
+}
+
+/// The impairments of an emulated inter-satellite link.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct LinkProfile {
+    #[serde(default)]
+    pub latency_ms: u64,
+    #[serde(default)]
+    pub jitter_ms: u64,
+    #[serde(default = "default_distribution")]
+    pub distribution: Distribution,
+    // Probability that a commit is lost.
+    #[serde(default)]
+    pub loss: f64,
+    // Probability that a commit is held back by `reorder_ms`, so that the next ones overtake it.
+    #[serde(default)]
+    pub reorder: f64,
+    #[serde(default)]
+    pub reorder_ms: u64,
+    // Bits per second the link carries, commits queue up behind each other, 0 for no cap.
+    #[serde(default)]
+    pub bandwidth_bps: u64,
+}
+
+impl Default for LinkProfile {
+    fn default() -> Self {
+        LinkProfile {
+            latency_ms: 0,
+            jitter_ms: 0,
+            distribution: default_distribution(),
+            loss: 0.0,
+            reorder: 0.0,
+            reorder_ms: 0,
+            bandwidth_bps: 0,
+        }
+    }
+}
+
+/// A profile for the links from `from` to `to`, either left out to match any end, the space station
+/// included.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct LinkOverride {
+    pub from: Option<u32>,
+    pub to: Option<u32>,
+    #[serde(flatten)]
+    pub profile: LinkProfile,
+}
+
+/// Isolates `units` from the other units and the space station from `start_ms` to `end_ms` after
+/// genesis. The isolated units still reach each other.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Partition {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub units: Vec<u32>,
+}
+
+/// The scenario of an experiment: how the links between the Bounce units behave, in TOML.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct Scenario {
+    // Seeds the draws of every link, so that a scenario runs the same way again.
+    #[serde(default)]
+    pub seed: u64,
+    // The profile of the links no override matches.
+    #[serde(default)]
+    pub default: LinkProfile,
+    // Profiles of specific links, the first one that matches applies.
+    #[serde(default)]
+    pub links: Vec<LinkOverride>,
+    #[serde(default)]
+    pub partitions: Vec<Partition>,
+    // When the ground stations reach the space station, always without a plan.
+    pub ground: Option<ContactPlan>,
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "failed to read the scenario: {}", e),
+            ScenarioError::Parse(e) => write!(f, "malformed scenario: {}", e),
+            ScenarioError::Invalid(e) => write!(f, "invalid scenario: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+fn check_profile(profile: &LinkProfile) -> Result<(), ScenarioError> {
+    for p in &[profile.loss, profile.reorder] {
+        if !(0.0..=1.0).contains(p) {
+            return Err(ScenarioError::Invalid(format!(
+                "probability {} is not between 0 and 1",
+                p
+            )));
+        }
+    }
+    Ok(())
+}
+
+pub fn parse_scenario(scenario: &str) -> Result<Scenario, ScenarioError> {
+    let scenario: Scenario = toml::from_str(scenario).map_err(ScenarioError::Parse)?;
+    check_profile(&scenario.default)?;
+    for link in &scenario.links {
+        check_profile(&link.profile)?;
+    }
+    for partition in &scenario.partitions {
+        if partition.end_ms <= partition.start_ms {
+            return Err(ScenarioError::Invalid(format!(
+                "partition of {:?} ends before it starts",
+                partition.units
+            )));
+        }
+    }
+    if let Some(ground) = &scenario.ground {
+        ground.check().map_err(ScenarioError::Invalid)?;
+    }
+    Ok(scenario)
+}
+
+pub fn load_scenario(path: &str) -> Result<Scenario, ScenarioError> {
+    parse_scenario(&fs::read_to_string(path).map_err(ScenarioError::Io)?)
+}
+
+// The state of the link from one end to another.
+struct LinkState {
+    // Each link draws from its own generator, so that the k-th commit over a link meets the same
+    // fate in every run however the traffic of the other links interleaves.
+    rng: StdRng,
+    // When the link is done transmitting the commits queued on it, in ms after genesis.
+    busy_until_ms: u64,
+}
+
+/// Decides the fate of each commit sent over the emulated links of a scenario.
+pub struct Emulator {
+    scenario: Scenario,
+    links: HashMap<(u32, u32), LinkState>,
+}
+
+impl Emulator {
+    pub fn new(scenario: Scenario) -> Self {
+        Emulator {
+            scenario,
+            links: HashMap::new(),
+        }
+    }
+
+    fn profile(&self, from: u32, to: u32) -> &LinkProfile {
+        self.scenario
+            .links
+            .iter()
+            .find(|link| link.from.is_none_or(|f| f == from) && link.to.is_none_or(|t| t == to))
+            .map_or(&self.scenario.default, |link| &link.profile)
+    }
+
+    fn is_partitioned(&self, from: u32, to: u32, now_ms: u64) -> bool {
+        self.scenario.partitions.iter().any(|p| {
+            (p.start_ms..p.end_ms).contains(&now_ms)
+                && (p.units.contains(&from) != p.units.contains(&to))
+        })
+    }
+
+    /// How long after `now_ms`, in ms after genesis, a commit of `size` bytes sent from `from` to
+    /// `to` arrives, or None if it is lost.
+    pub fn transit(&mut self, from: u32, to: u32, size: usize, now_ms: u64) -> Option<u64> {
+        let profile = self.profile(from, to).clone();
+        let partitioned = self.is_partitioned(from, to, now_ms);
+        let seed = self.scenario.seed ^ (u64::from(from) << 32 | u64::from(to));
+        let link = self.links.entry((from, to)).or_insert_with(|| LinkState {
+            rng: StdRng::seed_from_u64(seed),
+            busy_until_ms: 0,
+        });
+
+        // Every commit takes the same draws, whatever happens to it.
+        let lost = link.rng.gen::<f64>() < profile.loss;
+        let spread: f64 = link.rng.gen();
+        let held_back = link.rng.gen::<f64>() < profile.reorder;
+
+        if partitioned {
+            return None;
+        }
+        let mut delay_ms = 0;
+        if profile.bandwidth_bps > 0 {
+            let transmission_ms = (size as u64 * 8 * 1000).div_ceil(profile.bandwidth_bps);
+            link.busy_until_ms = link.busy_until_ms.max(now_ms) + transmission_ms;
+            delay_ms = link.busy_until_ms - now_ms;
+        }
+        if lost {
+            return None;
+        }
+        let jitter = profile.jitter_ms as f64;
+        delay_ms += match profile.distribution {
+            Distribution::Constant => profile.latency_ms,
+            Distribution::Uniform => {
+                (profile.latency_ms as f64 + (2.0 * spread - 1.0) * jitter).max(0.0) as u64
+            }
+            Distribution::Exponential => {
+                profile.latency_ms + (-(1.0 - spread).ln() * jitter) as u64
+            }
+        };
+        if held_back {
+            delay_ms += profile.reorder_ms;
+        }
+        Some(delay_ms)
+    }
+}
+
+// The end a commit was sent from: the unit that signed it, or the space station for a proposal.
+fn origin(commit: &Commit) -> u32 {
+    if commit.public_key.is_empty() {
+        SPACE_STATION
+    } else {
+        commit.signer_id
+    }
+}
+
+/// Emulates the links of a scenario over another link, e.g. in the relay path of the space
+/// station, delaying and dropping the commits it sends and receives.
+pub struct EmulatedLink {
+    inner: Arc<dyn Link>,
+    // The peers a broadcast goes to.
+    peers: Vec<u32>,
+    genesis_ms: u64,
+    emulator: Arc<std::sync::Mutex<Emulator>>,
+    inbox: Mutex<mpsc::Receiver<Commit>>,
+}
+
+impl EmulatedLink {
+    /// Emulates the links between `id`, this end, and `peers` over `inner`, with partitions timed
+    /// from `genesis_ms`.
+    pub fn new(
+        inner: Box<dyn Link>,
+        id: u32,
+        peers: Vec<u32>,
+        scenario: Scenario,
+        genesis_ms: u64,
+    ) -> Self {
+        let inner: Arc<dyn Link> = Arc::from(inner);
+        let emulator = Arc::new(std::sync::Mutex::new(Emulator::new(scenario)));
+        let (inbox_tx, inbox_rx) = mpsc::channel(INBOX_SIZE);
+
+        let receiving = inner.clone();
+        let incoming = emulator.clone();
+        tokio::spawn(async move {
+            while let Some(commit) = receiving.recv().await {
+                let now_ms = unix_time_ms().saturating_sub(genesis_ms);
+                let size = commit.encoded_len();
+                let transit = incoming
+                    .lock()
+                    .unwrap()
+                    .transit(origin(&commit), id, size, now_ms);
+                if let Some(delay_ms) = transit {
+                    let inbox_tx = inbox_tx.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                        let _ = inbox_tx.send(commit).await;
+                    });
+                }
+            }
+        });
+
+        EmulatedLink {
+            inner,
+            peers,
+            genesis_ms,
+            emulator,
+            inbox: Mutex::new(inbox_rx),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Link for EmulatedLink {
+    async fn send(&self, to: u32, commit: Commit) -> Result<(), LinkError> {
+        let now_ms = unix_time_ms().saturating_sub(self.genesis_ms);
+        let transit = self.emulator.lock().unwrap().transit(
+            origin(&commit),
+            to,
+            commit.encoded_len(),
+            now_ms,
+        );
+        match transit {
+            None => Ok(()),
+            Some(0) => self.inner.send(to, commit).await,
+            Some(delay_ms) => {
+                let inner = self.inner.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                    let _ = inner.send(to, commit).await;
+                });
+                Ok(())
+            }
+        }
+    }
+
+    async fn broadcast(&self, commit: Commit) -> Result<(), LinkError> {
+        send_to_all(self, self.peers.iter().copied(), &commit).await
+    }
+
+    async fn recv(&self) -> Option<Commit> {
+        self.inbox.lock().await.recv().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChannelLink, OutsidePolicy};
+
+    fn scenario(default: LinkProfile) -> Scenario {
+        Scenario {
+            seed: 7,
+            default,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_scenario_test() {
+        let scenario = parse_scenario(
+            r#"
+            seed = 42
+            [default]
+            latency_ms = 40
+            jitter_ms = 10
+            loss = 0.01
+            [[links]]
+            from = 0
+            to = 3
+            latency_ms = 600
+            distribution = "exponential"
+            bandwidth_bps = 9600
+            [[partitions]]
+            start_ms = 30000
+            end_ms = 60000
+            units = [3, 4]
+            [ground]
+            period_ms = 90000
+            duration_ms = 20000
+            setup_ms = 2000
+            outside = "queue"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(scenario.seed, 42);
+        assert_eq!(scenario.default.distribution, Distribution::Uniform);
+        assert_eq!(scenario.links[0].from, Some(0));
+        assert_eq!(scenario.links[0].profile.bandwidth_bps, 9600);
+        assert_eq!(scenario.partitions[0].units, vec![3, 4]);
+        assert_eq!(scenario.ground.unwrap().outside, OutsidePolicy::Queue);
+
+        assert!(matches!(
+            parse_scenario("[default]\nloss = 1.5"),
+            Err(ScenarioError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse_scenario("[[partitions]]\nstart_ms = 5\nend_ms = 5\nunits = []"),
+            Err(ScenarioError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse_scenario("[ground]\nperiod_ms = 100\nduration_ms = 50\nsetup_ms = 60"),
+            Err(ScenarioError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn emulator_is_reproducible_test() {
+        let profile = LinkProfile {
+            latency_ms: 100,
+            jitter_ms: 50,
+            loss: 0.3,
+            reorder: 0.2,
+            reorder_ms: 500,
+            ..Default::default()
+        };
+        let fates = |interleave: bool| {
+            let mut emulator = Emulator::new(scenario(profile.clone()));
+            (0..50)
+                .map(|k| {
+                    // Traffic over another link does not change the fate of this one.
+                    if interleave {
+                        emulator.transit(2, 1, 100, k);
+                    }
+                    emulator.transit(0, 1, 100, k)
+                })
+                .collect::<Vec<_>>()
+        };
+        let first = fates(false);
+        assert_eq!(first, fates(true));
+        assert!(first.contains(&None));
+        assert!(first
+            .iter()
+            .flatten()
+            .all(|d| (50..=150).contains(d) || (550..=650).contains(d)));
+    }
+
+    #[test]
+    fn emulator_caps_bandwidth_test() {
+        let mut emulator = Emulator::new(scenario(LinkProfile {
+            bandwidth_bps: 8000,
+            ..Default::default()
+        }));
+        // 1000 bytes take a second, the second commit waits for the first.
+        assert_eq!(emulator.transit(0, 1, 1000, 0), Some(1000));
+        assert_eq!(emulator.transit(0, 1, 1000, 0), Some(2000));
+        assert_eq!(emulator.transit(0, 2, 1000, 0), Some(1000));
+        assert_eq!(emulator.transit(0, 1, 1000, 5000), Some(1000));
+    }
+
+    #[test]
+    fn emulator_partitions_test() {
+        let mut scenario = scenario(LinkProfile::default());
+        scenario.partitions.push(Partition {
+            start_ms: 1000,
+            end_ms: 2000,
+            units: vec![3, 4],
+        });
+        scenario.links.push(LinkOverride {
+            from: Some(0),
+            to: None,
+            profile: LinkProfile {
+                loss: 1.0,
+                ..Default::default()
+            },
+        });
+        let mut emulator = Emulator::new(scenario);
+
+        assert_eq!(emulator.transit(1, 3, 10, 999), Some(0));
+        assert_eq!(emulator.transit(1, 3, 10, 1000), None);
+        assert_eq!(emulator.transit(3, SPACE_STATION, 10, 1500), None);
+        assert_eq!(emulator.transit(3, 4, 10, 1500), Some(0));
+        assert_eq!(emulator.transit(1, 2, 10, 1500), Some(0));
+        assert_eq!(emulator.transit(1, 3, 10, 2000), Some(0));
+        // Everything unit 0 sends is lost.
+        assert_eq!(emulator.transit(0, 2, 10, 0), None);
+    }
+
+    #[tokio::test]
+    async fn emulated_link_delays_commits() {
+        let (station_tx, station_rx) = mpsc::channel(5);
+        let (unit_tx, unit_rx) = mpsc::channel(5);
+        let station = EmulatedLink::new(
+            Box::new(ChannelLink::new(station_rx).with_peer(0, unit_tx)),
+            SPACE_STATION,
+            vec![0],
+            scenario(LinkProfile {
+                latency_ms: 100,
+                distribution: Distribution::Constant,
+                ..Default::default()
+            }),
+            unix_time_ms(),
+        );
+        let unit = ChannelLink::new(unit_rx).with_peer(SPACE_STATION, station_tx);
+
+        station.broadcast(Commit::default()).await.unwrap();
+        assert!(tokio::time::timeout(Duration::from_millis(50), unit.recv())
+            .await
+            .is_err());
+        assert!(unit.recv().await.is_some());
+
+        unit.broadcast(Commit::default()).await.unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), station.recv())
+                .await
+                .is_err()
+        );
+        assert!(station.recv().await.is_some());
+    }
+}
@@ -0,0 +1,142 @@
+use health_check_response::ServingStatus;
+use health_server::Health;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, watch};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+tonic::include_proto!("grpc.health.v1");
+
+// A receiver is kept with each sender, so that statuses are updated while nobody watches.
+type Statuses = HashMap<String, (watch::Sender<ServingStatus>, watch::Receiver<ServingStatus>)>;
+
+/// Serves the standard gRPC health checking protocol, grpc.health.v1.Health, with the status of
+/// each service set by the server. The empty service name stands for the server as a whole.
+#[derive(Clone, Default)]
+pub struct HealthService {
+    statuses: Arc<Mutex<Statuses>>,
+}
+
+impl HealthService {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set_status(&self, service: &str, status: ServingStatus) {
+        let mut statuses = self.statuses.lock().unwrap();
+        let (status_tx, status_rx) = statuses
+            .entry(service.to_string())
+            .or_insert_with(|| watch::channel(status));
+        if *status_rx.borrow() != status {
+            status_tx
+                .send(status)
+                .expect("the service keeps a receiver of each status");
+        }
+    }
+
+    // Follows the status of `service`, unknown until it is set.
+    fn subscribe(&self, service: &str) -> watch::Receiver<ServingStatus> {
+        let mut statuses = self.statuses.lock().unwrap();
+        let (_, status_rx) = statuses
+            .entry(service.to_string())
+            .or_insert_with(|| watch::channel(ServingStatus::ServiceUnknown));
+        status_rx.clone()
+    }
+}
+
+#[tonic::async_trait]
+impl Health for HealthService {
+    async fn check(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<HealthCheckResponse>, Status> {
+        let service = request.into_inner().service;
+        let status = self
+            .statuses
+            .lock()
+            .unwrap()
+            .get(&service)
+            .map(|(_, status_rx)| *status_rx.borrow());
+        match status {
+            Some(status) if status != ServingStatus::ServiceUnknown => {
+                Ok(Response::new(HealthCheckResponse {
+                    status: status.into(),
+                }))
+            }
+            _ => Err(Status::not_found(format!("unknown service {:?}", service))),
+        }
+    }
+
+    type WatchStream = ReceiverStream<Result<HealthCheckResponse, Status>>;
+
+    // Sends the status of the service, and again each time it changes, until the client hangs up.
+    async fn watch(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let mut status_rx = self.subscribe(&request.into_inner().service);
+        let (update_tx, update_rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            let mut sent = None;
+            loop {
+                let status = *status_rx.borrow();
+                if sent != Some(status) {
+                    let response = HealthCheckResponse {
+                        status: status.into(),
+                    };
+                    if update_tx.send(Ok(response)).await.is_err() {
+                        return;
+                    }
+                    sent = Some(status);
+                }
+                if status_rx.changed().await.is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(update_rx)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    fn request(service: &str) -> Request<HealthCheckRequest> {
+        Request::new(HealthCheckRequest {
+            service: service.to_string(),
+        })
+    }
+
+    async fn next_status(
+        updates: &mut ReceiverStream<Result<HealthCheckResponse, Status>>,
+    ) -> ServingStatus {
+        updates.next().await.unwrap().unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn health_service_test() {
+        let health = HealthService::new();
+        health.set_status("", ServingStatus::Serving);
+        let response = health.check(request("")).await.unwrap().into_inner();
+        assert_eq!(response.status(), ServingStatus::Serving);
+        assert_eq!(
+            health.check(request("other")).await.unwrap_err().code(),
+            tonic::Code::NotFound
+        );
+
+        // A service is watched before it is known, and each change is sent once.
+        let mut updates = health.watch(request("bounce")).await.unwrap().into_inner();
+        assert_eq!(
+            next_status(&mut updates).await,
+            ServingStatus::ServiceUnknown
+        );
+        health.set_status("bounce", ServingStatus::Serving);
+        assert_eq!(next_status(&mut updates).await, ServingStatus::Serving);
+        health.set_status("bounce", ServingStatus::Serving);
+        health.set_status("bounce", ServingStatus::NotServing);
+        assert_eq!(next_status(&mut updates).await, ServingStatus::NotServing);
+    }
+}
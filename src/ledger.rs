@@ -0,0 +1,354 @@
+use crate::commit::CommitType;
+use crate::{Commit, Phase, SlotInfo, SlotOutcome, SlotStatusResponse, Tick, UnitStatus};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What the space station has seen of one slot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlotRecord {
+    pub i: u32,
+    // The last phase of the slot the timer announced.
+    pub phase: Phase,
+    // Merkle root of the ground requests proposed in the slot, if any were.
+    pub root: Option<Vec<u8>>,
+    // Units whose single signatures were relayed, by the id they claim.
+    pub precommits: BTreeSet<u32>,
+    pub noncommits: BTreeSet<u32>,
+    pub outcome: SlotOutcome,
+    // The aggregated commit that finalized the slot.
+    pub certificate: Option<Commit>,
+    // Messages sent to relay the votes of the slot, and those a full broadcast would have added.
+    pub relayed: u64,
+    pub suppressed: u64,
+}
+
+impl SlotRecord {
+    fn new(i: u32) -> Self {
+        SlotRecord {
+            i,
+            phase: Phase::First,
+            root: None,
+            precommits: BTreeSet::new(),
+            noncommits: BTreeSet::new(),
+            outcome: SlotOutcome::Pending,
+            certificate: None,
+            relayed: 0,
+            suppressed: 0,
+        }
+    }
+
+    pub fn status(&self) -> SlotStatusResponse {
+        SlotStatusResponse {
+            i: self.i,
+            known: true,
+            root: self.root.clone().unwrap_or_default(),
+            precommits: self.precommits.iter().copied().collect(),
+            noncommits: self.noncommits.iter().copied().collect(),
+            outcome: self.outcome.into(),
+            certificate: self.certificate.clone(),
+            relayed: self.relayed,
+            suppressed: self.suppressed,
+        }
+    }
+}
+
+/// The space station's view of the progress of the flock, updated from timer ticks and the
+/// traffic of the Bounce units, with a record of each of the latest `capacity` slots.
+#[derive(Debug)]
+pub struct Ledger {
+    slot_info: SlotInfo,
+    records: BTreeMap<u32, SlotRecord>,
+    capacity: usize,
+    // The slot and type of the last single signature relayed for each unit, by the id it claims.
+    last_votes: BTreeMap<u32, (u32, CommitType)>,
+}
+
+impl Ledger {
+    pub fn new(capacity: usize) -> Self {
+        Ledger {
+            slot_info: SlotInfo::new(),
+            records: BTreeMap::new(),
+            capacity,
+            last_votes: BTreeMap::new(),
+        }
+    }
+
+    /// The current slot and phase, and the last committed slot.
+    pub fn slot_info(&self) -> &SlotInfo {
+        &self.slot_info
+    }
+
+    pub fn record(&self, i: u32) -> Option<&SlotRecord> {
+        self.records.get(&i)
+    }
+
+    /// Whether the timer stopped the flock, as opposed to not having started it yet.
+    pub fn is_stopped(&self) -> bool {
+        self.slot_info.phase == Phase::Stop && !self.records.is_empty()
+    }
+
+    pub fn tick(&mut self, tick: &Tick) {
+        if tick.phase == Phase::Stop {
+            self.slot_info.i = tick.i;
+            self.slot_info.phase = Phase::Stop;
+            return;
+        }
+        if tick.i != self.slot_info.i || tick.phase == Phase::First {
+            self.slot_info.advance_to(tick.i);
+        }
+        self.slot_info.phase = tick.phase.clone();
+        self.records
+            .entry(tick.i)
+            .or_insert_with(|| SlotRecord::new(tick.i))
+            .phase = tick.phase.clone();
+
+        // A certificate may still arrive for the previous slot, slots before it are given up.
+        for record in self.records.values_mut() {
+            if record.i + 1 < tick.i && record.outcome == SlotOutcome::Pending {
+                record.outcome = SlotOutcome::Unfinalized;
+            }
+        }
+        while self.records.len() > self.capacity {
+            let oldest = *self.records.keys().next().unwrap();
+            self.records.remove(&oldest);
+        }
+    }
+
+    pub fn propose(&mut self, i: u32, root: Vec<u8>) {
+        if let Some(record) = self.records.get_mut(&i) {
+            record.root = Some(root);
+        }
+    }
+
+    /// Notes a single signature relayed between the Bounce units.
+    pub fn vote(&mut self, commit: &Commit) {
+        if let Some(record) = self.records.get_mut(&commit.i) {
+            if commit.typ() == CommitType::Precommit {
+                record.precommits.insert(commit.signer_id);
+            } else {
+                record.noncommits.insert(commit.signer_id);
+            }
+            let last_vote = self
+                .last_votes
+                .entry(commit.signer_id)
+                .or_insert((commit.i, commit.typ()));
+            if commit.i >= last_vote.0 {
+                *last_vote = (commit.i, commit.typ());
+            }
+        }
+    }
+
+    /// The last vote relayed for unit `id`, which is alive if it voted in the current or the
+    /// previous slot.
+    pub fn unit_status(&self, id: u32) -> UnitStatus {
+        match self.last_votes.get(&id) {
+            Some(&(i, typ)) => UnitStatus {
+                id,
+                alive: i + 1 >= self.slot_info.i,
+                voted: true,
+                last_vote_slot: i,
+                last_vote: typ.into(),
+            },
+            None => UnitStatus {
+                id,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Counts the messages sent to relay a vote of slot `i`, out of the `num_units` a full
+    /// broadcast sends.
+    pub fn count_relay(&mut self, i: u32, sent: usize, num_units: usize) {
+        if let Some(record) = self.records.get_mut(&i) {
+            record.relayed += sent as u64;
+            record.suppressed += num_units.saturating_sub(sent) as u64;
+        }
+    }
+
+    pub fn is_finalized(&self, i: u32) -> bool {
+        self.records
+            .get(&i)
+            .is_some_and(|r| r.outcome != SlotOutcome::Pending)
+    }
+
+    /// Finalizes the slot of `certificate`, and returns whether it did. Only the first
+    /// certificate of a pending slot does, and a precommit must certify the proposed root.
+    pub fn finalize(&mut self, certificate: &Commit) -> bool {
+        let record = match self.records.get_mut(&certificate.i) {
+            Some(record) if record.outcome == SlotOutcome::Pending => record,
+            _ => return false,
+        };
+        record.outcome = if certificate.typ() == CommitType::Noncommit {
+            SlotOutcome::Noncommitted
+        } else if record.root.as_ref() == Some(&certificate.msg) {
+            SlotOutcome::Committed
+        } else {
+            return false;
+        };
+        record.certificate = Some(certificate.clone());
+
+        if certificate.i == self.slot_info.i {
+            self.slot_info.aggregated = true;
+        }
+        if record.outcome == SlotOutcome::Committed && certificate.i > self.slot_info.j {
+            self.slot_info.j = certificate.i;
+        }
+        true
+    }
+
+    /// The slots from `from` on that were finalized with a certificate, in order of their index.
+    pub fn finalized_since(&self, from: u32) -> Vec<SlotStatusResponse> {
+        self.records
+            .range(from..)
+            .map(|(_, record)| record)
+            .filter(|record| record.certificate.is_some())
+            .map(SlotRecord::status)
+            .collect()
+    }
+
+    pub fn status(&self, i: u32) -> SlotStatusResponse {
+        match self.records.get(&i) {
+            Some(record) => record.status(),
+            None => SlotStatusResponse {
+                i,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(i: u32, phase: Phase) -> Tick {
+        Tick { i, phase }
+    }
+
+    fn certificate(typ: CommitType, i: u32, msg: &[u8]) -> Commit {
+        Commit {
+            typ: typ.into(),
+            i,
+            msg: msg.to_vec(),
+            aggregated: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ledger_records_slot_test() {
+        let mut ledger = Ledger::new(10);
+        assert!(!ledger.is_stopped());
+
+        ledger.tick(&tick(3, Phase::First));
+        ledger.propose(3, b"root".to_vec());
+        for signer_id in &[0, 2, 2] {
+            ledger.vote(&Commit {
+                i: 3,
+                signer_id: *signer_id,
+                ..Default::default()
+            });
+        }
+        ledger.count_relay(3, 4, 5);
+        ledger.count_relay(3, 0, 5);
+        ledger.tick(&tick(3, Phase::Second));
+
+        let record = ledger.record(3).unwrap();
+        assert_eq!(record.phase, Phase::Second);
+        assert_eq!(
+            record.precommits.iter().copied().collect::<Vec<_>>(),
+            [0, 2]
+        );
+        assert_eq!(record.outcome, SlotOutcome::Pending);
+        assert_eq!((record.relayed, record.suppressed), (4, 6));
+        assert!(!ledger.is_finalized(3));
+
+        // A precommit for another message does not finalize the slot.
+        assert!(!ledger.finalize(&certificate(CommitType::Precommit, 3, b"other")));
+        assert!(ledger.finalize(&certificate(CommitType::Precommit, 3, b"root")));
+        assert!(!ledger.finalize(&certificate(CommitType::Noncommit, 3, b"")));
+
+        let status = ledger.status(3);
+        assert!(status.known);
+        assert_eq!(status.outcome(), SlotOutcome::Committed);
+        assert_eq!(status.certificate.unwrap().msg, b"root");
+        assert!(ledger.is_finalized(3));
+        assert_eq!(ledger.slot_info().j, 3);
+        assert!(ledger.slot_info().aggregated);
+
+        assert!(!ledger.status(4).known);
+    }
+
+    #[test]
+    fn ledger_unit_status_test() {
+        let mut ledger = Ledger::new(10);
+        ledger.tick(&tick(1, Phase::First));
+        ledger.tick(&tick(2, Phase::First));
+        for (i, signer_id, typ) in &[
+            (2, 0, CommitType::Precommit),
+            (1, 0, CommitType::Noncommit),
+            (1, 1, CommitType::Noncommit),
+            (5, 2, CommitType::Precommit),
+        ] {
+            ledger.vote(&Commit {
+                typ: (*typ).into(),
+                i: *i,
+                signer_id: *signer_id,
+                ..Default::default()
+            });
+        }
+
+        // An older vote does not replace the last one.
+        let unit = ledger.unit_status(0);
+        assert!(unit.alive && unit.voted);
+        assert_eq!(unit.last_vote_slot, 2);
+        assert_eq!(unit.last_vote(), CommitType::Precommit);
+        assert!(ledger.unit_status(1).alive);
+        // Votes for slots the ledger has no record of are not noted.
+        assert!(!ledger.unit_status(2).voted);
+
+        ledger.tick(&tick(3, Phase::First));
+        assert!(ledger.unit_status(0).alive);
+        assert!(!ledger.unit_status(1).alive);
+        assert_eq!(ledger.unit_status(1).last_vote(), CommitType::Noncommit);
+    }
+
+    #[test]
+    fn ledger_finalized_since_test() {
+        let mut ledger = Ledger::new(10);
+        for i in 0..4 {
+            ledger.tick(&tick(i, Phase::First));
+        }
+        ledger.propose(2, b"root".to_vec());
+        assert!(ledger.finalize(&certificate(CommitType::Noncommit, 3, b"")));
+        assert!(ledger.finalize(&certificate(CommitType::Precommit, 2, b"root")));
+
+        let finalized = ledger.finalized_since(0);
+        assert_eq!(finalized.iter().map(|s| s.i).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(finalized[0].outcome(), SlotOutcome::Committed);
+        assert_eq!(finalized[1].outcome(), SlotOutcome::Noncommitted);
+        assert_eq!(ledger.finalized_since(3).len(), 1);
+        assert!(ledger.finalized_since(4).is_empty());
+    }
+
+    #[test]
+    fn ledger_gives_up_old_slots_test() {
+        let mut ledger = Ledger::new(2);
+        ledger.tick(&tick(0, Phase::First));
+        ledger.tick(&tick(1, Phase::First));
+
+        // The certificate of the previous slot is still accepted.
+        assert!(ledger.finalize(&certificate(CommitType::Noncommit, 0, b"")));
+        assert_eq!(ledger.record(0).unwrap().outcome, SlotOutcome::Noncommitted);
+        assert_eq!(ledger.slot_info().j, 0);
+
+        ledger.tick(&tick(3, Phase::Third));
+        assert_eq!(ledger.record(1).unwrap().outcome, SlotOutcome::Unfinalized);
+        assert!(!ledger.finalize(&certificate(CommitType::Noncommit, 1, b"")));
+        // Only the latest slots are kept.
+        assert!(ledger.record(0).is_none());
+
+        ledger.tick(&tick(4, Phase::Stop));
+        assert!(ledger.is_stopped());
+        assert_eq!(ledger.slot_info().i, 4);
+    }
+}
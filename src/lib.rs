@@ -3,10 +3,56 @@ use log4rs::append::console::ConsoleAppender;
 use log4rs::append::file::FileAppender;
 use log4rs::config::{Appender, Config, Root};
 
+pub use auth::*;
+pub mod auth;
+pub use config::*;
+pub mod config;
+pub use contact::*;
+pub mod contact;
 pub use cubesat::*;
 pub mod cubesat;
+pub use emulator::*;
+pub mod emulator;
+pub use health::*;
+pub mod health;
+pub use ledger::*;
+pub mod ledger;
+pub use link::*;
+pub mod link;
+pub use lms::*;
+pub mod lms;
+pub use mempool::*;
+pub mod mempool;
+pub use merkle::*;
+pub mod merkle;
+pub use metrics::*;
+pub mod metrics;
+pub use orbit::*;
+pub mod orbit;
+pub use peer::*;
+pub mod peer;
+pub use proposer::*;
+pub mod proposer;
+pub use registry::*;
+pub mod registry;
+pub use relay::*;
+pub mod relay;
+pub use replay::*;
+pub mod replay;
+pub use router::*;
+pub mod router;
+pub use scheme::*;
+pub mod scheme;
+pub use signer::*;
+pub mod signer;
 pub use slot_info::*;
 pub mod slot_info;
+pub use tickets::*;
+pub mod tickets;
+pub use timer::*;
+pub mod timer;
+pub use udp::*;
+pub mod udp;
 
 tonic::include_proto!("bounce"); // The string specified here must match the proto package name
 
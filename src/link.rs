@@ -0,0 +1,152 @@
+use crate::Commit;
+use std::collections::BTreeMap;
+use std::fmt;
+use tokio::sync::{mpsc, Mutex};
+
+/// The peer id of the space station, next to the ids of the Bounce units.
+pub const SPACE_STATION: u32 = u32::MAX;
+
+#[derive(Debug, PartialEq)]
+pub enum LinkError {
+    // The peer, or every peer of a broadcast, no longer receives commits.
+    Closed,
+    // The link has no peer with this id.
+    UnknownPeer(u32),
+    // The address of a peer cannot be used.
+    Address(String),
+    // The commit does not fit in a single message of the link, with its encoded size.
+    TooLarge(usize),
+    // Commits to this peer are waiting already, and this one was dropped.
+    Congested(u32),
+    // The link failed to send or receive.
+    Io(String),
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::Closed => write!(f, "the link is closed"),
+            LinkError::UnknownPeer(id) => write!(f, "no link to peer {}", id),
+            LinkError::Address(address) => write!(f, "invalid peer address {}", address),
+            LinkError::TooLarge(size) => write!(f, "a commit of {} bytes is too large", size),
+            LinkError::Congested(id) => write!(f, "the link to peer {} is congested", id),
+            LinkError::Io(e) => write!(f, "link failure: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// Carries commits between a Bounce unit, or the space station, and its peers, identified by their
+/// member id or `SPACE_STATION`. Delivery is best effort: a commit that was sent may still be lost.
+///
+/// `recv` is only ever called by one task, while commits may be sent from several.
+#[tonic::async_trait]
+pub trait Link: Send + Sync {
+    /// Sends `commit` to the peer `to`.
+    async fn send(&self, to: u32, commit: Commit) -> Result<(), LinkError>;
+
+    /// Sends `commit` to every peer.
+    async fn broadcast(&self, commit: Commit) -> Result<(), LinkError>;
+
+    /// The next commit from any peer, or None once no peer can send any more.
+    async fn recv(&self) -> Option<Commit>;
+}
+
+// Sends to every one of `ids`, and returns the last error if any send failed.
+pub(crate) async fn send_to_all<L: Link + ?Sized>(
+    link: &L,
+    ids: impl Iterator<Item = u32>,
+    commit: &Commit,
+) -> Result<(), LinkError> {
+    let mut result = Ok(());
+    for id in ids {
+        if let Err(e) = link.send(id, commit.clone()).await {
+            result = Err(e);
+        }
+    }
+    result
+}
+
+/// A link between tasks of one process, over channels, e.g. between the space station and the
+/// Bounce units it runs.
+pub struct ChannelLink {
+    peers: BTreeMap<u32, mpsc::Sender<Commit>>,
+    inbox: Mutex<mpsc::Receiver<Commit>>,
+}
+
+impl ChannelLink {
+    /// Receives the commits sent to the other end of `inbox`.
+    pub fn new(inbox: mpsc::Receiver<Commit>) -> Self {
+        ChannelLink {
+            peers: BTreeMap::new(),
+            inbox: Mutex::new(inbox),
+        }
+    }
+
+    /// Sends the commits for peer `id` to `tx`.
+    pub fn with_peer(mut self, id: u32, tx: mpsc::Sender<Commit>) -> Self {
+        self.peers.insert(id, tx);
+        self
+    }
+}
+
+#[tonic::async_trait]
+impl Link for ChannelLink {
+    async fn send(&self, to: u32, commit: Commit) -> Result<(), LinkError> {
+        let tx = self.peers.get(&to).ok_or(LinkError::UnknownPeer(to))?;
+        tx.send(commit).await.map_err(|_| LinkError::Closed)
+    }
+
+    async fn broadcast(&self, commit: Commit) -> Result<(), LinkError> {
+        send_to_all(self, self.peers.keys().copied(), &commit).await
+    }
+
+    async fn recv(&self) -> Option<Commit> {
+        self.inbox.lock().await.recv().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn channel_link_test() {
+        let (station_tx, station_rx) = mpsc::channel(5);
+        let (unit_tx, unit_rx) = mpsc::channel(5);
+        let station = ChannelLink::new(station_rx).with_peer(0, unit_tx);
+        let unit = ChannelLink::new(unit_rx).with_peer(SPACE_STATION, station_tx);
+
+        unit.broadcast(Commit {
+            i: 3,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(station.recv().await.unwrap().i, 3);
+
+        station
+            .send(
+                0,
+                Commit {
+                    i: 4,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(unit.recv().await.unwrap().i, 4);
+        assert_eq!(
+            station.send(1, Commit::default()).await,
+            Err(LinkError::UnknownPeer(1))
+        );
+
+        drop(station);
+        assert_eq!(
+            unit.broadcast(Commit::default()).await,
+            Err(LinkError::Closed)
+        );
+        assert_eq!(unit.recv().await, None);
+    }
+}
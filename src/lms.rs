@@ -0,0 +1,846 @@
+use crate::{SchemeError, SchemeId, SignatureScheme};
+use rand::{thread_rng, Rng};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Typecodes of RFC 8554. Every level of a key uses LM-OTS with SHA-256, n = 32 and w = 4.
+pub const LMS_SHA256_M32_H5: u32 = 5;
+pub const LMS_SHA256_M32_H10: u32 = 6;
+const LMOTS_SHA256_N32_W4: u32 = 3;
+
+const N: usize = 32;
+const W: usize = 4;
+// Number of chains of an LM-OTS signature, and left shift of its checksum.
+const P: usize = 67;
+const LS: usize = 4;
+
+const D_PBLC: u16 = 0x8080;
+const D_MESG: u16 = 0x8181;
+const D_LEAF: u16 = 0x8282;
+const D_INTR: u16 = 0x8383;
+// Domains of the values derived from the seed of a key, outside of those of RFC 8554.
+const D_RANDOMIZER: u16 = 0xfffd;
+const D_CHILD_SEED: u16 = 0xfffe;
+const D_CHILD_ID: u16 = 0xffff;
+
+// Length of an LMS public key: types, I and the root of the tree.
+const LMS_PUBLIC_KEY_LEN: usize = 8 + 16 + N;
+const LMOTS_SIGNATURE_LEN: usize = 4 + N + P * N;
+
+/// The levels of the keys generated by default, giving 2^20 signatures per key.
+pub const DEFAULT_LMS_LEVELS: [u32; 2] = [LMS_SHA256_M32_H10, LMS_SHA256_M32_H10];
+
+fn height(lms_type: u32) -> Option<usize> {
+    match lms_type {
+        LMS_SHA256_M32_H5 => Some(5),
+        LMS_SHA256_M32_H10 => Some(10),
+        _ => None,
+    }
+}
+
+fn hash(parts: &[&[u8]]) -> [u8; N] {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+fn coef(s: &[u8], i: usize) -> usize {
+    let byte = s[i * W / 8] as usize;
+    (byte >> (8 - (W * (i % (8 / W)) + W))) & ((1 << W) - 1)
+}
+
+// The digest of the message followed by its checksum, whose digits give the chain lengths.
+fn digits(q_digest: &[u8; N]) -> Vec<u8> {
+    let checksum: usize = (0..N * 8 / W)
+        .map(|i| (1 << W) - 1 - coef(q_digest, i))
+        .sum();
+    let mut s = q_digest.to_vec();
+    s.extend_from_slice(&((checksum << LS) as u16).to_be_bytes());
+    s
+}
+
+fn chain(id: &[u8; 16], q: u32, i: usize, from: usize, to: usize, mut tmp: [u8; N]) -> [u8; N] {
+    for j in from..to {
+        tmp = hash(&[
+            id,
+            &q.to_be_bytes(),
+            &(i as u16).to_be_bytes(),
+            &[j as u8],
+            &tmp,
+        ]);
+    }
+    tmp
+}
+
+fn ots_public_key(id: &[u8; 16], q: u32, ends: &[[u8; N]]) -> [u8; N] {
+    let mut hasher = Sha256::new();
+    hasher.update(id);
+    hasher.update(q.to_be_bytes());
+    hasher.update(D_PBLC.to_be_bytes());
+    for end in ends {
+        hasher.update(end);
+    }
+    hasher.finalize().into()
+}
+
+// Reads the big endian fields of a key or a signature.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SchemeError> {
+        if self.bytes.len() < len {
+            return Err(SchemeError::Invalid);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, SchemeError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    fn array<const L: usize>(&mut self) -> Result<[u8; L], SchemeError> {
+        let mut bytes = [0; L];
+        bytes.copy_from_slice(self.take(L)?);
+        Ok(bytes)
+    }
+}
+
+/// One LMS key, a Merkle tree over the LM-OTS keys derived from its seed.
+struct LmsKey {
+    lms_type: u32,
+    height: usize,
+    id: [u8; 16],
+    seed: [u8; N],
+    // Nodes of the tree, the root at 1 and the children of r at 2r and 2r + 1.
+    nodes: Vec<[u8; N]>,
+}
+
+impl LmsKey {
+    fn new(lms_type: u32, id: [u8; 16], seed: [u8; N]) -> Self {
+        let height = height(lms_type).unwrap();
+        let leaves = 1 << height;
+        let mut key = LmsKey {
+            lms_type,
+            height,
+            id,
+            seed,
+            nodes: vec![[0; N]; 2 * leaves],
+        };
+        for q in 0..leaves as u32 {
+            let ends: Vec<[u8; N]> = (0..P)
+                .map(|i| chain(&id, q, i, 0, (1 << W) - 1, key.ots_private_key(q, i)))
+                .collect();
+            let r = leaves as u32 + q;
+            key.nodes[r as usize] = hash(&[
+                &id,
+                &r.to_be_bytes(),
+                &D_LEAF.to_be_bytes(),
+                &ots_public_key(&id, q, &ends),
+            ]);
+        }
+        for r in (1..leaves).rev() {
+            key.nodes[r] = hash(&[
+                &id,
+                &(r as u32).to_be_bytes(),
+                &D_INTR.to_be_bytes(),
+                &key.nodes[2 * r],
+                &key.nodes[2 * r + 1],
+            ]);
+        }
+        key
+    }
+
+    // The pseudorandom key generation of Appendix A of RFC 8554.
+    fn ots_private_key(&self, q: u32, i: usize) -> [u8; N] {
+        self.derive(q, i as u16, &[])
+    }
+
+    fn derive(&self, q: u32, domain: u16, data: &[u8]) -> [u8; N] {
+        hash(&[
+            &self.id,
+            &q.to_be_bytes(),
+            &domain.to_be_bytes(),
+            &[0xff],
+            &self.seed,
+            data,
+        ])
+    }
+
+    // The key signed by leaf q of this key in the level below.
+    fn child(&self, lms_type: u32, q: u32) -> LmsKey {
+        let mut id = [0; 16];
+        id.copy_from_slice(&self.derive(q, D_CHILD_ID, &[])[..16]);
+        LmsKey::new(lms_type, id, self.derive(q, D_CHILD_SEED, &[]))
+    }
+
+    fn public_key(&self) -> Vec<u8> {
+        [
+            &self.lms_type.to_be_bytes()[..],
+            &LMOTS_SHA256_N32_W4.to_be_bytes(),
+            &self.id,
+            &self.nodes[1],
+        ]
+        .concat()
+    }
+
+    // Signs `msg` with the one-time key q. The randomizer is derived from the message, so that
+    // signing the same message again with the same key gives the same signature.
+    fn sign(&self, q: u32, msg: &[u8]) -> Vec<u8> {
+        let randomizer = self.derive(q, D_RANDOMIZER, msg);
+        let digest = hash(&[
+            &self.id,
+            &q.to_be_bytes(),
+            &D_MESG.to_be_bytes(),
+            &randomizer,
+            msg,
+        ]);
+        let digits = digits(&digest);
+
+        let mut signature = q.to_be_bytes().to_vec();
+        signature.extend_from_slice(&LMOTS_SHA256_N32_W4.to_be_bytes());
+        signature.extend_from_slice(&randomizer);
+        for i in 0..P {
+            let y = chain(
+                &self.id,
+                q,
+                i,
+                0,
+                coef(&digits, i),
+                self.ots_private_key(q, i),
+            );
+            signature.extend_from_slice(&y);
+        }
+        signature.extend_from_slice(&self.lms_type.to_be_bytes());
+        let mut r = (1 << self.height) + q as usize;
+        while r > 1 {
+            signature.extend_from_slice(&self.nodes[r ^ 1]);
+            r /= 2;
+        }
+        signature
+    }
+}
+
+// The LM-OTS public key that the signature of `msg` read from `signature`, after its type, was
+// made with, computed as in Algorithm 4b of RFC 8554.
+fn ots_public_key_candidate(
+    signature: &mut Reader,
+    id: &[u8; 16],
+    q: u32,
+    msg: &[u8],
+) -> Result<[u8; N], SchemeError> {
+    let randomizer = signature.take(N)?;
+    let digest = hash(&[id, &q.to_be_bytes(), &D_MESG.to_be_bytes(), randomizer, msg]);
+    let digits = digits(&digest);
+    let mut ends = Vec::with_capacity(P);
+    for i in 0..P {
+        let y = signature.array()?;
+        ends.push(chain(id, q, i, coef(&digits, i), (1 << W) - 1, y));
+    }
+    Ok(ots_public_key(id, q, &ends))
+}
+
+// Verifies one LMS signature read from `signature` against the LMS public key `public_key`.
+fn verify_lms(signature: &mut Reader, msg: &[u8], public_key: &[u8]) -> Result<(), SchemeError> {
+    let mut key = Reader { bytes: public_key };
+    let lms_type = key.u32()?;
+    let height = height(lms_type).ok_or(SchemeError::Invalid)?;
+    if key.u32()? != LMOTS_SHA256_N32_W4 {
+        return Err(SchemeError::Invalid);
+    }
+    let id: [u8; 16] = key.array()?;
+    let root = key.take(N)?;
+
+    let q = signature.u32()?;
+    if signature.u32()? != LMOTS_SHA256_N32_W4 || q >> height != 0 {
+        return Err(SchemeError::Invalid);
+    }
+    let candidate = ots_public_key_candidate(signature, &id, q, msg)?;
+    if signature.u32()? != lms_type {
+        return Err(SchemeError::Invalid);
+    }
+
+    let mut r = (1u32 << height) + q;
+    let mut node = hash(&[&id, &r.to_be_bytes(), &D_LEAF.to_be_bytes(), &candidate]);
+    while r > 1 {
+        let sibling = signature.take(N)?;
+        let (left, right): (&[u8], &[u8]) = if r % 2 == 1 {
+            (sibling, &node)
+        } else {
+            (&node, sibling)
+        };
+        node = hash(&[
+            &id,
+            &(r / 2).to_be_bytes(),
+            &D_INTR.to_be_bytes(),
+            left,
+            right,
+        ]);
+        r /= 2;
+    }
+    if node[..] == *root {
+        Ok(())
+    } else {
+        Err(SchemeError::Invalid)
+    }
+}
+
+// Verifies an HSS signature of RFC 8554.
+fn verify_hss(signature: &[u8], msg: &[u8], public_key: &[u8]) -> Result<(), SchemeError> {
+    let mut key = Reader { bytes: public_key };
+    let levels = key.u32()?;
+    let mut current = key.take(LMS_PUBLIC_KEY_LEN)?.to_vec();
+    if levels == 0 || !key.bytes.is_empty() {
+        return Err(SchemeError::Invalid);
+    }
+
+    let mut signature = Reader { bytes: signature };
+    if signature.u32()? != levels - 1 {
+        return Err(SchemeError::Invalid);
+    }
+    for _ in 1..levels {
+        let mut signed = Reader {
+            bytes: signature.bytes,
+        };
+        // The public key of the next level follows its signature, whose length depends on the
+        // height of the current level.
+        let height = height(Reader { bytes: &current }.u32()?).ok_or(SchemeError::Invalid)?;
+        signature.take(4 + LMOTS_SIGNATURE_LEN + 4 + height * N)?;
+        let next = signature.take(LMS_PUBLIC_KEY_LEN)?;
+        verify_lms(&mut signed, next, &current)?;
+        current = next.to_vec();
+    }
+    verify_lms(&mut signature, msg, &current)?;
+    if signature.bytes.is_empty() {
+        Ok(())
+    } else {
+        Err(SchemeError::Invalid)
+    }
+}
+
+/// A stateful HSS signer, the one-time keys of which are used in order.
+struct HssSigner {
+    lms_types: Vec<u32>,
+    // The keys in use from the top level down, each but the first derived from and signed by the
+    // leaf in `parents` of the key above, with the signature in `signed_keys`.
+    keys: Vec<LmsKey>,
+    parents: Vec<u32>,
+    signed_keys: Vec<Vec<u8>>,
+    // Index of the next unused signature, and the number of signatures of the key.
+    next: u64,
+    limit: u64,
+    // The file to which `next` is saved before any signature is released.
+    state_file: Option<PathBuf>,
+}
+
+impl HssSigner {
+    fn new(private_key: &[u8], state_dir: Option<&Path>) -> Result<Self, SchemeError> {
+        let (lms_types, id, seed) = parse_private_key(private_key)?;
+        let limit = 1u64 << lms_types.iter().map(|t| height(*t).unwrap()).sum::<usize>();
+        let state_file = state_dir.map(|dir| dir.join(format!("{}.lms", hex::encode(id))));
+        let next = match &state_file {
+            Some(path) => read_state(path)?,
+            None => 0,
+        };
+        Ok(HssSigner {
+            keys: vec![LmsKey::new(lms_types[0], id, seed)],
+            lms_types,
+            parents: Vec::new(),
+            signed_keys: Vec::new(),
+            next,
+            limit,
+            state_file,
+        })
+    }
+
+    fn public_key(&self) -> Vec<u8> {
+        let levels = self.lms_types.len() as u32;
+        [&levels.to_be_bytes()[..], &self.keys[0].public_key()].concat()
+    }
+
+    // The leaf of every level used by signature `index`, the bottom level taking the least
+    // significant bits.
+    fn leaves(&self, index: u64) -> Vec<u32> {
+        let mut leaves = vec![0; self.lms_types.len()];
+        let mut rest = index;
+        for (level, lms_type) in self.lms_types.iter().enumerate().rev() {
+            let height = height(*lms_type).unwrap();
+            leaves[level] = (rest & ((1 << height) - 1)) as u32;
+            rest >>= height;
+        }
+        leaves
+    }
+
+    // Derives the keys of the lower levels below `leaves`, keeping those already in use.
+    fn descend(&mut self, leaves: &[u32]) {
+        for level in 1..self.lms_types.len() {
+            if self.parents.get(level - 1) == Some(&leaves[level - 1]) {
+                continue;
+            }
+            self.keys.truncate(level);
+            self.parents.truncate(level - 1);
+            self.signed_keys.truncate(level - 1);
+
+            let parent = &self.keys[level - 1];
+            let child = parent.child(self.lms_types[level], leaves[level - 1]);
+            // The signature is deterministic, so signing the same key again after a restart
+            // does not reveal more of the one-time key.
+            self.signed_keys
+                .push(parent.sign(leaves[level - 1], &child.public_key()));
+            self.keys.push(child);
+            self.parents.push(leaves[level - 1]);
+        }
+    }
+
+    fn sign(&mut self, msg: &[u8]) -> Result<Vec<u8>, SchemeError> {
+        if self.next >= self.limit {
+            return Err(SchemeError::Exhausted);
+        }
+        let index = self.next;
+        // The index is reserved on disk first, so that a restart never reuses a one-time key.
+        let path = self.state_file.as_ref().ok_or_else(|| {
+            SchemeError::KeyState(String::from("no directory to save the state of the key in"))
+        })?;
+        write_state(path, index + 1).map_err(|e| SchemeError::KeyState(e.to_string()))?;
+        self.next = index + 1;
+
+        let leaves = self.leaves(index);
+        self.descend(&leaves);
+
+        let bottom = self.keys.len() - 1;
+        let mut signature = (bottom as u32).to_be_bytes().to_vec();
+        for level in 1..=bottom {
+            signature.extend_from_slice(&self.signed_keys[level - 1]);
+            signature.extend_from_slice(&self.keys[level].public_key());
+        }
+        signature.extend_from_slice(&self.keys[bottom].sign(leaves[bottom], msg));
+        Ok(signature)
+    }
+}
+
+// The types of the levels of a private key, from the top, and the I and the seed of its top key.
+type PrivateKey = (Vec<u32>, [u8; 16], [u8; N]);
+
+fn parse_private_key(private_key: &[u8]) -> Result<PrivateKey, SchemeError> {
+    let malformed = || SchemeError::Malformed(String::from("malformed LMS private key"));
+    let mut reader = Reader { bytes: private_key };
+    let levels = reader.u32().map_err(|_| malformed())?;
+    if !(1..=8).contains(&levels) {
+        return Err(malformed());
+    }
+    let lms_types = (0..levels)
+        .map(|_| match reader.u32() {
+            Ok(lms_type) if height(lms_type).is_some() => Ok(lms_type),
+            _ => Err(malformed()),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let id = reader.array().map_err(|_| malformed())?;
+    let seed = reader.array().map_err(|_| malformed())?;
+    if !reader.bytes.is_empty() {
+        return Err(malformed());
+    }
+    Ok((lms_types, id, seed))
+}
+
+fn read_state(path: &Path) -> Result<u64, SchemeError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => contents
+            .trim()
+            .parse()
+            .map_err(|_| SchemeError::KeyState(format!("malformed key state {}", path.display()))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(SchemeError::KeyState(e.to_string())),
+    }
+}
+
+fn write_state(path: &Path, next: u64) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = fs::File::create(&tmp)?;
+    writeln!(file, "{}", next)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+/// Generates a private key with the given levels, from the top, each a `LMS_SHA256_M32_H*` type.
+pub fn new_lms_private_key(lms_types: &[u32]) -> Vec<u8> {
+    let mut rng = thread_rng();
+    let mut private_key = (lms_types.len() as u32).to_be_bytes().to_vec();
+    for lms_type in lms_types {
+        private_key.extend_from_slice(&lms_type.to_be_bytes());
+    }
+    private_key.extend((0..16 + N).map(|_| rng.gen::<u8>()));
+    private_key
+}
+
+// Encodes a list of HSS keys or signatures: a count, then each with its length.
+fn encode_list(items: &[&[u8]]) -> Vec<u8> {
+    let mut bytes = (items.len() as u32).to_be_bytes().to_vec();
+    for item in items {
+        bytes.extend_from_slice(&(item.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(item);
+    }
+    bytes
+}
+
+fn decode_list(bytes: &[u8]) -> Result<Vec<&[u8]>, SchemeError> {
+    let mut reader = Reader { bytes };
+    let count = reader.u32()?;
+    let mut items = Vec::new();
+    for _ in 0..count {
+        let len = reader.u32()? as usize;
+        items.push(reader.take(len)?);
+    }
+    if count == 0 || !reader.bytes.is_empty() {
+        return Err(SchemeError::Invalid);
+    }
+    Ok(items)
+}
+
+struct KeyStates {
+    dir: Option<PathBuf>,
+    signers: BTreeMap<Vec<u8>, HssSigner>,
+}
+
+/// Stateful hash-based signatures, HSS with LMS and LM-OTS of RFC 8554, for flocks that must stay
+/// secure against quantum computers.
+///
+/// There is no aggregation, so the "aggregate" of several signatures is their concatenation, and
+/// the same for public keys. Rogue keys are harmless without aggregation, so the proof of
+/// possession is empty.
+///
+/// Each private key signs at most 2^(sum of the heights of its levels) messages, and each one-time
+/// key is used once. The index of the next one is kept in memory, and saved in the directory given
+/// to `set_state_dir` before every signature is released. Until a directory is given, keys do not
+/// sign at all.
+pub struct LmsScheme {
+    states: Mutex<KeyStates>,
+}
+
+pub static LMS_SCHEME: LmsScheme = LmsScheme {
+    states: Mutex::new(KeyStates {
+        dir: None,
+        signers: BTreeMap::new(),
+    }),
+};
+
+impl LmsScheme {
+    /// Saves the state of the keys used from now on in `dir`, from which it is restored.
+    pub fn set_state_dir(&self, dir: impl Into<PathBuf>) -> io::Result<()> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let mut states = self.states.lock().unwrap();
+        states.dir = Some(dir);
+        states.signers.clear();
+        Ok(())
+    }
+
+    fn with_signer<T>(
+        &self,
+        private_key: &[u8],
+        f: impl FnOnce(&mut HssSigner) -> Result<T, SchemeError>,
+    ) -> Result<T, SchemeError> {
+        let mut states = self.states.lock().unwrap();
+        let KeyStates { dir, signers } = &mut *states;
+        if !signers.contains_key(private_key) {
+            let signer = HssSigner::new(private_key, dir.as_deref())?;
+            signers.insert(private_key.to_vec(), signer);
+        }
+        f(signers.get_mut(private_key).unwrap())
+    }
+
+    /// Number of signatures `private_key` can still make.
+    pub fn remaining(&self, private_key: &[u8]) -> Result<u64, SchemeError> {
+        self.with_signer(private_key, |signer| Ok(signer.limit - signer.next))
+    }
+}
+
+impl SignatureScheme for LmsScheme {
+    fn id(&self) -> SchemeId {
+        SchemeId::Lms
+    }
+
+    fn generate_private_key(&self) -> Vec<u8> {
+        new_lms_private_key(&DEFAULT_LMS_LEVELS)
+    }
+
+    fn derive_public_key(&self, private_key: &[u8]) -> Result<Vec<u8>, SchemeError> {
+        let public_key = self.with_signer(private_key, |signer| Ok(signer.public_key()))?;
+        Ok(encode_list(&[&public_key]))
+    }
+
+    fn sign(&self, private_key: &[u8], msg: &[u8]) -> Result<Vec<u8>, SchemeError> {
+        let signature = self.with_signer(private_key, |signer| signer.sign(msg))?;
+        Ok(encode_list(&[&signature]))
+    }
+
+    fn verify(&self, signature: &[u8], msg: &[u8], public_key: &[u8]) -> Result<(), SchemeError> {
+        let signatures = decode_list(signature)?;
+        let public_keys = decode_list(public_key)?;
+        if signatures.len() != public_keys.len() {
+            return Err(SchemeError::Invalid);
+        }
+        signatures
+            .iter()
+            .zip(&public_keys)
+            .try_for_each(|(signature, public_key)| verify_hss(signature, msg, public_key))
+    }
+
+    fn aggregate_signatures(&self, signatures: &[&[u8]]) -> Result<Vec<u8>, SchemeError> {
+        let mut items = Vec::new();
+        for signature in signatures {
+            items.extend(decode_list(signature).map_err(|_| malformed_list())?);
+        }
+        Ok(encode_list(&items))
+    }
+
+    fn aggregate_public_keys(&self, public_keys: &[&[u8]]) -> Result<Vec<u8>, SchemeError> {
+        self.aggregate_signatures(public_keys)
+    }
+
+    fn prove_possession(&self, _private_key: &[u8]) -> Result<Vec<u8>, SchemeError> {
+        Ok(Vec::new())
+    }
+
+    fn verify_possession(&self, public_key: &[u8], proof: &[u8]) -> Result<(), SchemeError> {
+        match decode_list(public_key)?.as_slice() {
+            [key] if proof.is_empty() && key.len() == 4 + LMS_PUBLIC_KEY_LEN => Ok(()),
+            _ => Err(SchemeError::Invalid),
+        }
+    }
+
+    fn is_aggregate_ordered(&self) -> bool {
+        true
+    }
+}
+
+fn malformed_list() -> SchemeError {
+    SchemeError::Malformed(String::from("malformed list of LMS signatures or keys"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_key() -> Vec<u8> {
+        new_lms_private_key(&[LMS_SHA256_M32_H5, LMS_SHA256_M32_H5])
+    }
+
+    // `LMS_SCHEME`, saving the state of its keys in a directory shared by the tests.
+    fn shared_scheme() -> &'static LmsScheme {
+        LMS_SCHEME
+            .set_state_dir(std::env::temp_dir().join("bounce-lms-state"))
+            .unwrap();
+        &LMS_SCHEME
+    }
+
+    // A known answer made by an implementation of RFC 8554 independent of this one: an HSS
+    // signature with two levels of LMS_SHA256_M32_H5, of the message of Appendix F. Its top key is
+    // derived from `KAT_ID` and `KAT_SEED` as in Appendix A, while its randomizers and the key of
+    // its bottom level are not derived the way this implementation does.
+    const KAT_ID: &str = "a491778dfd8237943f4b1d4bcb06d614";
+    const KAT_SEED: &str = "f5e9086f09f87c5c47590f0e66a98903ba937708ec8d5c1c855b137eb0db27ec";
+    const KAT_PUBLIC_KEY: &str = "000000020000000500000003a491778dfd8237943f4b1d4bcb06d614\
+                                  e113cf85781c5258b06f5dd43528e52b4db38c049e3ff0fd1b89a581085ac06e";
+    const KAT_SIGNATURE: &str = include_str!("testdata/hss-kat.hex");
+    const KAT_MESSAGE: &[u8] = b"The powers not delegated to the United States by the \
+        Constitution, nor prohibited by it to the States, are reserved to the States \
+        respectively, or to the people.\n";
+    // The LM-OTS public key of the signature of the message, leaf 27 of the bottom level.
+    const KAT_OTS_PUBLIC_KEY: &str =
+        "c02a9807ec301b74dabbc915c6e4953ee12d860de98167ea1894c44a197b0fde";
+    // The LM-OTS public key of leaf 5 of the top level.
+    const KAT_TOP_OTS_PUBLIC_KEY: &str =
+        "3bc2f4dc69085044ec7e68dc4b7d981b97a5d26c265dd35fcfb845cee39b6a5e";
+
+    fn kat_top_key() -> LmsKey {
+        let mut id = [0; 16];
+        id.copy_from_slice(&hex::decode(KAT_ID).unwrap());
+        let mut seed = [0; N];
+        seed.copy_from_slice(&hex::decode(KAT_SEED).unwrap());
+        LmsKey::new(LMS_SHA256_M32_H5, id, seed)
+    }
+
+    fn kat_signature() -> Vec<u8> {
+        hex::decode(KAT_SIGNATURE.split_whitespace().collect::<String>()).unwrap()
+    }
+
+    #[test]
+    fn hss_known_answer_test() {
+        let public_key = hex::decode(KAT_PUBLIC_KEY).unwrap();
+        let signature = kat_signature();
+        verify_hss(&signature, KAT_MESSAGE, &public_key).unwrap();
+        assert_eq!(
+            verify_hss(&signature, &KAT_MESSAGE[1..], &public_key),
+            Err(SchemeError::Invalid)
+        );
+        LMS_SCHEME
+            .verify(
+                &encode_list(&[&signature]),
+                KAT_MESSAGE,
+                &encode_list(&[&public_key]),
+            )
+            .unwrap();
+
+        assert_eq!(kat_top_key().public_key(), public_key[4..]);
+    }
+
+    #[test]
+    fn lm_ots_known_answer_test() {
+        // The signature of the bottom level follows the signature of the top level and the public
+        // key of the bottom level.
+        let signature = kat_signature();
+        let bottom = 4 + 4 + LMOTS_SIGNATURE_LEN + 4 + 5 * N;
+        let mut id = [0; 16];
+        id.copy_from_slice(&signature[bottom + 8..bottom + 24]);
+        let mut reader = Reader {
+            bytes: &signature[bottom + LMS_PUBLIC_KEY_LEN..],
+        };
+        let q = reader.u32().unwrap();
+        assert_eq!(reader.u32().unwrap(), LMOTS_SHA256_N32_W4);
+        let candidate = ots_public_key_candidate(&mut reader, &id, q, KAT_MESSAGE).unwrap();
+        assert_eq!(
+            (q, hex::encode(candidate).as_str()),
+            (27, KAT_OTS_PUBLIC_KEY)
+        );
+
+        // The signatures made here recover the one-time keys derived as in Appendix A.
+        let top = kat_top_key();
+        let signature = top.sign(5, KAT_MESSAGE);
+        let mut reader = Reader {
+            bytes: &signature[8..],
+        };
+        let candidate = ots_public_key_candidate(&mut reader, &top.id, 5, KAT_MESSAGE).unwrap();
+        assert_eq!(hex::encode(candidate), KAT_TOP_OTS_PUBLIC_KEY);
+    }
+
+    #[test]
+    fn lms_sign_verify_test() {
+        let scheme = shared_scheme();
+        let private_key = small_key();
+        let public_key = scheme.derive_public_key(&private_key).unwrap();
+        scheme.verify_possession(&public_key, &[]).unwrap();
+
+        // Signatures cross the boundary between two keys of the bottom level.
+        let signatures: Vec<Vec<u8>> = (0..40)
+            .map(|k| scheme.sign(&private_key, &[k]).unwrap())
+            .collect();
+        assert_eq!(scheme.remaining(&private_key).unwrap(), 1024 - 40);
+        for (k, signature) in signatures.iter().enumerate() {
+            scheme.verify(signature, &[k as u8], &public_key).unwrap();
+            assert_eq!(
+                scheme.verify(signature, &[k as u8 + 1], &public_key),
+                Err(SchemeError::Invalid)
+            );
+        }
+        // Every one-time key is used once, so the same message gets a new signature.
+        assert_ne!(scheme.sign(&private_key, &[0]).unwrap(), signatures[0]);
+
+        let mut tampered = signatures[0].clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert_eq!(
+            scheme.verify(&tampered, &[0], &public_key),
+            Err(SchemeError::Invalid)
+        );
+        assert_eq!(
+            scheme.verify(&signatures[0][..100], &[0], &public_key),
+            Err(SchemeError::Invalid)
+        );
+    }
+
+    #[test]
+    fn lms_multi_signature_test() {
+        let scheme = shared_scheme();
+        let private_keys: Vec<Vec<u8>> = (0..3).map(|_| small_key()).collect();
+        let public_keys: Vec<Vec<u8>> = private_keys
+            .iter()
+            .map(|k| scheme.derive_public_key(k).unwrap())
+            .collect();
+        let signatures: Vec<Vec<u8>> = private_keys
+            .iter()
+            .map(|k| scheme.sign(k, b"root").unwrap())
+            .collect();
+
+        let signature_refs: Vec<&[u8]> = signatures.iter().map(|s| s.as_slice()).collect();
+        let public_key_refs: Vec<&[u8]> = public_keys.iter().map(|k| k.as_slice()).collect();
+        let aggregate = scheme.aggregate_signatures(&signature_refs).unwrap();
+        scheme
+            .verify_aggregate(&aggregate, b"root", &public_key_refs)
+            .unwrap();
+        assert_eq!(
+            scheme.verify_aggregate(&aggregate, b"root", &public_key_refs[..2]),
+            Err(SchemeError::Invalid)
+        );
+        // Aggregates are aggregated further the same way, as votes are.
+        let partial = scheme.aggregate_signatures(&signature_refs[..2]).unwrap();
+        let aggregate = scheme
+            .aggregate_signatures(&[&partial, &signatures[2]])
+            .unwrap();
+        let partial_key = scheme.aggregate_public_keys(&public_key_refs[..2]).unwrap();
+        let public_key = scheme
+            .aggregate_public_keys(&[&partial_key, &public_keys[2]])
+            .unwrap();
+        scheme.verify(&aggregate, b"root", &public_key).unwrap();
+
+        // A multi-signature key is not the key of a member.
+        assert!(scheme.verify_possession(&public_key, &[]).is_err());
+        assert!(scheme.verify_possession(&public_keys[0], &[1]).is_err());
+    }
+
+    #[test]
+    fn lms_state_test() {
+        let dir = std::env::temp_dir().join(format!("lms-state-{}", thread_rng().gen::<u64>()));
+        let scheme = LmsScheme {
+            states: Mutex::new(KeyStates {
+                dir: None,
+                signers: BTreeMap::new(),
+            }),
+        };
+
+        // Without a directory to save its state in, a key does not sign at all.
+        let private_key = new_lms_private_key(&[LMS_SHA256_M32_H5]);
+        assert!(matches!(
+            scheme.sign(&private_key, b"a"),
+            Err(SchemeError::KeyState(_))
+        ));
+        assert_eq!(scheme.remaining(&private_key).unwrap(), 32);
+
+        scheme.set_state_dir(&dir).unwrap();
+        let public_key = scheme.derive_public_key(&private_key).unwrap();
+        let first = scheme.sign(&private_key, b"a").unwrap();
+
+        // After a restart, signing continues with the next one-time key.
+        scheme.set_state_dir(&dir).unwrap();
+        assert_eq!(scheme.remaining(&private_key).unwrap(), 31);
+        let second = scheme.sign(&private_key, b"a").unwrap();
+        assert_ne!(first, second);
+        scheme.verify(&second, b"a", &public_key).unwrap();
+
+        for _ in 0..30 {
+            scheme.sign(&private_key, b"b").unwrap();
+        }
+        assert_eq!(scheme.sign(&private_key, b"c"), Err(SchemeError::Exhausted));
+        scheme.set_state_dir(&dir).unwrap();
+        assert_eq!(scheme.sign(&private_key, b"c"), Err(SchemeError::Exhausted));
+
+        // A key whose state cannot be saved does not sign at all.
+        fs::remove_dir_all(&dir).unwrap();
+        let private_key = new_lms_private_key(&[LMS_SHA256_M32_H5]);
+        assert!(matches!(
+            scheme.sign(&private_key, b"a"),
+            Err(SchemeError::KeyState(_))
+        ));
+        assert_eq!(scheme.remaining(&private_key).unwrap(), 32);
+    }
+}
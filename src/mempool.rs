@@ -0,0 +1,236 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+#[derive(Clone, Debug)]
+pub struct MempoolConfig {
+    // Maximum number of requests waiting to be proposed.
+    pub max_pending: usize,
+    // Maximum number of requests a single submitter can have waiting.
+    pub max_per_submitter: usize,
+    // Maximum number of requests proposed in one slot.
+    pub max_batch_size: usize,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        MempoolConfig {
+            max_pending: 1024,
+            max_per_submitter: 16,
+            max_batch_size: 256,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AdmissionError {
+    Full,
+    SubmitterLimit,
+    Duplicate,
+}
+
+impl fmt::Display for AdmissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdmissionError::Full => write!(f, "the mempool is full"),
+            AdmissionError::SubmitterLimit => {
+                write!(f, "too many pending requests from this submitter")
+            }
+            AdmissionError::Duplicate => write!(f, "the request is already pending"),
+        }
+    }
+}
+
+impl std::error::Error for AdmissionError {}
+
+struct Entry<T> {
+    id: Vec<u8>,
+    item: T,
+}
+
+struct SubmitterQueue<T> {
+    submitter: Vec<u8>,
+    entries: VecDeque<Entry<T>>,
+}
+
+/// Requests waiting to be proposed at the start of the next slot.
+///
+/// Batches are filled round-robin over submitters, one request of each at a time, and a submitter
+/// that was served goes to the back of the line, so that a busy submitter cannot starve others.
+pub struct Mempool<T> {
+    config: MempoolConfig,
+    queues: VecDeque<SubmitterQueue<T>>,
+    len: usize,
+}
+
+impl<T> Mempool<T> {
+    pub fn new(config: MempoolConfig) -> Self {
+        Mempool {
+            config,
+            queues: VecDeque::new(),
+            len: 0,
+        }
+    }
+
+    pub fn config(&self) -> &MempoolConfig {
+        &self.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Queues `item`, identified by `id`, on behalf of `submitter`.
+    pub fn admit(&mut self, submitter: &[u8], id: Vec<u8>, item: T) -> Result<(), AdmissionError> {
+        if self.contains(&id) {
+            return Err(AdmissionError::Duplicate);
+        }
+        if self.len >= self.config.max_pending {
+            return Err(AdmissionError::Full);
+        }
+
+        let entry = Entry { id, item };
+        match self.queues.iter_mut().find(|q| q.submitter == submitter) {
+            Some(queue) => {
+                if queue.entries.len() >= self.config.max_per_submitter {
+                    return Err(AdmissionError::SubmitterLimit);
+                }
+                queue.entries.push_back(entry);
+            }
+            None => self.queues.push_back(SubmitterQueue {
+                submitter: submitter.to_vec(),
+                entries: vec![entry].into(),
+            }),
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn contains(&self, id: &[u8]) -> bool {
+        self.queues
+            .iter()
+            .any(|q| q.entries.iter().any(|entry| entry.id == id))
+    }
+
+    /// Returns how many requests will be proposed before the one identified by `id`.
+    pub fn position(&self, id: &[u8]) -> Option<usize> {
+        // Replays the round-robin of next_batch over the queue lengths.
+        let mut line: VecDeque<(usize, usize)> = (0..self.queues.len()).map(|q| (q, 0)).collect();
+        let mut position = 0;
+        while let Some((q, k)) = line.pop_front() {
+            let entries = &self.queues[q].entries;
+            if entries[k].id == id {
+                return Some(position);
+            }
+            position += 1;
+            if k + 1 < entries.len() {
+                line.push_back((q, k + 1));
+            }
+        }
+        None
+    }
+
+    /// Removes and returns the requests to propose in the next slot.
+    pub fn next_batch(&mut self) -> Vec<T> {
+        let mut batch = Vec::new();
+        while batch.len() < self.config.max_batch_size {
+            let mut queue = match self.queues.pop_front() {
+                Some(queue) => queue,
+                None => break,
+            };
+            batch.push(queue.entries.pop_front().unwrap().item);
+            if !queue.entries.is_empty() {
+                self.queues.push_back(queue);
+            }
+        }
+        self.len -= batch.len();
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mempool(
+        max_pending: usize,
+        max_per_submitter: usize,
+        max_batch_size: usize,
+    ) -> Mempool<u32> {
+        Mempool::new(MempoolConfig {
+            max_pending,
+            max_per_submitter,
+            max_batch_size,
+        })
+    }
+
+    fn id(n: u32) -> Vec<u8> {
+        n.to_be_bytes().to_vec()
+    }
+
+    #[test]
+    fn admission_limits_test() {
+        let mut mempool = mempool(3, 2, 10);
+
+        assert_eq!(mempool.admit(b"a", id(1), 1), Ok(()));
+        assert_eq!(
+            mempool.admit(b"a", id(1), 1),
+            Err(AdmissionError::Duplicate)
+        );
+        assert_eq!(mempool.admit(b"a", id(2), 2), Ok(()));
+        assert_eq!(
+            mempool.admit(b"a", id(3), 3),
+            Err(AdmissionError::SubmitterLimit)
+        );
+        assert_eq!(mempool.admit(b"b", id(4), 4), Ok(()));
+        assert_eq!(mempool.admit(b"c", id(5), 5), Err(AdmissionError::Full));
+        assert_eq!(mempool.len(), 3);
+
+        mempool.next_batch();
+        assert!(mempool.is_empty());
+        assert_eq!(mempool.admit(b"a", id(1), 1), Ok(()));
+    }
+
+    #[test]
+    fn round_robin_test() {
+        let mut mempool = mempool(100, 100, 3);
+        for n in 0..4 {
+            mempool.admit(b"busy", id(n), n).unwrap();
+        }
+        mempool.admit(b"quiet", id(10), 10).unwrap();
+        mempool.admit(b"other", id(20), 20).unwrap();
+        mempool.admit(b"other", id(21), 21).unwrap();
+
+        assert_eq!(mempool.position(&id(0)), Some(0));
+        assert_eq!(mempool.position(&id(10)), Some(1));
+        assert_eq!(mempool.position(&id(20)), Some(2));
+        assert_eq!(mempool.position(&id(1)), Some(3));
+        assert_eq!(mempool.position(&id(21)), Some(4));
+        assert_eq!(mempool.position(&id(3)), Some(6));
+        assert_eq!(mempool.position(&id(99)), None);
+
+        assert_eq!(mempool.next_batch(), vec![0, 10, 20]);
+        assert_eq!(mempool.next_batch(), vec![1, 21, 2]);
+        assert_eq!(mempool.next_batch(), vec![3]);
+        assert!(mempool.next_batch().is_empty());
+    }
+
+    #[test]
+    fn served_submitter_goes_to_the_back_test() {
+        let mut mempool = mempool(100, 100, 1);
+        mempool.admit(b"a", id(1), 1).unwrap();
+        mempool.admit(b"a", id(2), 2).unwrap();
+        mempool.admit(b"b", id(3), 3).unwrap();
+
+        assert_eq!(mempool.next_batch(), vec![1]);
+        // Submitter a was just served, so b goes first even though a has an older request.
+        mempool.admit(b"c", id(4), 4).unwrap();
+        assert_eq!(mempool.position(&id(3)), Some(0));
+        assert_eq!(mempool.next_batch(), vec![3]);
+        assert_eq!(mempool.next_batch(), vec![2]);
+        assert_eq!(mempool.next_batch(), vec![4]);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::commit::CommitType;
+use crate::{BounceResponse, MerkleProof, Registry};
+use sha2::{Digest, Sha256};
+
+// Domain separation between leaves and inner nodes, so that an inner node can never be passed off
+// as a message.
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+pub fn hash_leaf(msg: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(msg);
+    hasher.finalize().to_vec()
+}
+
+fn hash_node(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().to_vec()
+}
+
+/// Merkle tree over the messages batched in a slot, the flock signs only its root.
+///
+/// A node without a sibling is promoted to the next level as it is, instead of being paired with a
+/// copy of itself, so that two different batches never share a root.
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+    // levels[0] holds the leaf hashes and the last level holds only the root.
+    levels: Vec<Vec<Vec<u8>>>,
+}
+
+impl MerkleTree {
+    /// Builds the tree over `msgs`, which must not be empty.
+    pub fn new<T: AsRef<[u8]>>(msgs: &[T]) -> Self {
+        assert!(!msgs.is_empty(), "a Merkle tree needs at least one leaf");
+
+        let mut levels = vec![msgs
+            .iter()
+            .map(|msg| hash_leaf(msg.as_ref()))
+            .collect::<Vec<_>>()];
+
+        while levels.last().unwrap().len() > 1 {
+            let level = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    [node] => node.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(level);
+        }
+
+        MerkleTree { levels }
+    }
+
+    pub fn root(&self) -> Vec<u8> {
+        self.levels.last().unwrap()[0].clone()
+    }
+
+    pub fn num_leaves(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Returns the proof that the message at `index` is included under the root.
+    pub fn proof(&self, index: usize) -> MerkleProof {
+        assert!(index < self.num_leaves());
+
+        let mut siblings = Vec::new();
+        let mut i = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(i ^ 1) {
+                siblings.push(sibling.clone());
+            }
+            i /= 2;
+        }
+
+        MerkleProof {
+            index: index as u32,
+            num_leaves: self.num_leaves() as u32,
+            siblings,
+        }
+    }
+}
+
+/// Checks that `msg` is included under `root`, which the flock certified.
+pub fn verify_inclusion(root: &[u8], msg: &[u8], proof: &MerkleProof) -> bool {
+    if proof.index >= proof.num_leaves {
+        return false;
+    }
+
+    let mut hash = hash_leaf(msg);
+    let mut index = proof.index as usize;
+    let mut width = proof.num_leaves as usize;
+    let mut siblings = proof.siblings.iter();
+
+    while width > 1 {
+        // The last node of a level with an odd width has no sibling and is promoted as it is.
+        if index ^ 1 < width {
+            let sibling = match siblings.next() {
+                Some(sibling) => sibling,
+                None => return false,
+            };
+            hash = if index.is_multiple_of(2) {
+                hash_node(&hash, sibling)
+            } else {
+                hash_node(sibling, &hash)
+            };
+        }
+        index /= 2;
+        width = width.div_ceil(2);
+    }
+
+    siblings.next().is_none() && hash == root
+}
+
+/// Checks that `response` certifies `msg`, i.e. a supermajority of the members of `registry`
+/// aggregated a precommit over a root under which `msg` is included.
+pub fn verify_response(msg: &[u8], response: &BounceResponse, registry: &Registry) -> bool {
+    let (certificate, proof) = match (&response.certificate, &response.proof) {
+        (Some(certificate), Some(proof)) => (certificate, proof),
+        _ => return false,
+    };
+
+    certificate.typ() == CommitType::Precommit
+        && registry.verify_certificate(certificate).is_ok()
+        && verify_inclusion(&certificate.msg, msg, proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{new_member, Bn256Scheme, Commit, SchemeId, SignatureScheme};
+
+    fn msgs(n: usize) -> Vec<Vec<u8>> {
+        (0..n)
+            .map(|i| format!("message {}", i).into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn single_leaf_test() {
+        let tree = MerkleTree::new(&msgs(1));
+        assert_eq!(tree.root(), hash_leaf(b"message 0"));
+
+        let proof = tree.proof(0);
+        assert!(proof.siblings.is_empty());
+        assert!(verify_inclusion(&tree.root(), b"message 0", &proof));
+    }
+
+    #[test]
+    fn root_test() {
+        let msgs = msgs(3);
+        let tree = MerkleTree::new(&msgs);
+
+        let left = hash_node(&hash_leaf(&msgs[0]), &hash_leaf(&msgs[1]));
+        assert_eq!(tree.root(), hash_node(&left, &hash_leaf(&msgs[2])));
+    }
+
+    #[test]
+    fn proofs_test() {
+        for n in 1..=9 {
+            let msgs = msgs(n);
+            let tree = MerkleTree::new(&msgs);
+            assert_eq!(tree.num_leaves(), n);
+
+            for (index, msg) in msgs.iter().enumerate() {
+                let proof = tree.proof(index);
+                assert!(verify_inclusion(&tree.root(), msg, &proof));
+
+                // The proof only holds for the message it was made for.
+                assert!(!verify_inclusion(&tree.root(), b"forged", &proof));
+                if n > 1 {
+                    let other = &msgs[(index + 1) % n];
+                    assert!(!verify_inclusion(&tree.root(), other, &proof));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_proof_test() {
+        let msgs = msgs(5);
+        let tree = MerkleTree::new(&msgs);
+        let root = tree.root();
+
+        let mut proof = tree.proof(2);
+        proof.index = 5;
+        assert!(!verify_inclusion(&root, &msgs[2], &proof));
+
+        let mut proof = tree.proof(2);
+        proof.siblings.pop();
+        assert!(!verify_inclusion(&root, &msgs[2], &proof));
+
+        let mut proof = tree.proof(2);
+        proof.siblings.push(root.clone());
+        assert!(!verify_inclusion(&root, &msgs[2], &proof));
+
+        let mut proof = tree.proof(2);
+        proof.siblings[0][0] ^= 1;
+        assert!(!verify_inclusion(&root, &msgs[2], &proof));
+    }
+
+    #[test]
+    fn verify_response_test() {
+        let msgs = msgs(3);
+        let tree = MerkleTree::new(&msgs);
+
+        let private_keys: Vec<Vec<u8>> = (1..=3).map(|k| vec![k; 32]).collect();
+        let members: Vec<_> = (0..3)
+            .map(|id| new_member(&Bn256Scheme, id, &private_keys[id as usize]))
+            .collect();
+        let registry = Registry::new(&Bn256Scheme, &members).unwrap();
+        let sign = |private_keys: &[Vec<u8>], msg: &[u8]| {
+            let signatures: Vec<Vec<u8>> = private_keys
+                .iter()
+                .map(|k| Bn256Scheme.sign(k, msg).unwrap())
+                .collect();
+            let signatures: Vec<&[u8]> = signatures.iter().map(Vec::as_slice).collect();
+            Bn256Scheme.aggregate_signatures(&signatures).unwrap()
+        };
+
+        let certificate = Commit {
+            typ: CommitType::Precommit.into(),
+            i: 4,
+            j: 4,
+            aggregated: true,
+            public_key: Vec::new(),
+            msg: tree.root(),
+            signature: sign(&private_keys[..2], &tree.root()),
+            signer_id: 0,
+            requests: Vec::new(),
+            timestamp_ms: 0,
+            nonce: 0,
+            scheme: SchemeId::Bn256.into(),
+            signers: vec![0b011],
+        };
+        let response = BounceResponse {
+            certificate: Some(certificate.clone()),
+            proof: Some(tree.proof(1)),
+        };
+
+        assert!(verify_response(&msgs[1], &response, &registry));
+        assert!(!verify_response(&msgs[0], &response, &registry));
+
+        let mut forged = response.clone();
+        forged.certificate.as_mut().unwrap().signature = sign(&private_keys[..2], b"x");
+        assert!(!verify_response(&msgs[1], &forged, &registry));
+
+        // A key outside the flock signing alone certifies nothing, whatever key it carries.
+        let outsider = [vec![7; 32]];
+        let mut foreign = response.clone();
+        let foreign_certificate = foreign.certificate.as_mut().unwrap();
+        foreign_certificate.public_key = Bn256Scheme.derive_public_key(&outsider[0]).unwrap();
+        foreign_certificate.signature = sign(&outsider, &tree.root());
+        assert!(!verify_response(&msgs[1], &foreign, &registry));
+        foreign.certificate.as_mut().unwrap().signers = vec![0b001];
+        assert!(!verify_response(&msgs[1], &foreign, &registry));
+
+        // Nor does a single member.
+        let mut single = response.clone();
+        let single_certificate = single.certificate.as_mut().unwrap();
+        single_certificate.signature = sign(&private_keys[..1], &tree.root());
+        single_certificate.signers = vec![0b001];
+        assert!(!verify_response(&msgs[1], &single, &registry));
+
+        let mut noncommit = response;
+        noncommit
+            .certificate
+            .as_mut()
+            .unwrap()
+            .set_typ(CommitType::Noncommit);
+        assert!(!verify_response(&msgs[1], &noncommit, &registry));
+    }
+}
@@ -0,0 +1,42 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters a Bounce unit exposes to whoever supervises it.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    // Phase transitions that the unit never observed because it fell behind the timer.
+    missed_ticks: AtomicU64,
+    // Commits the unit dropped because it could not process them.
+    dropped_messages: AtomicU64,
+    // Peers the unit marked as suspect for sending invalid votes.
+    suspects: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn missed_ticks(&self) -> u64 {
+        self.missed_ticks.load(Ordering::Relaxed)
+    }
+
+    pub fn add_missed_ticks(&self, n: u64) {
+        self.missed_ticks.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn dropped_messages(&self) -> u64 {
+        self.dropped_messages.load(Ordering::Relaxed)
+    }
+
+    pub fn add_dropped_messages(&self, n: u64) {
+        self.dropped_messages.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn suspects(&self) -> u64 {
+        self.suspects.load(Ordering::Relaxed)
+    }
+
+    pub fn add_suspects(&self, n: u64) {
+        self.suspects.fetch_add(n, Ordering::Relaxed);
+    }
+}
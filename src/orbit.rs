@@ -0,0 +1,806 @@
+use chrono::NaiveDate;
+use std::f64::consts::PI;
+use std::{fmt, fs, io};
+
+// WGS-72, the constants SGP4 and the element sets are defined with.
+const EARTH_RADIUS_KM: f64 = 6378.135;
+const MU: f64 = 398_600.8;
+const J2: f64 = 0.001_082_616;
+const J3: f64 = -0.000_002_538_81;
+const J4: f64 = -0.000_001_655_97;
+
+// WGS-84, for the location of the ground station.
+const WGS84_RADIUS_KM: f64 = 6378.137;
+const WGS84_FLATTENING: f64 = 1.0 / 298.257_223_563;
+
+const TWO_PI: f64 = 2.0 * PI;
+const MS_PER_MINUTE: f64 = 60_000.0;
+
+// Step between the elevations sampled to find passes. Above the usual minimum elevations, a pass
+// of a satellite in low Earth orbit lasts a few minutes.
+const PASS_STEP_MS: u64 = 20_000;
+// Precision of the start, end and culmination of a pass.
+const PASS_PRECISION_MS: u64 = 100;
+
+#[derive(Debug)]
+pub enum OrbitError {
+    Io(io::Error),
+    // A line of the element set is malformed, with the line and why.
+    Tle(usize, String),
+    // The orbit has a period over 225 minutes, which needs the deep space terms of SDP4.
+    DeepSpace(f64),
+    // The propagation broke down at some minutes after epoch, e.g. after the satellite decayed.
+    Propagation(f64, &'static str),
+}
+
+impl fmt::Display for OrbitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrbitError::Io(e) => write!(f, "failed to read the element set: {}", e),
+            OrbitError::Tle(line, e) => write!(f, "malformed element set, line {}: {}", line, e),
+            OrbitError::DeepSpace(period) => write!(
+                f,
+                "the orbit has a period of {:.0} minutes, only near Earth orbits are propagated",
+                period
+            ),
+            OrbitError::Propagation(t, e) => {
+                write!(f, "propagation failed {:.1} minutes after epoch: {}", t, e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OrbitError {}
+
+/// The mean elements of a two-line element set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tle {
+    pub name: String,
+    pub norad_id: u32,
+    // Unix timestamp in ms of the elements.
+    pub epoch_ms: i64,
+    // Drag term, in inverse Earth radii.
+    pub bstar: f64,
+    // Angles in radians.
+    pub inclination: f64,
+    pub raan: f64,
+    pub eccentricity: f64,
+    pub arg_perigee: f64,
+    pub mean_anomaly: f64,
+    // Kozai mean motion in radians per minute.
+    pub mean_motion: f64,
+}
+
+fn checksum(line: &str) -> u32 {
+    line.bytes()
+        .take(68)
+        .map(|c| match c {
+            b'0'..=b'9' => (c - b'0') as u32,
+            b'-' => 1,
+            _ => 0,
+        })
+        .sum::<u32>()
+        % 10
+}
+
+// Reads the columns `start..end`, counted from 1 as in the format, of a line.
+fn field<T: std::str::FromStr>(
+    line: &str,
+    n: usize,
+    start: usize,
+    end: usize,
+) -> Result<T, OrbitError> {
+    let text = line[start - 1..end].trim();
+    text.parse()
+        .map_err(|_| OrbitError::Tle(n, format!("columns {}-{} hold {:?}", start, end, text)))
+}
+
+// Reads a number written with an implied leading decimal point and an exponent, e.g. " 28098-4".
+fn exponential(line: &str, n: usize, start: usize) -> Result<f64, OrbitError> {
+    let text = &line[start - 1..start + 7];
+    let sign = if text.starts_with('-') { -1.0 } else { 1.0 };
+    let mantissa: f64 = field::<f64>(&format!("0.{}", &text[1..6]), n, 1, 7)?;
+    let exponent: i32 = field(text, n, 7, 8)?;
+    Ok(sign * mantissa * 10f64.powi(exponent))
+}
+
+fn check_line(line: &str, n: usize) -> Result<(), OrbitError> {
+    if line.len() < 69 || !line.is_ascii() {
+        return Err(OrbitError::Tle(n, "shorter than 69 characters".to_string()));
+    }
+    if !line.starts_with(&n.to_string()) {
+        return Err(OrbitError::Tle(n, format!("does not start with {}", n)));
+    }
+    let expected = checksum(line);
+    if line[68..69] != expected.to_string() {
+        return Err(OrbitError::Tle(n, format!("checksum is not {}", expected)));
+    }
+    Ok(())
+}
+
+/// Parses an element set, with or without the line naming the satellite.
+pub fn parse_tle(tle: &str) -> Result<Tle, OrbitError> {
+    let lines: Vec<&str> = tle
+        .lines()
+        .map(|line| line.trim_end())
+        .filter(|line| !line.is_empty())
+        .collect();
+    let (name, line1, line2) = match lines.as_slice() {
+        [line1, line2] => ("", *line1, *line2),
+        [name, line1, line2] => (name.trim_start_matches("0 ").trim(), *line1, *line2),
+        _ => {
+            return Err(OrbitError::Tle(
+                0,
+                "expected two or three lines".to_string(),
+            ))
+        }
+    };
+    check_line(line1, 1)?;
+    check_line(line2, 2)?;
+    let norad_id = field(line1, 1, 3, 7)?;
+    if field::<u32>(line2, 2, 3, 7)? != norad_id {
+        return Err(OrbitError::Tle(2, "is of another satellite".to_string()));
+    }
+
+    let year: i32 = field(line1, 1, 19, 20)?;
+    let year = if year < 57 { 2000 + year } else { 1900 + year };
+    let day: f64 = field(line1, 1, 21, 32)?;
+    let epoch_ms = NaiveDate::from_ymd_opt(year, 1, 1)
+        .expect("the year has two digits")
+        .and_hms(0, 0, 0)
+        .timestamp_millis()
+        + ((day - 1.0) * 86_400_000.0).round() as i64;
+
+    Ok(Tle {
+        name: name.to_string(),
+        norad_id,
+        epoch_ms,
+        bstar: exponential(line1, 1, 54)?,
+        inclination: field::<f64>(line2, 2, 9, 16)?.to_radians(),
+        raan: field::<f64>(line2, 2, 18, 25)?.to_radians(),
+        eccentricity: field::<f64>(&format!("0.{}", &line2[26..33]), 2, 1, 9)?,
+        arg_perigee: field::<f64>(line2, 2, 35, 42)?.to_radians(),
+        mean_anomaly: field::<f64>(line2, 2, 44, 51)?.to_radians(),
+        mean_motion: field::<f64>(line2, 2, 53, 63)? * TWO_PI / 1440.0,
+    })
+}
+
+pub fn load_tle(path: &str) -> Result<Tle, OrbitError> {
+    parse_tle(&fs::read_to_string(path).map_err(OrbitError::Io)?)
+}
+
+/// Propagates an element set with SGP4, as in Vallado et al., "Revisiting Spacetrack Report #3",
+/// 2006. Only near Earth orbits, with a period under 225 minutes, are supported, which covers
+/// cubesats in low Earth orbit.
+#[derive(Clone, Debug)]
+pub struct Sgp4 {
+    tle: Tle,
+    xke: f64,
+    // Brouwer mean motion, in radians per minute.
+    no: f64,
+    // Perigee below 220 km, the higher order drag terms are left out.
+    simple: bool,
+    aycof: f64,
+    con41: f64,
+    cc1: f64,
+    cc4: f64,
+    cc5: f64,
+    d2: f64,
+    d3: f64,
+    d4: f64,
+    delmo: f64,
+    eta: f64,
+    argpdot: f64,
+    omgcof: f64,
+    sinmao: f64,
+    t2cof: f64,
+    t3cof: f64,
+    t4cof: f64,
+    t5cof: f64,
+    x1mth2: f64,
+    x7thm1: f64,
+    mdot: f64,
+    nodedot: f64,
+    xlcof: f64,
+    xmcof: f64,
+    nodecf: f64,
+}
+
+impl Sgp4 {
+    pub fn new(tle: &Tle) -> Result<Self, OrbitError> {
+        let xke = 60.0 / (EARTH_RADIUS_KM.powi(3) / MU).sqrt();
+        let j3oj2 = J3 / J2;
+        let x2o3 = 2.0 / 3.0;
+        let ecco = tle.eccentricity;
+        let inclo = tle.inclination;
+        let argpo = tle.arg_perigee;
+
+        // Recovers the Brouwer mean motion and semi-major axis from the Kozai mean motion.
+        let eccsq = ecco * ecco;
+        let omeosq = 1.0 - eccsq;
+        let rteosq = omeosq.sqrt();
+        let cosio = inclo.cos();
+        let cosio2 = cosio * cosio;
+        let ak = (xke / tle.mean_motion).powf(x2o3);
+        let d1 = 0.75 * J2 * (3.0 * cosio2 - 1.0) / (rteosq * omeosq);
+        let del = d1 / (ak * ak);
+        let adel = ak * (1.0 - del * del - del * (1.0 / 3.0 + 134.0 * del * del / 81.0));
+        let del = d1 / (adel * adel);
+        let no = tle.mean_motion / (1.0 + del);
+
+        let period = TWO_PI / no;
+        if period >= 225.0 {
+            return Err(OrbitError::DeepSpace(period));
+        }
+
+        let ao = (xke / no).powf(x2o3);
+        let sinio = inclo.sin();
+        let po = ao * omeosq;
+        let con42 = 1.0 - 5.0 * cosio2;
+        let con41 = -con42 - cosio2 - cosio2;
+        let posq = po * po;
+        let rp = ao * (1.0 - ecco);
+
+        let ss = 78.0 / EARTH_RADIUS_KM + 1.0;
+        let simple = rp < 220.0 / EARTH_RADIUS_KM + 1.0;
+        let mut sfour = ss;
+        let mut qzms24 = ((120.0 - 78.0) / EARTH_RADIUS_KM).powi(4);
+        let perige = (rp - 1.0) * EARTH_RADIUS_KM;
+        if perige < 156.0 {
+            sfour = if perige < 98.0 { 20.0 } else { perige - 78.0 };
+            qzms24 = ((120.0 - sfour) / EARTH_RADIUS_KM).powi(4);
+            sfour = sfour / EARTH_RADIUS_KM + 1.0;
+        }
+        let pinvsq = 1.0 / posq;
+
+        let tsi = 1.0 / (ao - sfour);
+        let eta = ao * ecco * tsi;
+        let etasq = eta * eta;
+        let eeta = ecco * eta;
+        let psisq = (1.0 - etasq).abs();
+        let coef = qzms24 * tsi.powi(4);
+        let coef1 = coef / psisq.powf(3.5);
+        let cc2 = coef1
+            * no
+            * (ao * (1.0 + 1.5 * etasq + eeta * (4.0 + etasq))
+                + 0.375 * J2 * tsi / psisq * con41 * (8.0 + 3.0 * etasq * (8.0 + etasq)));
+        let cc1 = tle.bstar * cc2;
+        let cc3 = if ecco > 1.0e-4 {
+            -2.0 * coef * tsi * j3oj2 * no * sinio / ecco
+        } else {
+            0.0
+        };
+        let x1mth2 = 1.0 - cosio2;
+        let cc4 = 2.0
+            * no
+            * coef1
+            * ao
+            * omeosq
+            * (eta * (2.0 + 0.5 * etasq) + ecco * (0.5 + 2.0 * etasq)
+                - J2 * tsi / (ao * psisq)
+                    * (-3.0 * con41 * (1.0 - 2.0 * eeta + etasq * (1.5 - 0.5 * eeta))
+                        + 0.75
+                            * x1mth2
+                            * (2.0 * etasq - eeta * (1.0 + etasq))
+                            * (2.0 * argpo).cos()));
+        let cc5 = 2.0 * coef1 * ao * omeosq * (1.0 + 2.75 * (etasq + eeta) + eeta * etasq);
+
+        // Secular rates of the mean anomaly, argument of perigee and right ascension.
+        let cosio4 = cosio2 * cosio2;
+        let temp1 = 1.5 * J2 * pinvsq * no;
+        let temp2 = 0.5 * temp1 * J2 * pinvsq;
+        let temp3 = -0.46875 * J4 * pinvsq * pinvsq * no;
+        let mdot = no
+            + 0.5 * temp1 * rteosq * con41
+            + 0.0625 * temp2 * rteosq * (13.0 - 78.0 * cosio2 + 137.0 * cosio4);
+        let argpdot = -0.5 * temp1 * con42
+            + 0.0625 * temp2 * (7.0 - 114.0 * cosio2 + 395.0 * cosio4)
+            + temp3 * (3.0 - 36.0 * cosio2 + 49.0 * cosio4);
+        let xhdot1 = -temp1 * cosio;
+        let nodedot = xhdot1
+            + (0.5 * temp2 * (4.0 - 19.0 * cosio2) + 2.0 * temp3 * (3.0 - 7.0 * cosio2)) * cosio;
+
+        let omgcof = tle.bstar * cc3 * argpo.cos();
+        let xmcof = if ecco > 1.0e-4 {
+            -x2o3 * coef * tle.bstar / eeta
+        } else {
+            0.0
+        };
+        let nodecf = 3.5 * omeosq * xhdot1 * cc1;
+        let t2cof = 1.5 * cc1;
+        let xlcof = -0.25 * j3oj2 * sinio * (3.0 + 5.0 * cosio) / (1.0 + cosio).max(1.5e-12);
+        let aycof = -0.5 * j3oj2 * sinio;
+        let delmo = (1.0 + eta * tle.mean_anomaly.cos()).powi(3);
+        let sinmao = tle.mean_anomaly.sin();
+        let x7thm1 = 7.0 * cosio2 - 1.0;
+
+        let (mut d2, mut d3, mut d4, mut t3cof, mut t4cof, mut t5cof) =
+            (0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        if !simple {
+            let cc1sq = cc1 * cc1;
+            d2 = 4.0 * ao * tsi * cc1sq;
+            let temp = d2 * tsi * cc1 / 3.0;
+            d3 = (17.0 * ao + sfour) * temp;
+            d4 = 0.5 * temp * ao * tsi * (221.0 * ao + 31.0 * sfour) * cc1;
+            t3cof = d2 + 2.0 * cc1sq;
+            t4cof = 0.25 * (3.0 * d3 + cc1 * (12.0 * d2 + 10.0 * cc1sq));
+            t5cof = 0.2
+                * (3.0 * d4 + 12.0 * cc1 * d3 + 6.0 * d2 * d2 + 15.0 * cc1sq * (2.0 * d2 + cc1sq));
+        }
+
+        Ok(Sgp4 {
+            tle: tle.clone(),
+            xke,
+            no,
+            simple,
+            aycof,
+            con41,
+            cc1,
+            cc4,
+            cc5,
+            d2,
+            d3,
+            d4,
+            delmo,
+            eta,
+            argpdot,
+            omgcof,
+            sinmao,
+            t2cof,
+            t3cof,
+            t4cof,
+            t5cof,
+            x1mth2,
+            x7thm1,
+            mdot,
+            nodedot,
+            xlcof,
+            xmcof,
+            nodecf,
+        })
+    }
+
+    pub fn tle(&self) -> &Tle {
+        &self.tle
+    }
+
+    /// The position in km and velocity in km/s, in the TEME frame, `t` minutes after epoch.
+    pub fn propagate(&self, t: f64) -> Result<([f64; 3], [f64; 3]), OrbitError> {
+        let tle = &self.tle;
+
+        // Secular gravity and drag.
+        let xmdf = tle.mean_anomaly + self.mdot * t;
+        let argpdf = tle.arg_perigee + self.argpdot * t;
+        let nodedf = tle.raan + self.nodedot * t;
+        let mut argpm = argpdf;
+        let mut mm = xmdf;
+        let t2 = t * t;
+        let mut nodem = nodedf + self.nodecf * t2;
+        let mut tempa = 1.0 - self.cc1 * t;
+        let mut tempe = tle.bstar * self.cc4 * t;
+        let mut templ = self.t2cof * t2;
+        if !self.simple {
+            let delomg = self.omgcof * t;
+            let delm = self.xmcof * ((1.0 + self.eta * xmdf.cos()).powi(3) - self.delmo);
+            let temp = delomg + delm;
+            mm = xmdf + temp;
+            argpm = argpdf - temp;
+            let t3 = t2 * t;
+            let t4 = t3 * t;
+            tempa = tempa - self.d2 * t2 - self.d3 * t3 - self.d4 * t4;
+            tempe += tle.bstar * self.cc5 * (mm.sin() - self.sinmao);
+            templ += self.t3cof * t3 + t4 * (self.t4cof + t * self.t5cof);
+        }
+
+        let am = (self.xke / self.no).powf(2.0 / 3.0) * tempa * tempa;
+        let nm = self.xke / am.powf(1.5);
+        let mut em = tle.eccentricity - tempe;
+        if !(-0.001..1.0).contains(&em) {
+            return Err(OrbitError::Propagation(
+                t,
+                "the eccentricity is out of range",
+            ));
+        }
+        if em < 1.0e-6 {
+            em = 1.0e-6;
+        }
+        mm += self.no * templ;
+        let xlm = (mm + argpm + nodem) % TWO_PI;
+        nodem %= TWO_PI;
+        argpm %= TWO_PI;
+        mm = (xlm - argpm - nodem) % TWO_PI;
+        let (sinip, cosip) = tle.inclination.sin_cos();
+
+        // Long period periodics.
+        let axnl = em * argpm.cos();
+        let temp = 1.0 / (am * (1.0 - em * em));
+        let aynl = em * argpm.sin() + temp * self.aycof;
+        let xl = mm + argpm + nodem + temp * self.xlcof * axnl;
+
+        // Solves Kepler's equation.
+        let u = (xl - nodem) % TWO_PI;
+        let mut eo1 = u;
+        let (mut sineo1, mut coseo1) = (0.0, 0.0);
+        for _ in 0..10 {
+            sineo1 = eo1.sin();
+            coseo1 = eo1.cos();
+            let step =
+                (u - aynl * coseo1 + axnl * sineo1 - eo1) / (1.0 - coseo1 * axnl - sineo1 * aynl);
+            eo1 += step.clamp(-0.95, 0.95);
+            if step.abs() < 1.0e-12 {
+                break;
+            }
+        }
+
+        // Short period periodics.
+        let ecose = axnl * coseo1 + aynl * sineo1;
+        let esine = axnl * sineo1 - aynl * coseo1;
+        let el2 = axnl * axnl + aynl * aynl;
+        let pl = am * (1.0 - el2);
+        if pl < 0.0 {
+            return Err(OrbitError::Propagation(
+                t,
+                "the semi-latus rectum is negative",
+            ));
+        }
+        let rl = am * (1.0 - ecose);
+        let rdotl = am.sqrt() * esine / rl;
+        let rvdotl = pl.sqrt() / rl;
+        let betal = (1.0 - el2).sqrt();
+        let temp = esine / (1.0 + betal);
+        let sinu = am / rl * (sineo1 - aynl - axnl * temp);
+        let cosu = am / rl * (coseo1 - axnl + aynl * temp);
+        let su = sinu.atan2(cosu);
+        let sin2u = (cosu + cosu) * sinu;
+        let cos2u = 1.0 - 2.0 * sinu * sinu;
+        let temp = 1.0 / pl;
+        let temp1 = 0.5 * J2 * temp;
+        let temp2 = temp1 * temp;
+
+        let mrt = rl * (1.0 - 1.5 * temp2 * betal * self.con41) + 0.5 * temp1 * self.x1mth2 * cos2u;
+        if mrt < 1.0 {
+            return Err(OrbitError::Propagation(t, "the satellite has decayed"));
+        }
+        let su = su - 0.25 * temp2 * self.x7thm1 * sin2u;
+        let xnode = nodem + 1.5 * temp2 * cosip * sin2u;
+        let xinc = tle.inclination + 1.5 * temp2 * cosip * sinip * cos2u;
+        let mvt = rdotl - nm * temp1 * self.x1mth2 * sin2u / self.xke;
+        let rvdot = rvdotl + nm * temp1 * (self.x1mth2 * cos2u + 1.5 * self.con41) / self.xke;
+
+        // Orientation vectors.
+        let (sinsu, cossu) = su.sin_cos();
+        let (snod, cnod) = xnode.sin_cos();
+        let (sini, cosi) = xinc.sin_cos();
+        let xmx = -snod * cosi;
+        let xmy = cnod * cosi;
+        let ux = [
+            xmx * sinsu + cnod * cossu,
+            xmy * sinsu + snod * cossu,
+            sini * sinsu,
+        ];
+        let vx = [
+            xmx * cossu - cnod * sinsu,
+            xmy * cossu - snod * sinsu,
+            sini * cossu,
+        ];
+
+        let km_per_s = EARTH_RADIUS_KM * self.xke / 60.0;
+        let mut position = [0.0; 3];
+        let mut velocity = [0.0; 3];
+        for k in 0..3 {
+            position[k] = mrt * ux[k] * EARTH_RADIUS_KM;
+            velocity[k] = (mvt * ux[k] + rvdot * vx[k]) * km_per_s;
+        }
+        Ok((position, velocity))
+    }
+
+    /// The position in km, in the TEME frame, at a Unix timestamp in ms.
+    pub fn position_at(&self, unix_ms: u64) -> Result<[f64; 3], OrbitError> {
+        let t = (unix_ms as i64 - self.tle.epoch_ms) as f64 / MS_PER_MINUTE;
+        Ok(self.propagate(t)?.0)
+    }
+}
+
+// Greenwich mean sidereal time in radians at a Unix timestamp in ms, UT1 taken as UTC.
+fn gmst(unix_ms: u64) -> f64 {
+    let jd = unix_ms as f64 / 86_400_000.0 + 2_440_587.5;
+    let tut1 = (jd - 2_451_545.0) / 36_525.0;
+    let seconds = -6.2e-6 * tut1.powi(3)
+        + 0.093_104 * tut1 * tut1
+        + (876_600.0 * 3600.0 + 8_640_184.812_866) * tut1
+        + 67_310.548_41;
+    (seconds.to_radians() / 240.0).rem_euclid(TWO_PI)
+}
+
+/// Where a ground station is on the WGS-84 ellipsoid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GroundLocation {
+    pub latitude_deg: f64,
+    pub longitude_deg: f64,
+    pub altitude_m: f64,
+}
+
+impl GroundLocation {
+    // The position in km, fixed to the Earth, and the local vertical.
+    fn ecef(&self) -> ([f64; 3], [f64; 3]) {
+        let (sin_lat, cos_lat) = self.latitude_deg.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = self.longitude_deg.to_radians().sin_cos();
+        let e2 = WGS84_FLATTENING * (2.0 - WGS84_FLATTENING);
+        let n = WGS84_RADIUS_KM / (1.0 - e2 * sin_lat * sin_lat).sqrt();
+        let h = self.altitude_m / 1000.0;
+        let position = [
+            (n + h) * cos_lat * cos_lon,
+            (n + h) * cos_lat * sin_lon,
+            (n * (1.0 - e2) + h) * sin_lat,
+        ];
+        let up = [cos_lat * cos_lon, cos_lat * sin_lon, sin_lat];
+        (position, up)
+    }
+
+    /// The elevation in degrees of a satellite at `unix_ms`, negative below the horizon.
+    pub fn elevation_deg(&self, orbit: &Sgp4, unix_ms: u64) -> Result<f64, OrbitError> {
+        let teme = orbit.position_at(unix_ms)?;
+        // Polar motion is left out, it moves the satellite by a few meters at most.
+        let (sin_g, cos_g) = gmst(unix_ms).sin_cos();
+        let satellite = [
+            cos_g * teme[0] + sin_g * teme[1],
+            -sin_g * teme[0] + cos_g * teme[1],
+            teme[2],
+        ];
+        let (site, up) = self.ecef();
+        let range: Vec<f64> = satellite.iter().zip(&site).map(|(s, g)| s - g).collect();
+        let distance = range.iter().map(|r| r * r).sum::<f64>().sqrt();
+        let height = range.iter().zip(&up).map(|(r, u)| r * u).sum::<f64>();
+        Ok((height / distance).asin().to_degrees())
+    }
+}
+
+/// Parses the location of a ground station given as LATITUDE,LONGITUDE[,ALTITUDE_M], in degrees and
+/// meters.
+pub fn parse_location(location: &str) -> Result<GroundLocation, String> {
+    let values = location
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("malformed location {:?}: {}", location, e))?;
+    match values.as_slice() {
+        [latitude_deg, longitude_deg] | [latitude_deg, longitude_deg, _] => Ok(GroundLocation {
+            latitude_deg: *latitude_deg,
+            longitude_deg: *longitude_deg,
+            altitude_m: values.get(2).copied().unwrap_or(0.0),
+        }),
+        _ => Err(format!(
+            "location {:?} is not LATITUDE,LONGITUDE[,ALTITUDE_M]",
+            location
+        )),
+    }
+}
+
+/// A pass of a satellite above the minimum elevation of a ground station, as Unix timestamps in ms.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pass {
+    // Acquisition of signal, when the satellite rises above the minimum elevation.
+    pub aos_ms: u64,
+    // Time of closest approach, when the satellite culminates.
+    pub tca_ms: u64,
+    // Loss of signal.
+    pub los_ms: u64,
+    pub max_elevation_deg: f64,
+}
+
+impl Pass {
+    pub fn contains(&self, unix_ms: u64) -> bool {
+        self.aos_ms <= unix_ms && unix_ms < self.los_ms
+    }
+}
+
+/// The first time from `unix_ms` within one of `passes`, sorted by start, or None if all are over.
+pub fn next_in_pass(passes: &[Pass], unix_ms: u64) -> Option<u64> {
+    passes
+        .iter()
+        .find(|pass| unix_ms < pass.los_ms)
+        .map(|pass| unix_ms.max(pass.aos_ms))
+}
+
+// Narrows down when the elevation crosses `min_elevation_deg` between `below_ms` and `above_ms`.
+fn crossing(
+    location: &GroundLocation,
+    orbit: &Sgp4,
+    min_elevation_deg: f64,
+    mut below_ms: u64,
+    mut above_ms: u64,
+) -> Result<u64, OrbitError> {
+    while below_ms.max(above_ms) - below_ms.min(above_ms) > PASS_PRECISION_MS {
+        let mid_ms = below_ms / 2 + above_ms / 2;
+        if location.elevation_deg(orbit, mid_ms)? >= min_elevation_deg {
+            above_ms = mid_ms;
+        } else {
+            below_ms = mid_ms;
+        }
+    }
+    Ok(above_ms)
+}
+
+// Narrows down when the elevation culminates between `start_ms` and `end_ms`.
+fn culmination(
+    location: &GroundLocation,
+    orbit: &Sgp4,
+    mut start_ms: u64,
+    mut end_ms: u64,
+) -> Result<(u64, f64), OrbitError> {
+    while end_ms - start_ms > PASS_PRECISION_MS {
+        let third_ms = (end_ms - start_ms) / 3;
+        if location.elevation_deg(orbit, start_ms + third_ms)?
+            < location.elevation_deg(orbit, end_ms - third_ms)?
+        {
+            start_ms += third_ms;
+        } else {
+            end_ms -= third_ms;
+        }
+    }
+    Ok((start_ms, location.elevation_deg(orbit, start_ms)?))
+}
+
+/// The passes over `location` above `min_elevation_deg` that are in progress or start between
+/// `from_ms` and `to_ms`. A pass in progress at `from_ms` starts then, and the last pass is followed
+/// until it ends. Passes shorter than the sampling step of 20 s may be missed.
+pub fn predict_passes(
+    orbit: &Sgp4,
+    location: &GroundLocation,
+    min_elevation_deg: f64,
+    from_ms: u64,
+    to_ms: u64,
+) -> Result<Vec<Pass>, OrbitError> {
+    let mut passes = Vec::new();
+    let mut aos_ms = None;
+    // The sample with the highest elevation in the current pass.
+    let mut peak = (from_ms, f64::MIN);
+    let mut previous_ms = from_ms;
+    let mut t_ms = from_ms;
+    loop {
+        let elevation = location.elevation_deg(orbit, t_ms)?;
+        match aos_ms {
+            None if t_ms > to_ms => break,
+            None if elevation >= min_elevation_deg => {
+                aos_ms = Some(if t_ms == from_ms {
+                    from_ms
+                } else {
+                    crossing(location, orbit, min_elevation_deg, previous_ms, t_ms)?
+                });
+                peak = (t_ms, elevation);
+            }
+            None => {}
+            Some(_) if elevation >= min_elevation_deg && elevation > peak.1 => {
+                peak = (t_ms, elevation);
+            }
+            Some(_) if elevation >= min_elevation_deg => {}
+            Some(aos) => {
+                let los_ms = crossing(location, orbit, min_elevation_deg, t_ms, previous_ms)?;
+                let (tca_ms, max_elevation_deg) = culmination(
+                    location,
+                    orbit,
+                    peak.0.saturating_sub(PASS_STEP_MS).max(aos),
+                    (peak.0 + PASS_STEP_MS).min(los_ms),
+                )?;
+                passes.push(Pass {
+                    aos_ms: aos,
+                    tca_ms,
+                    los_ms,
+                    max_elevation_deg,
+                });
+                aos_ms = None;
+            }
+        }
+        previous_ms = t_ms;
+        t_ms += PASS_STEP_MS;
+    }
+    Ok(passes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The test case of Vallado et al. with the largest eccentricity among the near Earth orbits.
+    const VANGUARD: &str = "\
+1 00005U 58002B   00179.78495062  .00000023  00000-0  28098-4 0  4753
+2 00005  34.2682 348.7242 1859667 331.7664  19.3264 10.82419157413667";
+
+    // A cubesat in a sun-synchronous orbit at about 500 km.
+    const CUBESAT: &str = "\
+CUBESAT
+1 43013U 17073E   21100.50000000  .00000447  00000-0  29164-4 0  9999
+2 43013  97.7035 145.2104 0011547 279.3813  80.6075 15.22064581184218";
+
+    fn assert_close(actual: [f64; 3], expected: [f64; 3], tolerance: f64) {
+        for k in 0..3 {
+            assert!(
+                (actual[k] - expected[k]).abs() < tolerance,
+                "{:?} is not {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn parse_tle_test() {
+        let tle = parse_tle(VANGUARD).unwrap();
+        assert_eq!(tle.norad_id, 5);
+        assert_eq!(tle.name, "");
+        // 2000-06-27 18:50:19.733568 UTC
+        assert_eq!(tle.epoch_ms, 962_131_819_734);
+        assert!((tle.bstar - 2.8098e-5).abs() < 1e-12);
+        assert!((tle.eccentricity - 0.1859667).abs() < 1e-12);
+
+        assert_eq!(parse_tle(CUBESAT).unwrap().name, "CUBESAT");
+
+        let corrupted = VANGUARD.replace("34.2682", "34.2683");
+        assert!(matches!(parse_tle(&corrupted), Err(OrbitError::Tle(2, _))));
+        assert!(matches!(parse_tle("1 00005U"), Err(OrbitError::Tle(0, _))));
+    }
+
+    #[test]
+    fn sgp4_test() {
+        // The reference positions and velocities of Vallado et al.
+        let orbit = Sgp4::new(&parse_tle(VANGUARD).unwrap()).unwrap();
+        let (position, velocity) = orbit.propagate(0.0).unwrap();
+        assert_close(position, [7022.46529266, -1400.08296755, 0.03995155], 1e-3);
+        assert_close(velocity, [1.893841015, 6.405893759, 4.534807250], 1e-6);
+        let (position, velocity) = orbit.propagate(360.0).unwrap();
+        assert_close(
+            position,
+            [-7154.03120202, -3783.17682504, -3536.19412294],
+            1e-3,
+        );
+        assert_close(velocity, [4.741887409, -4.151817765, -2.093935425], 1e-6);
+    }
+
+    #[test]
+    fn predict_passes_test() {
+        let orbit = Sgp4::new(&parse_tle(CUBESAT).unwrap()).unwrap();
+        let location = GroundLocation {
+            latitude_deg: 47.37,
+            longitude_deg: 8.54,
+            altitude_m: 408.0,
+        };
+        let from_ms = orbit.tle().epoch_ms as u64;
+        let to_ms = from_ms + 86_400_000;
+        let passes = predict_passes(&orbit, &location, 10.0, from_ms, to_ms).unwrap();
+
+        // A polar orbit passes over mid latitudes a few times a day.
+        assert!((2..=8).contains(&passes.len()), "{:?}", passes);
+        for (pass, next) in passes.iter().zip(passes.iter().skip(1)) {
+            assert!(pass.los_ms < next.aos_ms);
+        }
+        for pass in &passes {
+            assert!(pass.aos_ms < pass.tca_ms && pass.tca_ms < pass.los_ms);
+            assert!(pass.los_ms - pass.aos_ms < 15 * 60_000);
+            assert!(pass.max_elevation_deg >= 10.0 && pass.max_elevation_deg <= 90.0);
+            let at_aos = location.elevation_deg(&orbit, pass.aos_ms).unwrap();
+            assert!((at_aos - 10.0).abs() < 0.1, "{}", at_aos);
+            let before = location.elevation_deg(&orbit, pass.tca_ms - 1000).unwrap();
+            assert!(before <= pass.max_elevation_deg + 1e-6);
+        }
+
+        // From the middle of a pass, the pass starts right away.
+        let middle = &passes[0];
+        let passes = predict_passes(&orbit, &location, 10.0, middle.tca_ms, to_ms).unwrap();
+        assert_eq!(passes[0].aos_ms, middle.tca_ms);
+        assert!((passes[0].los_ms as i64 - middle.los_ms as i64).abs() <= PASS_PRECISION_MS as i64);
+
+        let first = &passes[0];
+        assert_eq!(next_in_pass(&passes, first.tca_ms), Some(first.tca_ms));
+        assert_eq!(next_in_pass(&passes, first.los_ms), Some(passes[1].aos_ms));
+        assert_eq!(next_in_pass(&passes, to_ms + 86_400_000), None);
+
+        let geostationary = Tle {
+            mean_motion: TWO_PI / 1436.0,
+            ..orbit.tle().clone()
+        };
+        assert!(matches!(
+            Sgp4::new(&geostationary),
+            Err(OrbitError::DeepSpace(_))
+        ));
+    }
+}
@@ -0,0 +1,306 @@
+use crate::bounce_peer_client::BouncePeerClient;
+use crate::bounce_peer_server::BouncePeer;
+use crate::link::send_to_all;
+use crate::{authenticate_proposal, Commit, Delivered, Link, LinkError, Registry};
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, Mutex};
+use tonic::transport::Endpoint;
+use tonic::{Request, Response, Status};
+
+// How long a delivery may take before the commit is given up, as a lossy link would lose it.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Commits waiting to be delivered to a peer.
+const LINK_QUEUE: usize = 64;
+
+/// Receives the commits peers deliver, and hands them to a Bounce unit, or the space station.
+/// Anyone may reach the service, so only commits from the flock are handed over.
+pub struct PeerService {
+    request_tx: mpsc::Sender<Commit>,
+    registry: Registry,
+    space_station_key: Vec<u8>,
+}
+
+impl PeerService {
+    pub fn new(
+        request_tx: mpsc::Sender<Commit>,
+        registry: Registry,
+        space_station_key: Vec<u8>,
+    ) -> Self {
+        PeerService {
+            request_tx,
+            registry,
+            space_station_key,
+        }
+    }
+
+    // Checks that `commit` is a vote of a member, a certificate of a supermajority, or a proposal
+    // signed by the space station.
+    fn check_sender(&self, commit: &Commit) -> Result<(), String> {
+        if commit.aggregated {
+            self.registry
+                .verify_certificate(commit)
+                .map_err(|e| e.to_string())
+        } else if commit.requests.is_empty() {
+            self.registry
+                .verify_vote(commit)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        } else {
+            authenticate_proposal(commit, &self.space_station_key).map_err(|e| e.to_string())
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl BouncePeer for PeerService {
+    async fn deliver(&self, request: Request<Commit>) -> Result<Response<Delivered>, Status> {
+        let commit = request.into_inner();
+        self.check_sender(&commit)
+            .map_err(Status::permission_denied)?;
+        self.request_tx
+            .send(commit)
+            .await
+            .map_err(|_| Status::unavailable("the receiver stopped"))?;
+        Ok(Response::new(Delivered {}))
+    }
+}
+
+// A peer at `address`. Commits are delivered in order by a task of its own, so that an unreachable
+// peer never holds up the others, and are lost if the peer cannot be reached.
+struct Peer {
+    tx: mpsc::Sender<Commit>,
+}
+
+impl Peer {
+    // The peer is connected to on the first delivery, and again after a delivery failed.
+    fn connect(address: &str) -> Result<Self, LinkError> {
+        let uri = if address.contains("://") {
+            address.to_string()
+        } else {
+            format!("http://{}", address)
+        };
+        let channel = Endpoint::from_shared(uri)
+            .map_err(|_| LinkError::Address(address.to_string()))?
+            .timeout(DELIVERY_TIMEOUT)
+            .connect_lazy()
+            .map_err(|_| LinkError::Address(address.to_string()))?;
+        let mut client = BouncePeerClient::new(channel);
+
+        let (tx, mut rx) = mpsc::channel::<Commit>(LINK_QUEUE);
+        let peer = address.to_string();
+        tokio::spawn(async move {
+            // Failures are only logged when the peer goes from reachable to unreachable, and back.
+            let mut reachable = true;
+            while let Some(commit) = rx.recv().await {
+                match client.deliver(commit).await {
+                    Ok(_) if !reachable => {
+                        info!("Peer {}\tReachable again", peer);
+                        reachable = true;
+                    }
+                    Ok(_) => {}
+                    Err(e) if reachable => {
+                        warn!("Peer {}\tFailed to deliver a commit: {}", peer, e.message());
+                        reachable = false;
+                    }
+                    Err(_) => {}
+                }
+            }
+        });
+        Ok(Peer { tx })
+    }
+}
+
+/// A link over the `BouncePeer` gRPC service, to peers running in other processes.
+pub struct GrpcLink {
+    peers: BTreeMap<u32, Peer>,
+    inbox: Mutex<mpsc::Receiver<Commit>>,
+}
+
+impl GrpcLink {
+    /// The link, and the service to serve for the peers to deliver their commits to it, which
+    /// only accepts those of the members of `registry`, and the proposals signed with
+    /// `space_station_key`.
+    pub fn new(registry: Registry, space_station_key: Vec<u8>) -> (Self, PeerService) {
+        let (request_tx, request_rx) = mpsc::channel(LINK_QUEUE);
+        let link = GrpcLink {
+            peers: BTreeMap::new(),
+            inbox: Mutex::new(request_rx),
+        };
+        (
+            link,
+            PeerService::new(request_tx, registry, space_station_key),
+        )
+    }
+
+    /// Delivers the commits for peer `id` to the service at `address`, e.g. "10.0.0.7:50061".
+    pub fn add_peer(&mut self, id: u32, address: &str) -> Result<(), LinkError> {
+        self.peers.insert(id, Peer::connect(address)?);
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl Link for GrpcLink {
+    async fn send(&self, to: u32, commit: Commit) -> Result<(), LinkError> {
+        let peer = self.peers.get(&to).ok_or(LinkError::UnknownPeer(to))?;
+        peer.tx.try_send(commit).map_err(|e| match e {
+            TrySendError::Full(_) => LinkError::Congested(to),
+            TrySendError::Closed(_) => LinkError::Closed,
+        })
+    }
+
+    async fn broadcast(&self, commit: Commit) -> Result<(), LinkError> {
+        send_to_all(self, self.peers.keys().copied(), &commit).await
+    }
+
+    async fn recv(&self) -> Option<Commit> {
+        self.inbox.lock().await.recv().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bounce_peer_server::BouncePeerServer;
+    use crate::commit::CommitType;
+    use crate::{new_member, sign_proposal, Bn256Scheme, SignatureScheme};
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+
+    const SPACE_STATION_PRIVATE_KEY: [u8; 32] = [8; 32];
+
+    fn registry() -> Registry {
+        Registry::new(&Bn256Scheme, &[new_member(&Bn256Scheme, 0, &[1; 32])]).unwrap()
+    }
+
+    fn service(request_tx: mpsc::Sender<Commit>) -> PeerService {
+        let space_station_key = Bn256Scheme
+            .derive_public_key(&SPACE_STATION_PRIVATE_KEY)
+            .unwrap();
+        PeerService::new(request_tx, registry(), space_station_key)
+    }
+
+    fn proposal(private_key: &[u8]) -> Commit {
+        let mut proposal = Commit {
+            i: 1,
+            msg: b"root".to_vec(),
+            requests: vec![Commit::default()],
+            ..Default::default()
+        };
+        sign_proposal(&mut proposal, &Bn256Scheme, private_key);
+        proposal
+    }
+
+    fn vote(private_key: &[u8], i: u32) -> Commit {
+        Commit {
+            typ: CommitType::Precommit.into(),
+            i,
+            msg: b"root".to_vec(),
+            public_key: Bn256Scheme.derive_public_key(private_key).unwrap(),
+            signature: Bn256Scheme.sign(private_key, b"root").unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn grpc_link_test() {
+        let (receiver, service) = GrpcLink::new(registry(), Vec::new());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(
+            Server::builder()
+                .add_service(BouncePeerServer::new(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        // Nothing listens on a port that was bound and released.
+        let unreachable = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+
+        let (mut sender, _) = GrpcLink::new(registry(), Vec::new());
+        // Nothing listens there, which must not hold up the delivery to the other peer.
+        sender.add_peer(0, &unreachable).unwrap();
+        sender.add_peer(1, &address).unwrap();
+
+        // The server may not be listening yet when the first commits are sent, and they are lost.
+        let mut i = 0;
+        let received = loop {
+            sender.broadcast(vote(&[1; 32], i)).await.unwrap();
+            if let Ok(Some(commit)) =
+                tokio::time::timeout(Duration::from_millis(200), receiver.recv()).await
+            {
+                break commit;
+            }
+            i += 1;
+            assert!(i < 20);
+        };
+        assert!(received.i <= i);
+
+        assert_eq!(
+            sender.send(2, Commit::default()).await,
+            Err(LinkError::UnknownPeer(2))
+        );
+        assert_eq!(
+            sender.add_peer(2, "not an address"),
+            Err(LinkError::Address("not an address".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn peer_service_refuses_outsiders_test() {
+        let (request_tx, mut request_rx) = mpsc::channel(4);
+        let service = service(request_tx);
+
+        for commit in [
+            // A vote of a key outside the flock, a forged vote of the member, and a certificate
+            // that no supermajority signed.
+            vote(&[7; 32], 0),
+            Commit {
+                signature: vote(&[7; 32], 0).signature,
+                ..vote(&[1; 32], 0)
+            },
+            Commit {
+                aggregated: true,
+                ..vote(&[7; 32], 0)
+            },
+        ] {
+            let status = service.deliver(Request::new(commit)).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        }
+
+        for commit in [vote(&[1; 32], 0), proposal(&SPACE_STATION_PRIVATE_KEY)] {
+            service.deliver(Request::new(commit.clone())).await.unwrap();
+            assert_eq!(request_rx.recv().await, Some(commit));
+        }
+    }
+
+    #[tokio::test]
+    async fn peer_service_refuses_forged_proposal_test() {
+        let (request_tx, mut request_rx) = mpsc::channel(4);
+        let service = service(request_tx);
+
+        // A proposal signed by a member rather than the space station, one claiming the key of
+        // the space station, and one without a signature.
+        let mut impostor = proposal(&SPACE_STATION_PRIVATE_KEY);
+        impostor.signature = proposal(&[1; 32]).signature;
+        let mut unsigned = proposal(&SPACE_STATION_PRIVATE_KEY);
+        unsigned.signature.clear();
+        for commit in [proposal(&[1; 32]), impostor, unsigned] {
+            let status = service.deliver(Request::new(commit)).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        }
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), request_rx.recv())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use crate::commit::CommitType;
+use crate::{
+    sign_proposal, unix_time_ms, Batch, Batches, Bn256Scheme, Commit, Ledger, Link, Mempool,
+    MerkleTree, Phase, ReplayCache, ReplayError, SlotOutcome, Tick, TicketBook,
+};
+use log::{error, info};
+use std::sync::Arc;
+use tokio::sync::{broadcast, Mutex};
+
+/// A ground request waiting in the mempool to be proposed.
+pub struct PendingRequest {
+    pub commit: Commit,
+    pub ticket: u64,
+}
+
+/// Follows the timer, and at the start of every slot proposes the next batch of ground requests
+/// from the mempool to the Bounce units, signed with the BN256 key of the space station.
+///
+/// Requests are only remembered by the replay cache once they are proposed. A request that went
+/// stale while it waited, or that was proposed before, would make every unit sign a noncommit for
+/// the whole batch, so it is dropped from the batch and fails on its own.
+pub struct Proposer {
+    link: Arc<dyn Link>,
+    ledger: Arc<Mutex<Ledger>>,
+    mempool: Arc<Mutex<Mempool<PendingRequest>>>,
+    replay_cache: Arc<Mutex<ReplayCache>>,
+    batches: Batches,
+    tickets: Arc<Mutex<TicketBook>>,
+    private_key: Vec<u8>,
+}
+
+impl Proposer {
+    pub fn new(
+        link: Arc<dyn Link>,
+        ledger: Arc<Mutex<Ledger>>,
+        mempool: Arc<Mutex<Mempool<PendingRequest>>>,
+        replay_cache: Arc<Mutex<ReplayCache>>,
+        batches: Batches,
+        tickets: Arc<Mutex<TicketBook>>,
+        private_key: Vec<u8>,
+    ) -> Self {
+        Proposer {
+            link,
+            ledger,
+            mempool,
+            replay_cache,
+            batches,
+            tickets,
+            private_key,
+        }
+    }
+
+    /// Proposes batches until the timer stops.
+    pub async fn run(mut self, mut timer_rx: broadcast::Receiver<Tick>) {
+        loop {
+            let tick = match timer_rx.recv().await {
+                Ok(tick) => tick,
+                // Only the latest transition matters, which is received next.
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            self.tick(&tick).await;
+        }
+    }
+
+    pub async fn tick(&mut self, tick: &Tick) {
+        self.ledger.lock().await.tick(tick);
+        if tick.phase != Phase::First {
+            return;
+        }
+
+        // The replay cache is locked before the mempool, in the order the space station takes
+        // them to admit a request, so that a request is always either pending or remembered.
+        let (requests, dropped) = {
+            let mut replay_cache = self.replay_cache.lock().await;
+            let batch = self.mempool.lock().await.next_batch();
+            let now_ms = unix_time_ms();
+            let mut requests = Vec::new();
+            let mut dropped = Vec::new();
+            for request in batch {
+                match replay_cache.admit(&request.commit, now_ms) {
+                    Ok(()) => requests.push(request),
+                    // A request the replay cache failed to save could be proposed again after a
+                    // restart, so it fails like a stale one.
+                    Err(e @ ReplayError::Persist(_)) => {
+                        error!(
+                            "Space Station\tDropping a ground request from slot {}: {}",
+                            tick.i, e
+                        );
+                        dropped.push(request.ticket);
+                    }
+                    Err(e) => {
+                        info!(
+                            "Space Station\tDropping a ground request from slot {}: {}",
+                            tick.i, e
+                        );
+                        dropped.push(request.ticket);
+                    }
+                }
+            }
+            (requests, dropped)
+        };
+
+        let mut ledger = self.ledger.lock().await;
+        let mut batches = self.batches.lock().await;
+        let mut tickets = self.tickets.lock().await;
+        for ticket in dropped {
+            tickets.fail(ticket);
+        }
+        // A batch of a slot the ledger gave up on will never be finalized, dropping it lets its
+        // requests fail instead of waiting forever.
+        let given_up: Vec<u32> = batches
+            .keys()
+            .copied()
+            .filter(|&i| {
+                ledger
+                    .record(i)
+                    .is_none_or(|r| r.outcome != SlotOutcome::Pending)
+            })
+            .collect();
+        for i in given_up {
+            for ticket in batches.remove(&i).unwrap().tickets {
+                tickets.fail(ticket);
+            }
+        }
+
+        if requests.is_empty() {
+            return;
+        }
+
+        let msgs: Vec<&[u8]> = requests.iter().map(|r| r.commit.msg.as_slice()).collect();
+        let tree = MerkleTree::new(&msgs);
+        let mut proposal = Commit {
+            typ: CommitType::Precommit.into(),
+            i: tick.i,
+            j: 0,
+            aggregated: false,
+            public_key: Vec::new(),
+            msg: tree.root(),
+            signature: Vec::new(),
+            signer_id: 0,
+            requests: requests.iter().map(|r| r.commit.clone()).collect(),
+            timestamp_ms: 0,
+            nonce: 0,
+            scheme: 0,
+            signers: Vec::new(),
+        };
+        sign_proposal(&mut proposal, &Bn256Scheme, &self.private_key);
+        info!(
+            "Space Station\tProposing {} ground requests in slot {}",
+            requests.len(),
+            tick.i
+        );
+
+        ledger.propose(tick.i, tree.root());
+        drop(ledger);
+        for request in &requests {
+            tickets.propose(request.ticket, tick.i);
+        }
+        drop(tickets);
+        batches.insert(
+            tick.i,
+            Batch {
+                tree,
+                tickets: requests.into_iter().map(|r| r.ticket).collect(),
+            },
+        );
+        drop(batches);
+
+        if let Err(e) = self.link.broadcast(proposal).await {
+            info!(
+                "Space Station\tFailed to send a request to the Bounce units: {}",
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        new_member, sign_request, verify_response, Bn256Scheme, BounceConfig, ChannelLink, Cubesat,
+        FailureMode, KeySigner, MempoolConfig, Registry, RelayStrategy, RequestState, Router,
+        SignatureScheme, SPACE_STATION,
+    };
+    use std::collections::HashMap;
+    use tokio::sync::mpsc;
+
+    const GROUND_STATION_PRIVATE_KEY: [u8; 32] = [9; 32];
+    const SPACE_STATION_PRIVATE_KEY: [u8; 32] = [8; 32];
+    const WINDOW_MS: u64 = 60_000;
+
+    fn request(msg: &[u8], timestamp_ms: u64) -> Commit {
+        let mut request = Commit {
+            typ: CommitType::Precommit.into(),
+            msg: msg.to_vec(),
+            timestamp_ms,
+            ..Default::default()
+        };
+        sign_request(&mut request, &Bn256Scheme, &GROUND_STATION_PRIVATE_KEY);
+        request
+    }
+
+    #[tokio::test]
+    async fn stale_request_is_dropped_from_its_batch_test() {
+        let config = BounceConfig {
+            num_cubesats: 1,
+            members: vec![new_member(&Bn256Scheme, 0, &[1; 32])],
+            ground_station_keys: vec![Bn256Scheme
+                .derive_public_key(&GROUND_STATION_PRIVATE_KEY)
+                .unwrap()],
+            request_window_ms: WINDOW_MS,
+            replay_cache_size: 100,
+            space_station_key: Bn256Scheme
+                .derive_public_key(&SPACE_STATION_PRIVATE_KEY)
+                .unwrap(),
+            ..Default::default()
+        };
+        let registry = Registry::from_config(&config).unwrap();
+
+        // A flock of a single Bounce unit, which certifies a proposal on its own.
+        let (station_tx, station_rx) = mpsc::channel(10);
+        let (unit_tx, unit_rx) = mpsc::channel(10);
+        let (timer_tx, timer_rx) = broadcast::channel(10);
+        let mut cubesat = Cubesat::new(
+            0,
+            &config,
+            Box::new(KeySigner::new(&Bn256Scheme, vec![1; 32]).unwrap()),
+            Box::new(ChannelLink::new(unit_rx).with_peer(SPACE_STATION, station_tx)),
+            timer_rx,
+            FailureMode::Honest,
+        )
+        .unwrap();
+        tokio::spawn(async move { cubesat.run().await });
+        let link: Arc<dyn Link> = Arc::new(ChannelLink::new(station_rx).with_peer(0, unit_tx));
+
+        let ledger = Arc::new(Mutex::new(Ledger::new(10)));
+        let mempool = Arc::new(Mutex::new(Mempool::new(MempoolConfig::default())));
+        let batches: Batches = Arc::new(Mutex::new(HashMap::new()));
+        let tickets = Arc::new(Mutex::new(TicketBook::new(10)));
+        let mut proposer = Proposer::new(
+            link.clone(),
+            ledger.clone(),
+            mempool.clone(),
+            Arc::new(Mutex::new(ReplayCache::new(WINDOW_MS, 100))),
+            batches.clone(),
+            tickets.clone(),
+            SPACE_STATION_PRIVATE_KEY.to_vec(),
+        );
+        let mut router = Router::new(
+            link.clone(),
+            registry.clone(),
+            RelayStrategy::Targeted,
+            ledger,
+            batches,
+            tickets.clone(),
+            broadcast::channel(10).0,
+        );
+
+        // The first request went stale while it waited in the mempool.
+        let now_ms = unix_time_ms();
+        let requests = [
+            request(b"hello", now_ms - 2 * WINDOW_MS),
+            request(b"world", now_ms),
+        ];
+        let mut numbers = Vec::new();
+        for (k, request) in requests.iter().enumerate() {
+            let ticket = tickets.lock().await.issue();
+            numbers.push(ticket);
+            mempool
+                .lock()
+                .await
+                .admit(
+                    &request.public_key,
+                    vec![k as u8],
+                    PendingRequest {
+                        commit: request.clone(),
+                        ticket,
+                    },
+                )
+                .unwrap();
+        }
+
+        let tick = Tick {
+            i: 1,
+            phase: Phase::First,
+        };
+        timer_tx.send(tick.clone()).unwrap();
+        proposer.tick(&tick).await;
+        assert_eq!(
+            tickets.lock().await.result(numbers[0]).state(),
+            RequestState::Failed
+        );
+
+        // The rest of the batch is certified, and answered with the proof of its message.
+        let certificate = loop {
+            let commit = link.recv().await.unwrap();
+            if commit.aggregated {
+                break commit;
+            }
+        };
+        assert_eq!(certificate.typ(), CommitType::Precommit);
+        router.route(certificate).await;
+        let result = tickets.lock().await.result(numbers[1]);
+        assert_eq!(result.state(), RequestState::Finalized);
+        assert!(verify_response(
+            b"world",
+            result.response.as_ref().unwrap(),
+            &registry
+        ));
+    }
+}
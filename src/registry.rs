@@ -0,0 +1,333 @@
+use crate::{
+    signature_scheme, supermajority, BounceConfig, Commit, Member, SchemeError, SignatureScheme,
+    VoteError,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Registers the Bounce unit `id` holding `private_key`.
+pub fn new_member(scheme: &dyn SignatureScheme, id: u32, private_key: &[u8]) -> Member {
+    Member {
+        id,
+        public_key: scheme.derive_public_key(private_key).unwrap(),
+        proof_of_possession: scheme.prove_possession(private_key).unwrap(),
+        address: String::new(),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RegistryError {
+    // The proof of possession of the member with this id does not verify.
+    InvalidProof(u32),
+    // The member with this id reuses the id or the key of another member.
+    Duplicate(u32),
+    // The key of this Bounce unit is not registered.
+    NotAMember,
+    // The flock names a scheme without an implementation.
+    Scheme(SchemeError),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::InvalidProof(id) => {
+                write!(f, "invalid proof of possession for member {}", id)
+            }
+            RegistryError::Duplicate(id) => write!(f, "member {} is registered twice", id),
+            RegistryError::NotAMember => write!(f, "the key is not registered in the flock"),
+            RegistryError::Scheme(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+#[derive(Debug, PartialEq)]
+pub enum CertificateError {
+    // The commit is a single vote, not an aggregate.
+    NotAggregated,
+    // The certificate was signed with another scheme than the one of the flock.
+    SchemeMismatch,
+    // The signer bitmap selects this id, which is not a member of the flock.
+    UnknownSigner(u32),
+    // The signer bitmap selects this many members, fewer than a supermajority.
+    NoSupermajority(usize),
+    // The signature does not verify under the aggregate key of the selected members.
+    BadSignature,
+}
+
+impl fmt::Display for CertificateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertificateError::NotAggregated => write!(f, "the commit is not aggregated"),
+            CertificateError::SchemeMismatch => {
+                write!(f, "the certificate uses another signature scheme")
+            }
+            CertificateError::UnknownSigner(id) => {
+                write!(f, "signer {} is not a member of the flock", id)
+            }
+            CertificateError::NoSupermajority(n) => {
+                write!(f, "only {} members signed, short of a supermajority", n)
+            }
+            CertificateError::BadSignature => write!(f, "the certificate signature is invalid"),
+        }
+    }
+}
+
+impl std::error::Error for CertificateError {}
+
+/// Public keys of the members of the flock, each with a verified proof of possession, and the
+/// scheme the flock signs with.
+#[derive(Clone)]
+pub struct Registry {
+    scheme: &'static dyn SignatureScheme,
+    ids: HashMap<Vec<u8>, u32>,
+    keys: BTreeMap<u32, Vec<u8>>,
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("scheme", &self.scheme.id())
+            .field("ids", &self.ids)
+            .finish()
+    }
+}
+
+impl Registry {
+    pub fn new(
+        scheme: &'static dyn SignatureScheme,
+        members: &[Member],
+    ) -> Result<Self, RegistryError> {
+        let mut ids = HashMap::new();
+        let mut keys = BTreeMap::new();
+        for member in members {
+            if scheme
+                .verify_possession(&member.public_key, &member.proof_of_possession)
+                .is_err()
+            {
+                return Err(RegistryError::InvalidProof(member.id));
+            }
+            if keys.insert(member.id, member.public_key.clone()).is_some()
+                || ids.insert(member.public_key.clone(), member.id).is_some()
+            {
+                return Err(RegistryError::Duplicate(member.id));
+            }
+        }
+        Ok(Registry { scheme, ids, keys })
+    }
+
+    pub fn from_config(bounce_config: &BounceConfig) -> Result<Self, RegistryError> {
+        let scheme = signature_scheme(bounce_config.scheme).map_err(RegistryError::Scheme)?;
+        Registry::new(scheme, &bounce_config.members)
+    }
+
+    pub fn scheme(&self) -> &'static dyn SignatureScheme {
+        self.scheme
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn contains(&self, public_key: &[u8]) -> bool {
+        self.ids.contains_key(public_key)
+    }
+
+    pub fn id_of(&self, public_key: &[u8]) -> Option<u32> {
+        self.ids.get(public_key).copied()
+    }
+
+    pub fn public_key(&self, id: u32) -> Option<&[u8]> {
+        self.keys.get(&id).map(Vec::as_slice)
+    }
+
+    /// The ids of the members, in increasing order.
+    pub fn ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.keys.keys().copied()
+    }
+
+    /// Checks that `vote` is a single signature of a member, and returns the id of its signer.
+    pub fn verify_vote(&self, vote: &Commit) -> Result<u32, VoteError> {
+        if vote.scheme != self.scheme.id() as i32 {
+            return Err(VoteError::SchemeMismatch);
+        }
+        let id = self
+            .id_of(&vote.public_key)
+            .ok_or(VoteError::UnknownSigner)?;
+        self.scheme
+            .verify(&vote.signature, &vote.msg, &vote.public_key)
+            .map_err(|_| VoteError::BadSignature)?;
+        Ok(id)
+    }
+
+    /// Checks that `certificate` aggregates the votes of a supermajority of the members: its
+    /// signature must verify under the aggregate of the keys of the members selected by its signer
+    /// bitmap, in the order of their ids. The public key carried by the certificate is not trusted.
+    pub fn verify_certificate(&self, certificate: &Commit) -> Result<(), CertificateError> {
+        if !certificate.aggregated {
+            return Err(CertificateError::NotAggregated);
+        }
+        if certificate.scheme != self.scheme.id() as i32 {
+            return Err(CertificateError::SchemeMismatch);
+        }
+        let mut public_keys = Vec::new();
+        for (byte, bits) in certificate.signers.iter().enumerate() {
+            for bit in (0..8).filter(|bit| bits & (1 << bit) != 0) {
+                let id = byte as u32 * 8 + bit;
+                let public_key = self
+                    .public_key(id)
+                    .ok_or(CertificateError::UnknownSigner(id))?;
+                public_keys.push(public_key);
+            }
+        }
+        if public_keys.len() < supermajority(self.len()) {
+            return Err(CertificateError::NoSupermajority(public_keys.len()));
+        }
+        self.scheme
+            .verify_aggregate(&certificate.signature, &certificate.msg, &public_keys)
+            .map_err(|_| CertificateError::BadSignature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::CommitType;
+    use crate::Bn256Scheme;
+
+    #[test]
+    fn registry_test() {
+        let members: Vec<Member> = (0..3)
+            .map(|id| new_member(&Bn256Scheme, id, &[id as u8 + 1; 32]))
+            .collect();
+        let registry = Registry::new(&Bn256Scheme, &members).unwrap();
+
+        assert_eq!(registry.len(), 3);
+        assert_eq!(registry.id_of(&members[2].public_key), Some(2));
+        let outsider = Bn256Scheme.derive_public_key(&[7; 32]).unwrap();
+        assert!(!registry.contains(&outsider));
+    }
+
+    fn certificate(private_keys: &[Vec<u8>], signers: Vec<u8>, msg: &[u8]) -> Commit {
+        let signatures: Vec<Vec<u8>> = private_keys
+            .iter()
+            .map(|k| Bn256Scheme.sign(k, msg).unwrap())
+            .collect();
+        let signatures: Vec<&[u8]> = signatures.iter().map(Vec::as_slice).collect();
+        Commit {
+            typ: CommitType::Precommit.into(),
+            aggregated: true,
+            msg: msg.to_vec(),
+            signature: Bn256Scheme.aggregate_signatures(&signatures).unwrap(),
+            signers,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn verify_certificate_test() {
+        let private_keys: Vec<Vec<u8>> = (0..4).map(|id| vec![id as u8 + 1; 32]).collect();
+        let members: Vec<Member> = (0..4)
+            .map(|id| new_member(&Bn256Scheme, id, &private_keys[id as usize]))
+            .collect();
+        let registry = Registry::new(&Bn256Scheme, &members).unwrap();
+        assert_eq!(registry.ids().collect::<Vec<_>>(), [0, 1, 2, 3]);
+
+        let valid = certificate(&private_keys[1..], vec![0b1110], b"root");
+        assert_eq!(registry.verify_certificate(&valid), Ok(()));
+
+        // The bitmap must name exactly the members that signed.
+        let wrong_signers = certificate(&private_keys[1..], vec![0b0111], b"root");
+        assert_eq!(
+            registry.verify_certificate(&wrong_signers),
+            Err(CertificateError::BadSignature)
+        );
+        let too_few = certificate(&private_keys[..2], vec![0b0011], b"root");
+        assert_eq!(
+            registry.verify_certificate(&too_few),
+            Err(CertificateError::NoSupermajority(2))
+        );
+        let outsider = certificate(&[vec![7; 32]], vec![0b1_0000], b"root");
+        assert_eq!(
+            registry.verify_certificate(&outsider),
+            Err(CertificateError::UnknownSigner(4))
+        );
+        let mut vote = valid.clone();
+        vote.aggregated = false;
+        assert_eq!(
+            registry.verify_certificate(&vote),
+            Err(CertificateError::NotAggregated)
+        );
+
+        let vote = certificate(&private_keys[2..3], Vec::new(), b"root");
+        let vote = Commit {
+            aggregated: false,
+            public_key: members[2].public_key.clone(),
+            ..vote
+        };
+        assert_eq!(registry.verify_vote(&vote), Ok(2));
+        let forged = Commit {
+            public_key: members[1].public_key.clone(),
+            ..vote.clone()
+        };
+        assert_eq!(registry.verify_vote(&forged), Err(VoteError::BadSignature));
+        let outsider = Commit {
+            public_key: Bn256Scheme.derive_public_key(&[7; 32]).unwrap(),
+            ..vote
+        };
+        assert_eq!(
+            registry.verify_vote(&outsider),
+            Err(VoteError::UnknownSigner)
+        );
+    }
+
+    #[test]
+    fn invalid_proof_test() {
+        let mut members: Vec<Member> = (0..3)
+            .map(|id| new_member(&Bn256Scheme, id, &[id as u8 + 1; 32]))
+            .collect();
+
+        // A rogue key, e.g. chosen to cancel out the others in an aggregate, comes without a valid
+        // proof since its private key is unknown. Reusing the proof of another key does not help.
+        members[2].public_key = Bn256Scheme.derive_public_key(&[7; 32]).unwrap();
+        assert_eq!(
+            Registry::new(&Bn256Scheme, &members).unwrap_err(),
+            RegistryError::InvalidProof(2)
+        );
+
+        // A plain signature over the key without the domain is not a proof either.
+        members[2].proof_of_possession =
+            Bn256Scheme.sign(&[7; 32], &members[2].public_key).unwrap();
+        assert_eq!(
+            Registry::new(&Bn256Scheme, &members).unwrap_err(),
+            RegistryError::InvalidProof(2)
+        );
+
+        members[2] = new_member(&Bn256Scheme, 2, &[7; 32]);
+        assert!(Registry::new(&Bn256Scheme, &members).is_ok());
+    }
+
+    #[test]
+    fn duplicate_member_test() {
+        let a = new_member(&Bn256Scheme, 0, &[1; 32]);
+        let b = new_member(&Bn256Scheme, 1, &[2; 32]);
+        let mut same_id = new_member(&Bn256Scheme, 0, &[3; 32]);
+
+        assert_eq!(
+            Registry::new(&Bn256Scheme, &[a.clone(), b.clone(), same_id.clone()]).unwrap_err(),
+            RegistryError::Duplicate(0)
+        );
+        same_id.id = 2;
+        let mut same_key = a.clone();
+        same_key.id = 3;
+        assert_eq!(
+            Registry::new(&Bn256Scheme, &[a, b, same_id, same_key]).unwrap_err(),
+            RegistryError::Duplicate(3)
+        );
+    }
+}
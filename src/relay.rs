@@ -0,0 +1,144 @@
+use crate::{Commit, Registry};
+use std::collections::{BTreeMap, HashSet};
+
+/// How the space station relays the single signatures of the Bounce units to each other.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RelayStrategy {
+    // Every vote is sent to every unit, its signer included.
+    Broadcast,
+    // A vote is sent once to every unit but its signer, and not at all once its slot is final.
+    Targeted,
+    // Nothing is relayed, the units send their votes to each other over their own links.
+    Direct,
+}
+
+impl RelayStrategy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "broadcast" => Some(RelayStrategy::Broadcast),
+            "targeted" => Some(RelayStrategy::Targeted),
+            "direct" => Some(RelayStrategy::Direct),
+            _ => None,
+        }
+    }
+}
+
+/// Picks the Bounce units a vote is relayed to, among the members of the flock.
+#[derive(Debug)]
+pub struct Relay {
+    strategy: RelayStrategy,
+    registry: Registry,
+    // Votes already relayed in the latest slots, by commit type and public key.
+    relayed: BTreeMap<u32, HashSet<(i32, Vec<u8>)>>,
+}
+
+impl Relay {
+    pub fn new(strategy: RelayStrategy, registry: Registry) -> Self {
+        Relay {
+            strategy,
+            registry,
+            relayed: BTreeMap::new(),
+        }
+    }
+
+    /// The ids of the units to send `vote` to, given whether its slot is already finalized. The
+    /// signer of the vote is the member holding its public key, whatever id the vote claims.
+    pub fn targets(&mut self, vote: &Commit, finalized: bool) -> Vec<u32> {
+        let all = self.registry.ids();
+        if self.strategy == RelayStrategy::Broadcast {
+            return all.collect();
+        }
+        if finalized || self.strategy == RelayStrategy::Direct {
+            return Vec::new();
+        }
+        let signer = match self.registry.id_of(&vote.public_key) {
+            Some(id) => id,
+            None => return Vec::new(),
+        };
+
+        // Votes arrive for the current slot, or the previous one from a unit lagging behind.
+        self.relayed.retain(|&i, _| i + 1 >= vote.i);
+        let first = self
+            .relayed
+            .entry(vote.i)
+            .or_default()
+            .insert((vote.typ, vote.public_key.clone()));
+        if !first {
+            return Vec::new();
+        }
+        all.filter(|id| *id != signer).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{new_member, Bn256Scheme, SignatureScheme};
+
+    fn private_key(id: u32) -> [u8; 32] {
+        [id as u8 + 1; 32]
+    }
+
+    fn registry(ids: &[u32]) -> Registry {
+        let members: Vec<_> = ids
+            .iter()
+            .map(|&id| new_member(&Bn256Scheme, id, &private_key(id)))
+            .collect();
+        Registry::new(&Bn256Scheme, &members).unwrap()
+    }
+
+    fn vote(i: u32, signer_id: u32) -> Commit {
+        Commit {
+            i,
+            signer_id,
+            public_key: Bn256Scheme
+                .derive_public_key(&private_key(signer_id))
+                .unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn targeted_relay_test() {
+        // Member ids need not be consecutive, nor start at 0.
+        let mut relay = Relay::new(RelayStrategy::Targeted, registry(&[3, 8, 10, 12]));
+
+        assert_eq!(relay.targets(&vote(5, 8), false), vec![3, 10, 12]);
+        // The same vote relayed back to the space station is not sent again.
+        assert!(relay.targets(&vote(5, 8), false).is_empty());
+        assert_eq!(relay.targets(&vote(5, 10), false), vec![3, 8, 12]);
+        // Nor is any vote once the slot is final.
+        assert!(relay.targets(&vote(5, 12), true).is_empty());
+
+        // Votes of the next slot are new.
+        assert_eq!(relay.targets(&vote(6, 8), false), vec![3, 10, 12]);
+
+        // The signer is found from the public key of the vote, not the id it claims.
+        let spoofed = Commit {
+            signer_id: 3,
+            ..vote(7, 8)
+        };
+        assert_eq!(relay.targets(&spoofed, false), vec![3, 10, 12]);
+        // And the votes of outsiders are not relayed.
+        assert!(relay.targets(&vote(7, 0), false).is_empty());
+    }
+
+    #[test]
+    fn broadcast_relay_test() {
+        let mut relay = Relay::new(RelayStrategy::Broadcast, registry(&[0, 1, 5]));
+        for _ in 0..2 {
+            assert_eq!(relay.targets(&vote(5, 1), true), vec![0, 1, 5]);
+        }
+        assert_eq!(
+            RelayStrategy::from_name("targeted"),
+            Some(RelayStrategy::Targeted)
+        );
+        assert_eq!(RelayStrategy::from_name("gossip"), None);
+    }
+
+    #[test]
+    fn direct_relay_test() {
+        let mut relay = Relay::new(RelayStrategy::Direct, registry(&[0, 1, 2]));
+        assert!(relay.targets(&vote(5, 1), false).is_empty());
+    }
+}
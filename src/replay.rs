@@ -0,0 +1,362 @@
+use crate::{request_payload, Commit};
+use sha2::{Digest, Sha256};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+#[derive(Debug, PartialEq)]
+pub enum ReplayError {
+    // The request timestamp is outside of the freshness window.
+    Stale,
+    // The same request was already seen.
+    Replayed,
+    // The request could not be saved to the file of the cache.
+    Persist(String),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Stale => {
+                write!(f, "the request timestamp is outside the freshness window")
+            }
+            ReplayError::Replayed => write!(f, "the request was already submitted"),
+            ReplayError::Persist(e) => write!(f, "failed to persist the replay cache: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Identifies a ground request by everything its signature covers and by its signer.
+pub fn request_id(request: &Commit) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(&request.public_key);
+    hasher.update(request_payload(request));
+    hasher.finalize().to_vec()
+}
+
+enum Line {
+    Floor(u64),
+    Entry(u64, Vec<u8>),
+}
+
+fn parse_line(line: &str) -> Option<Line> {
+    let mut fields = line.split_whitespace();
+    let line = match (fields.next()?, fields.next()?) {
+        ("floor", floor_ms) => Line::Floor(floor_ms.parse().ok()?),
+        (timestamp_ms, id) => Line::Entry(timestamp_ms.parse().ok()?, hex::decode(id).ok()?),
+    };
+    Some(line)
+}
+
+/// Ground requests seen within the freshness window.
+///
+/// A request is only accepted if its timestamp is within `window_ms` of the current time and it
+/// was not seen before. Requests older than the window are forgotten since they are refused as
+/// stale anyway. When more than `capacity` requests are fresh, the oldest are forgotten too and the
+/// window shrinks to exclude them, so that a forgotten request can never be accepted again.
+///
+/// With a path, accepted requests are appended to a file from which the cache is restored on
+/// restart.
+pub struct ReplayCache {
+    window_ms: u64,
+    capacity: usize,
+    // Requests with a timestamp at or below this were forgotten.
+    floor_ms: u64,
+    // Accepted requests ordered by timestamp.
+    entries: VecDeque<(u64, Vec<u8>)>,
+    ids: HashSet<Vec<u8>>,
+
+    path: Option<PathBuf>,
+    log: Option<BufWriter<File>>,
+    // Lines in the file, compacted when it grows well beyond the entries it still needs.
+    log_len: usize,
+}
+
+impl ReplayCache {
+    /// A cache that lives only in memory.
+    pub fn new(window_ms: u64, capacity: usize) -> Self {
+        ReplayCache {
+            window_ms,
+            capacity,
+            floor_ms: 0,
+            entries: VecDeque::new(),
+            ids: HashSet::new(),
+            path: None,
+            log: None,
+            log_len: 0,
+        }
+    }
+
+    /// A cache persisted to `path`, restoring the requests it holds.
+    pub fn open(
+        path: impl Into<PathBuf>,
+        window_ms: u64,
+        capacity: usize,
+        now_ms: u64,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        let mut cache = ReplayCache::new(window_ms, capacity);
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                for line in contents.lines() {
+                    match parse_line(line) {
+                        Some(Line::Floor(floor_ms)) => {
+                            cache.floor_ms = cache.floor_ms.max(floor_ms)
+                        }
+                        Some(Line::Entry(timestamp_ms, id)) => cache.insert(timestamp_ms, id),
+                        None => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!("malformed replay cache entry: {}", line),
+                            ))
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        cache.evict(now_ms);
+
+        cache.path = Some(path);
+        cache.compact()?;
+        Ok(cache)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Checks that `request` is fresh and was not seen before, without remembering it.
+    pub fn check(&self, request: &Commit, now_ms: u64) -> Result<(), ReplayError> {
+        self.check_id(request.timestamp_ms, &request_id(request), now_ms)
+    }
+
+    /// Checks `request` and remembers it, so that it is refused from then on.
+    pub fn admit(&mut self, request: &Commit, now_ms: u64) -> Result<(), ReplayError> {
+        let id = request_id(request);
+        self.check_id(request.timestamp_ms, &id, now_ms)?;
+
+        self.evict(now_ms);
+        self.insert(request.timestamp_ms, id.clone());
+
+        // The request is remembered in memory either way, but a restart would let it through.
+        self.persist(request.timestamp_ms, &id)
+            .map_err(|e| ReplayError::Persist(e.to_string()))
+    }
+
+    fn persist(&mut self, timestamp_ms: u64, id: &[u8]) -> io::Result<()> {
+        if let Some(log) = &mut self.log {
+            writeln!(log, "{} {}", timestamp_ms, hex::encode(id))?;
+            log.flush()?;
+            self.log_len += 1;
+        }
+        if self.log_len > 2 * self.capacity {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    fn check_id(&self, timestamp_ms: u64, id: &[u8], now_ms: u64) -> Result<(), ReplayError> {
+        let oldest_ms = now_ms.saturating_sub(self.window_ms).max(self.floor_ms + 1);
+        if timestamp_ms < oldest_ms || timestamp_ms > now_ms.saturating_add(self.window_ms) {
+            return Err(ReplayError::Stale);
+        }
+        if self.ids.contains(id) {
+            return Err(ReplayError::Replayed);
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, timestamp_ms: u64, id: Vec<u8>) {
+        if timestamp_ms <= self.floor_ms || !self.ids.insert(id.clone()) {
+            return;
+        }
+        // Requests mostly arrive in order, so this is usually a push to the back.
+        let at = self
+            .entries
+            .iter()
+            .rposition(|(t, _)| *t <= timestamp_ms)
+            .map_or(0, |k| k + 1);
+        self.entries.insert(at, (timestamp_ms, id));
+
+        while self.entries.len() > self.capacity {
+            self.forget_oldest();
+        }
+    }
+
+    fn evict(&mut self, now_ms: u64) {
+        let oldest_ms = now_ms.saturating_sub(self.window_ms);
+        while matches!(self.entries.front(), Some((t, _)) if *t < oldest_ms) {
+            self.forget_oldest();
+        }
+    }
+
+    fn forget_oldest(&mut self) {
+        if let Some((timestamp_ms, id)) = self.entries.pop_front() {
+            self.ids.remove(&id);
+            self.floor_ms = self.floor_ms.max(timestamp_ms);
+        }
+    }
+
+    // Rewrites the file with only the requests still remembered.
+    fn compact(&mut self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let tmp = path.with_extension("tmp");
+        {
+            let mut file = BufWriter::new(File::create(&tmp)?);
+            // Requests forgotten to stay within capacity are kept out by the floor.
+            writeln!(file, "floor {}", self.floor_ms)?;
+            for (timestamp_ms, id) in &self.entries {
+                writeln!(file, "{} {}", timestamp_ms, hex::encode(id))?;
+            }
+            file.flush()?;
+        }
+        fs::rename(&tmp, path)?;
+
+        self.log = Some(BufWriter::new(OpenOptions::new().append(true).open(path)?));
+        self.log_len = self.entries.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(timestamp_ms: u64, nonce: u64) -> Commit {
+        Commit {
+            msg: b"hello".to_vec(),
+            timestamp_ms,
+            nonce,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn freshness_window_test() {
+        let mut cache = ReplayCache::new(1000, 10);
+
+        assert_eq!(cache.admit(&request(5000, 0), 5000), Ok(()));
+        assert_eq!(cache.admit(&request(4000, 0), 5000), Ok(()));
+        assert_eq!(cache.admit(&request(6000, 0), 5000), Ok(()));
+        assert_eq!(
+            cache.admit(&request(3999, 0), 5000),
+            Err(ReplayError::Stale)
+        );
+        assert_eq!(
+            cache.admit(&request(6001, 0), 5000),
+            Err(ReplayError::Stale)
+        );
+
+        assert_eq!(
+            cache.admit(&request(5000, 0), 5500),
+            Err(ReplayError::Replayed)
+        );
+        assert_eq!(cache.admit(&request(5000, 1), 5500), Ok(()));
+
+        // Requests older than the window are forgotten, they are stale by now.
+        assert_eq!(cache.admit(&request(6000, 1), 6200), Ok(()));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache.check(&request(5000, 0), 6200),
+            Err(ReplayError::Stale)
+        );
+    }
+
+    #[test]
+    fn capacity_test() {
+        let mut cache = ReplayCache::new(1000, 2);
+
+        cache.admit(&request(5000, 0), 5000).unwrap();
+        cache.admit(&request(5100, 0), 5100).unwrap();
+        cache.admit(&request(4900, 0), 5100).unwrap();
+        assert_eq!(cache.len(), 2);
+
+        // The oldest request was forgotten to make room, and it must not be accepted again, so
+        // the window now starts after it.
+        assert_eq!(
+            cache.check(&request(4900, 0), 5100),
+            Err(ReplayError::Stale)
+        );
+        assert_eq!(
+            cache.check(&request(4900, 1), 5100),
+            Err(ReplayError::Stale)
+        );
+        assert_eq!(
+            cache.check(&request(5000, 0), 5100),
+            Err(ReplayError::Replayed)
+        );
+        assert_eq!(cache.check(&request(5001, 0), 5100), Ok(()));
+    }
+
+    #[test]
+    fn survives_restart_test() {
+        let dir = std::env::temp_dir().join(format!("bounce-replay-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("replay-cache");
+        let _ = fs::remove_file(&path);
+
+        {
+            let mut cache = ReplayCache::open(&path, 1000, 3, 5000).unwrap();
+            for k in 0..10 {
+                cache.admit(&request(5000 + k, 0), 5000 + k).unwrap();
+            }
+        }
+
+        let cache = ReplayCache::open(&path, 1000, 3, 5010).unwrap();
+        assert_eq!(cache.len(), 3);
+        assert_eq!(
+            cache.check(&request(5009, 0), 5010),
+            Err(ReplayError::Replayed)
+        );
+        assert_eq!(
+            cache.check(&request(5005, 0), 5010),
+            Err(ReplayError::Stale)
+        );
+        assert_eq!(cache.check(&request(5010, 0), 5010), Ok(()));
+
+        // The file only keeps what is still needed.
+        let lines = fs::read_to_string(&path).unwrap().lines().count();
+        assert_eq!(lines, 4);
+
+        fs::write(&path, "garbage").unwrap();
+        assert!(ReplayCache::open(&path, 1000, 3, 5010).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn persist_error_test() {
+        let dir = std::env::temp_dir().join(format!("bounce-replay-lost-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut cache = ReplayCache::open(dir.join("replay-cache"), 1000, 1, 5000).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // The file is appended to until it is compacted, which fails without its directory.
+        cache.admit(&request(5000, 0), 5000).unwrap();
+        cache.admit(&request(5001, 0), 5001).unwrap();
+        assert!(matches!(
+            cache.admit(&request(5002, 0), 5002),
+            Err(ReplayError::Persist(_))
+        ));
+        // The request is still refused until a restart.
+        assert_eq!(
+            cache.check(&request(5002, 0), 5002),
+            Err(ReplayError::Replayed)
+        );
+    }
+}
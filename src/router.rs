@@ -0,0 +1,357 @@
+use crate::commit::CommitType;
+use crate::{
+    BounceResponse, Commit, Ledger, Link, LinkError, MerkleTree, Registry, Relay, RelayStrategy,
+    SlotStatusResponse, TicketBook,
+};
+use log::{info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{broadcast, mpsc, Mutex};
+
+// Number of votes waiting to be sent to a unit before further votes for it are dropped.
+const OUTBOX: usize = 64;
+
+/// Ground requests proposed together in one slot under a single Merkle root, answered in the order
+/// of their tickets.
+pub struct Batch {
+    pub tree: MerkleTree,
+    pub tickets: Vec<u64>,
+}
+
+/// The batches waiting for a certificate, by slot.
+pub type Batches = Arc<Mutex<HashMap<u32, Batch>>>;
+
+/// Routes what the Bounce units send to the space station: single signatures are relayed to the
+/// other units, and the first certificate that finalizes a slot answers the ground requests of its
+/// batch. Only votes and certificates of the members of the flock are used.
+pub struct Router {
+    link: Arc<dyn Link>,
+    registry: Registry,
+    relay: Relay,
+    ledger: Arc<Mutex<Ledger>>,
+    batches: Batches,
+    tickets: Arc<Mutex<TicketBook>>,
+    // Every slot finalized by a certificate, sent while the ledger is locked.
+    certificates_tx: broadcast::Sender<SlotStatusResponse>,
+    // Votes waiting to be relayed, by unit. Every unit is sent its votes in order by a task of its
+    // own, so that a unit falling behind never holds up the others.
+    outboxes: BTreeMap<u32, mpsc::Sender<Commit>>,
+}
+
+impl Router {
+    pub fn new(
+        link: Arc<dyn Link>,
+        registry: Registry,
+        relay_strategy: RelayStrategy,
+        ledger: Arc<Mutex<Ledger>>,
+        batches: Batches,
+        tickets: Arc<Mutex<TicketBook>>,
+        certificates_tx: broadcast::Sender<SlotStatusResponse>,
+    ) -> Self {
+        let outboxes = registry
+            .ids()
+            .map(|id| (id, spawn_outbox(link.clone(), id)))
+            .collect();
+        Router {
+            link,
+            relay: Relay::new(relay_strategy, registry.clone()),
+            registry,
+            ledger,
+            batches,
+            tickets,
+            certificates_tx,
+            outboxes,
+        }
+    }
+
+    /// Routes the commits of the units until the link closes.
+    pub async fn run(mut self) {
+        while let Some(commit) = self.link.recv().await {
+            self.route(commit).await;
+        }
+    }
+
+    pub async fn route(&mut self, commit: Commit) {
+        if commit.aggregated {
+            info!(
+                "Space Station\tReceived an aggregated signature from Bounce Unit {}",
+                commit.signer_id
+            );
+            self.answer(commit).await;
+        } else {
+            info!(
+                "Space Station\tReceived a single signature from Bounce Unit {}",
+                commit.signer_id
+            );
+            self.relay_vote(commit).await;
+        }
+    }
+
+    async fn answer(&mut self, certificate: Commit) {
+        // Every unit may aggregate, only the first certificate that finalizes its slot in the
+        // ledger answers the ground requests of the slot, so only that one is worth verifying.
+        if self.ledger.lock().await.is_finalized(certificate.i) {
+            return;
+        }
+        if let Err(e) = self.registry.verify_certificate(&certificate) {
+            warn!(
+                "Space Station\tDropping an invalid certificate from Bounce Unit {}: {}",
+                certificate.signer_id, e
+            );
+            return;
+        }
+        let mut ledger = self.ledger.lock().await;
+        if !ledger.finalize(&certificate) {
+            return;
+        }
+        // Nobody may be subscribed.
+        let _ = self.certificates_tx.send(ledger.status(certificate.i));
+        let batch = match self.batches.lock().await.remove(&certificate.i) {
+            Some(batch) => batch,
+            None => return,
+        };
+        drop(ledger);
+
+        let mut tickets = self.tickets.lock().await;
+        for (index, ticket) in batch.tickets.into_iter().enumerate() {
+            let proof = if certificate.typ() == CommitType::Precommit {
+                Some(batch.tree.proof(index))
+            } else {
+                None
+            };
+            tickets.finalize(
+                ticket,
+                BounceResponse {
+                    certificate: Some(certificate.clone()),
+                    proof,
+                },
+            );
+        }
+    }
+
+    async fn relay_vote(&mut self, vote: Commit) {
+        // The vote is recorded under the member holding its key, not the id it claims.
+        let vote = match self.registry.verify_vote(&vote) {
+            Ok(signer_id) => Commit { signer_id, ..vote },
+            Err(e) => {
+                warn!(
+                    "Space Station\tDropping an invalid vote from Bounce Unit {}: {}",
+                    vote.signer_id, e
+                );
+                return;
+            }
+        };
+        let targets = {
+            let mut ledger = self.ledger.lock().await;
+            ledger.vote(&vote);
+            let targets = self.relay.targets(&vote, ledger.is_finalized(vote.i));
+            ledger.count_relay(vote.i, targets.len(), self.registry.len());
+            targets
+        };
+        for id in targets {
+            let result = match self.outboxes[&id].try_send(vote.clone()) {
+                Ok(()) => continue,
+                Err(TrySendError::Full(_)) => LinkError::Congested(id),
+                Err(TrySendError::Closed(_)) => LinkError::Closed,
+            };
+            info!(
+                "Space Station\tFailed to send a request to Bounce Unit {}: {}",
+                id, result
+            );
+        }
+    }
+}
+
+// Sends the votes queued for unit `id` over `link`, one at a time.
+fn spawn_outbox(link: Arc<dyn Link>, id: u32) -> mpsc::Sender<Commit> {
+    let (tx, mut rx) = mpsc::channel::<Commit>(OUTBOX);
+    tokio::spawn(async move {
+        while let Some(commit) = rx.recv().await {
+            if let Err(e) = link.send(id, commit).await {
+                info!(
+                    "Space Station\tFailed to send a request to Bounce Unit {}: {}",
+                    id, e
+                );
+            }
+        }
+    });
+    tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        new_member, verify_response, Bn256Scheme, ChannelLink, Phase, RequestState,
+        SignatureScheme, SlotOutcome, Tick,
+    };
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    fn private_key(id: u32) -> Vec<u8> {
+        vec![id as u8 + 1; 32]
+    }
+
+    fn registry() -> Registry {
+        let members: Vec<_> = (0..3)
+            .map(|id| new_member(&Bn256Scheme, id, &private_key(id)))
+            .collect();
+        Registry::new(&Bn256Scheme, &members).unwrap()
+    }
+
+    fn vote(typ: CommitType, i: u32, id: u32) -> Commit {
+        Commit {
+            typ: typ.into(),
+            i,
+            msg: vec![i as u8],
+            public_key: Bn256Scheme.derive_public_key(&private_key(id)).unwrap(),
+            signature: Bn256Scheme.sign(&private_key(id), &[i as u8]).unwrap(),
+            signer_id: id,
+            ..Default::default()
+        }
+    }
+
+    // The certificate of slot `i` over `msg`, signed by the units of `ids`.
+    fn certificate(i: u32, msg: &[u8], ids: &[u32]) -> Commit {
+        let signatures: Vec<Vec<u8>> = ids
+            .iter()
+            .map(|&id| Bn256Scheme.sign(&private_key(id), msg).unwrap())
+            .collect();
+        let signatures: Vec<&[u8]> = signatures.iter().map(Vec::as_slice).collect();
+        Commit {
+            typ: CommitType::Precommit.into(),
+            i,
+            aggregated: true,
+            msg: msg.to_vec(),
+            signature: Bn256Scheme.aggregate_signatures(&signatures).unwrap(),
+            signers: vec![ids.iter().fold(0u8, |bitmap, id| bitmap | 1 << id)],
+            ..Default::default()
+        }
+    }
+
+    // A router over a channel link to the units 0, 1 and 2, whose inboxes hold `capacities` commits.
+    fn router(capacities: [usize; 3]) -> (Router, Vec<mpsc::Receiver<Commit>>) {
+        let (_, station_rx) = mpsc::channel(1);
+        let mut link = ChannelLink::new(station_rx);
+        let mut inboxes = Vec::new();
+        for (id, capacity) in capacities.iter().enumerate() {
+            let (tx, rx) = mpsc::channel(*capacity);
+            link = link.with_peer(id as u32, tx);
+            inboxes.push(rx);
+        }
+        let router = Router::new(
+            Arc::new(link),
+            registry(),
+            RelayStrategy::Targeted,
+            Arc::new(Mutex::new(Ledger::new(10))),
+            Arc::new(Mutex::new(HashMap::new())),
+            Arc::new(Mutex::new(TicketBook::new(10))),
+            broadcast::channel(10).0,
+        );
+        (router, inboxes)
+    }
+
+    #[tokio::test]
+    async fn router_answers_batch_test() {
+        let (mut router, _inboxes) = router([4; 3]);
+        let mut certificates_rx = router.certificates_tx.subscribe();
+
+        let msgs = [b"hello".to_vec(), b"world".to_vec()];
+        let tree = MerkleTree::new(&msgs);
+        let root = tree.root();
+        let tickets = {
+            let mut book = router.tickets.lock().await;
+            let tickets = vec![book.issue(), book.issue()];
+            for ticket in &tickets {
+                book.propose(*ticket, 5);
+            }
+            tickets
+        };
+        {
+            let mut ledger = router.ledger.lock().await;
+            ledger.tick(&Tick {
+                i: 5,
+                phase: Phase::First,
+            });
+            ledger.propose(5, root.clone());
+        }
+        router.batches.lock().await.insert(
+            5,
+            Batch {
+                tree,
+                tickets: tickets.clone(),
+            },
+        );
+
+        // Neither a certificate without a supermajority, nor one over another root, answers the
+        // batch.
+        router.route(certificate(5, &root, &[1])).await;
+        router.route(certificate(5, b"other", &[0, 1])).await;
+        assert!(!router.ledger.lock().await.is_finalized(5));
+        assert!(!router.tickets.lock().await.result(tickets[0]).is_settled());
+
+        router.route(certificate(5, &root, &[0, 2])).await;
+        assert_eq!(
+            router.ledger.lock().await.record(5).unwrap().outcome,
+            SlotOutcome::Committed
+        );
+        assert_eq!(certificates_rx.recv().await.unwrap().i, 5);
+        assert!(router.batches.lock().await.is_empty());
+        // Every request is answered with the proof of its own message.
+        for (ticket, msg) in tickets.iter().zip(&msgs) {
+            let result = router.tickets.lock().await.result(*ticket);
+            assert_eq!(result.state(), RequestState::Finalized);
+            assert!(verify_response(
+                msg,
+                result.response.as_ref().unwrap(),
+                &router.registry
+            ));
+        }
+
+        // Later certificates of the slot change nothing.
+        router.route(certificate(5, &root, &[0, 1, 2])).await;
+        assert!(timeout(Duration::from_millis(50), certificates_rx.recv())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn router_relays_past_slow_unit_test() {
+        // Unit 1 takes a single commit, and never receives it.
+        let (mut router, mut inboxes) = router([8, 1, 8]);
+
+        let votes = [
+            vote(CommitType::Precommit, 5, 0),
+            vote(CommitType::Noncommit, 5, 0),
+            vote(CommitType::Precommit, 6, 0),
+            vote(CommitType::Precommit, 6, 2),
+        ];
+        for vote in &votes {
+            router.route(vote.clone()).await;
+        }
+        // A vote claiming to be from a member without its key is not relayed.
+        router
+            .route(Commit {
+                signature: vote(CommitType::Precommit, 6, 2).signature,
+                ..vote(CommitType::Precommit, 6, 1)
+            })
+            .await;
+
+        // Units 0 and 2 get the votes of the others in order, while unit 1 is behind.
+        for (id, expected) in [(0, &votes[3..]), (2, &votes[..3])] {
+            for vote in expected {
+                assert_eq!(inboxes[id].recv().await.as_ref(), Some(vote));
+            }
+        }
+        // Unit 1 still gets every vote, in order, once it catches up.
+        for vote in &votes {
+            assert_eq!(inboxes[1].recv().await.as_ref(), Some(vote));
+        }
+        for inbox in &mut inboxes {
+            assert!(timeout(Duration::from_millis(50), inbox.recv())
+                .await
+                .is_err());
+        }
+    }
+}
@@ -0,0 +1,471 @@
+use crate::{SchemeId, LMS_SCHEME};
+use bls_signatures_rs::bn256::Bn256;
+use bls_signatures_rs::MultiSignature;
+use blst::min_pk;
+use blst::BLST_ERROR;
+use rand::{thread_rng, Rng};
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum SchemeError {
+    // A key or a signature could not be decoded, or the operation failed on it.
+    Malformed(String),
+    // The signature does not verify.
+    Invalid,
+    // No implementation for the scheme id in the commit.
+    Unsupported(i32),
+    // Every one-time key of a stateful private key has been used.
+    Exhausted,
+    // The state of a stateful private key could not be restored or saved.
+    KeyState(String),
+}
+
+impl fmt::Display for SchemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemeError::Malformed(e) => write!(f, "malformed key or signature: {}", e),
+            SchemeError::Invalid => write!(f, "the signature does not verify"),
+            SchemeError::Unsupported(id) => write!(f, "unsupported signature scheme {}", id),
+            SchemeError::Exhausted => write!(f, "every one-time key of the private key was used"),
+            SchemeError::KeyState(e) => write!(f, "failed to keep the state of the key: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SchemeError {}
+
+/// An aggregatable signature scheme, the only interface protocol code uses to sign and verify.
+///
+/// Keys and signatures are opaque byte strings in the encoding of the scheme, and every commit
+/// names the scheme it was signed with.
+pub trait SignatureScheme: Send + Sync {
+    fn id(&self) -> SchemeId;
+
+    fn generate_private_key(&self) -> Vec<u8>;
+
+    fn derive_public_key(&self, private_key: &[u8]) -> Result<Vec<u8>, SchemeError>;
+
+    fn sign(&self, private_key: &[u8], msg: &[u8]) -> Result<Vec<u8>, SchemeError>;
+
+    fn verify(&self, signature: &[u8], msg: &[u8], public_key: &[u8]) -> Result<(), SchemeError>;
+
+    fn aggregate_signatures(&self, signatures: &[&[u8]]) -> Result<Vec<u8>, SchemeError>;
+
+    fn aggregate_public_keys(&self, public_keys: &[&[u8]]) -> Result<Vec<u8>, SchemeError>;
+
+    /// Verifies a signature aggregated from signatures of `msg` under each of `public_keys`. The
+    /// keys must come with a proof of possession.
+    fn verify_aggregate(
+        &self,
+        signature: &[u8],
+        msg: &[u8],
+        public_keys: &[&[u8]],
+    ) -> Result<(), SchemeError> {
+        let public_key = self.aggregate_public_keys(public_keys)?;
+        self.verify(signature, msg, &public_key)
+    }
+
+    /// Proves that the holder of `private_key` registers its public key.
+    fn prove_possession(&self, private_key: &[u8]) -> Result<Vec<u8>, SchemeError>;
+
+    fn verify_possession(&self, public_key: &[u8], proof: &[u8]) -> Result<(), SchemeError>;
+
+    /// Whether an aggregate depends on the order of what it aggregates, e.g. a concatenation.
+    /// Votes are then aggregated in the order of the ids of their signers.
+    fn is_aggregate_ordered(&self) -> bool {
+        false
+    }
+}
+
+fn malformed<E: fmt::Display>(e: E) -> SchemeError {
+    SchemeError::Malformed(e.to_string())
+}
+
+/// BLS signatures over the BN256 curve of `bls-signatures-rs`, signatures in G1 and keys in G2.
+pub struct Bn256Scheme;
+
+impl Bn256Scheme {
+    // Proofs of possession sign the public key under their own domain, so that a proof is never
+    // a valid vote and a vote is never a valid proof.
+    const POP_DOMAIN: &'static [u8] = b"BOUNCE-BN256-POP";
+
+    fn pop_message(public_key: &[u8]) -> Vec<u8> {
+        [Bn256Scheme::POP_DOMAIN, public_key].concat()
+    }
+}
+
+impl SignatureScheme for Bn256Scheme {
+    fn id(&self) -> SchemeId {
+        SchemeId::Bn256
+    }
+
+    fn generate_private_key(&self) -> Vec<u8> {
+        let mut rng = thread_rng();
+        (0..32).map(|_| rng.gen()).collect()
+    }
+
+    fn derive_public_key(&self, private_key: &[u8]) -> Result<Vec<u8>, SchemeError> {
+        Bn256.derive_public_key(private_key).map_err(malformed)
+    }
+
+    fn sign(&self, private_key: &[u8], msg: &[u8]) -> Result<Vec<u8>, SchemeError> {
+        Bn256.sign(private_key, msg).map_err(malformed)
+    }
+
+    fn verify(&self, signature: &[u8], msg: &[u8], public_key: &[u8]) -> Result<(), SchemeError> {
+        Bn256
+            .verify(signature, msg, public_key)
+            .map_err(|_| SchemeError::Invalid)
+    }
+
+    fn aggregate_signatures(&self, signatures: &[&[u8]]) -> Result<Vec<u8>, SchemeError> {
+        Bn256.aggregate_signatures(signatures).map_err(malformed)
+    }
+
+    fn aggregate_public_keys(&self, public_keys: &[&[u8]]) -> Result<Vec<u8>, SchemeError> {
+        Bn256.aggregate_public_keys(public_keys).map_err(malformed)
+    }
+
+    fn prove_possession(&self, private_key: &[u8]) -> Result<Vec<u8>, SchemeError> {
+        let public_key = self.derive_public_key(private_key)?;
+        self.sign(private_key, &Bn256Scheme::pop_message(&public_key))
+    }
+
+    fn verify_possession(&self, public_key: &[u8], proof: &[u8]) -> Result<(), SchemeError> {
+        self.verify(proof, &Bn256Scheme::pop_message(public_key), public_key)
+    }
+}
+
+/// BLS signatures over BLS12-381 following the IETF ciphersuite
+/// `BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_`, i.e. minimal public key size with keys in G1,
+/// signatures in G2 and proofs of possession. Points are in the compressed encoding of the draft.
+pub struct Bls12381Scheme;
+
+impl Bls12381Scheme {
+    const SIGNATURE_DST: &'static [u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+    const POP_DST: &'static [u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+    fn private_key(private_key: &[u8]) -> Result<min_pk::SecretKey, SchemeError> {
+        min_pk::SecretKey::from_bytes(private_key).map_err(blst_malformed)
+    }
+
+    // Decodes and validates a public key, KeyValidate in the draft.
+    fn public_key(public_key: &[u8]) -> Result<min_pk::PublicKey, SchemeError> {
+        min_pk::PublicKey::key_validate(public_key).map_err(blst_malformed)
+    }
+
+    fn signature(signature: &[u8]) -> Result<min_pk::Signature, SchemeError> {
+        min_pk::Signature::sig_validate(signature, true).map_err(blst_malformed)
+    }
+
+    fn verify_with(
+        signature: &[u8],
+        msg: &[u8],
+        public_key: &[u8],
+        dst: &[u8],
+    ) -> Result<(), SchemeError> {
+        let signature = Bls12381Scheme::signature(signature).map_err(|_| SchemeError::Invalid)?;
+        let public_key =
+            Bls12381Scheme::public_key(public_key).map_err(|_| SchemeError::Invalid)?;
+        match signature.verify(false, msg, dst, &[], &public_key, false) {
+            BLST_ERROR::BLST_SUCCESS => Ok(()),
+            _ => Err(SchemeError::Invalid),
+        }
+    }
+}
+
+fn blst_malformed(e: BLST_ERROR) -> SchemeError {
+    SchemeError::Malformed(format!("{:?}", e))
+}
+
+impl SignatureScheme for Bls12381Scheme {
+    fn id(&self) -> SchemeId {
+        SchemeId::Bls12381
+    }
+
+    fn generate_private_key(&self) -> Vec<u8> {
+        let mut rng = thread_rng();
+        let ikm: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
+        // KeyGen of the draft, which cannot fail with 32 bytes of key material.
+        min_pk::SecretKey::key_gen(&ikm, &[])
+            .unwrap()
+            .to_bytes()
+            .to_vec()
+    }
+
+    fn derive_public_key(&self, private_key: &[u8]) -> Result<Vec<u8>, SchemeError> {
+        let private_key = Bls12381Scheme::private_key(private_key)?;
+        Ok(private_key.sk_to_pk().compress().to_vec())
+    }
+
+    fn sign(&self, private_key: &[u8], msg: &[u8]) -> Result<Vec<u8>, SchemeError> {
+        let private_key = Bls12381Scheme::private_key(private_key)?;
+        Ok(private_key
+            .sign(msg, Bls12381Scheme::SIGNATURE_DST, &[])
+            .compress()
+            .to_vec())
+    }
+
+    fn verify(&self, signature: &[u8], msg: &[u8], public_key: &[u8]) -> Result<(), SchemeError> {
+        Bls12381Scheme::verify_with(signature, msg, public_key, Bls12381Scheme::SIGNATURE_DST)
+    }
+
+    fn aggregate_signatures(&self, signatures: &[&[u8]]) -> Result<Vec<u8>, SchemeError> {
+        let signatures = signatures
+            .iter()
+            .map(|signature| Bls12381Scheme::signature(signature))
+            .collect::<Result<Vec<_>, _>>()?;
+        let signatures: Vec<&min_pk::Signature> = signatures.iter().collect();
+        let aggregate =
+            min_pk::AggregateSignature::aggregate(&signatures, false).map_err(blst_malformed)?;
+        Ok(aggregate.to_signature().compress().to_vec())
+    }
+
+    fn aggregate_public_keys(&self, public_keys: &[&[u8]]) -> Result<Vec<u8>, SchemeError> {
+        let public_keys = public_keys
+            .iter()
+            .map(|public_key| Bls12381Scheme::public_key(public_key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let public_keys: Vec<&min_pk::PublicKey> = public_keys.iter().collect();
+        let aggregate =
+            min_pk::AggregatePublicKey::aggregate(&public_keys, false).map_err(blst_malformed)?;
+        Ok(aggregate.to_public_key().compress().to_vec())
+    }
+
+    /// FastAggregateVerify of the draft.
+    fn verify_aggregate(
+        &self,
+        signature: &[u8],
+        msg: &[u8],
+        public_keys: &[&[u8]],
+    ) -> Result<(), SchemeError> {
+        let signature = Bls12381Scheme::signature(signature).map_err(|_| SchemeError::Invalid)?;
+        let public_keys = public_keys
+            .iter()
+            .map(|public_key| Bls12381Scheme::public_key(public_key))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| SchemeError::Invalid)?;
+        let public_keys: Vec<&min_pk::PublicKey> = public_keys.iter().collect();
+        match signature.fast_aggregate_verify(
+            false,
+            msg,
+            Bls12381Scheme::SIGNATURE_DST,
+            &public_keys,
+        ) {
+            BLST_ERROR::BLST_SUCCESS => Ok(()),
+            _ => Err(SchemeError::Invalid),
+        }
+    }
+
+    /// PopProve of the draft.
+    fn prove_possession(&self, private_key: &[u8]) -> Result<Vec<u8>, SchemeError> {
+        let private_key = Bls12381Scheme::private_key(private_key)?;
+        let public_key = private_key.sk_to_pk().compress();
+        Ok(private_key
+            .sign(&public_key, Bls12381Scheme::POP_DST, &[])
+            .compress()
+            .to_vec())
+    }
+
+    /// PopVerify of the draft.
+    fn verify_possession(&self, public_key: &[u8], proof: &[u8]) -> Result<(), SchemeError> {
+        Bls12381Scheme::verify_with(proof, public_key, public_key, Bls12381Scheme::POP_DST)
+    }
+}
+
+impl SchemeId {
+    /// Looks up a scheme by the name used in the flock configuration and on the command line.
+    pub fn from_name(name: &str) -> Option<SchemeId> {
+        match name {
+            "bn256" => Some(SchemeId::Bn256),
+            "bls12-381" => Some(SchemeId::Bls12381),
+            "lms" => Some(SchemeId::Lms),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the implementation of the scheme a commit or a flock names.
+pub fn signature_scheme(id: i32) -> Result<&'static dyn SignatureScheme, SchemeError> {
+    match SchemeId::from_i32(id) {
+        Some(SchemeId::Bn256) => Ok(&Bn256Scheme),
+        Some(SchemeId::Bls12381) => Ok(&Bls12381Scheme),
+        Some(SchemeId::Lms) => Ok(&LMS_SCHEME),
+        None => Err(SchemeError::Unsupported(id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bn256_scheme_test() {
+        let scheme = signature_scheme(SchemeId::Bn256.into()).unwrap();
+        assert_eq!(scheme.id(), SchemeId::Bn256);
+
+        let private_keys: Vec<Vec<u8>> = (0..3).map(|_| scheme.generate_private_key()).collect();
+        let public_keys: Vec<Vec<u8>> = private_keys
+            .iter()
+            .map(|k| scheme.derive_public_key(k).unwrap())
+            .collect();
+        let signatures: Vec<Vec<u8>> = private_keys
+            .iter()
+            .map(|k| scheme.sign(k, b"root").unwrap())
+            .collect();
+
+        for (signature, public_key) in signatures.iter().zip(&public_keys) {
+            scheme.verify(signature, b"root", public_key).unwrap();
+            assert_eq!(
+                scheme.verify(signature, b"other", public_key),
+                Err(SchemeError::Invalid)
+            );
+        }
+
+        let signature_refs: Vec<&[u8]> = signatures.iter().map(|s| s.as_slice()).collect();
+        let public_key_refs: Vec<&[u8]> = public_keys.iter().map(|k| k.as_slice()).collect();
+        let aggregate = scheme.aggregate_signatures(&signature_refs).unwrap();
+        scheme
+            .verify_aggregate(&aggregate, b"root", &public_key_refs)
+            .unwrap();
+        assert_eq!(
+            scheme.verify_aggregate(&aggregate, b"root", &public_key_refs[..2]),
+            Err(SchemeError::Invalid)
+        );
+
+        let proof = scheme.prove_possession(&private_keys[0]).unwrap();
+        scheme.verify_possession(&public_keys[0], &proof).unwrap();
+        assert!(scheme.verify_possession(&public_keys[1], &proof).is_err());
+        // A proof of possession is not a signature over the key alone.
+        let signature = scheme.sign(&private_keys[0], &public_keys[0]).unwrap();
+        assert!(scheme
+            .verify_possession(&public_keys[0], &signature)
+            .is_err());
+
+        assert!(matches!(
+            scheme.verify(b"garbage", b"root", &public_keys[0]),
+            Err(SchemeError::Invalid)
+        ));
+        assert!(matches!(
+            scheme.aggregate_signatures(&[b"garbage"]),
+            Err(SchemeError::Malformed(_))
+        ));
+        assert!(matches!(
+            signature_scheme(42),
+            Err(SchemeError::Unsupported(42))
+        ));
+    }
+
+    // Signing vectors of the Ethereum consensus specifications, which use this ciphersuite.
+    const BLS12_381_PRIVATE_KEY: &str =
+        "263dbd792f5b1be47ed85f8938c0f29586af0d3ac7b977f21c278fe1462040e3";
+    const BLS12_381_PUBLIC_KEY: &str =
+        "a491d1b0ecd9bb917989f0e74f0dea0422eac4a873e5e2644f368dffb9a\
+        6e20fd6e10c1b77654d067c0618f6e5a7f79a";
+    const BLS12_381_VECTORS: [(&str, &str); 3] = [
+        (
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "b6ed936746e01f8ecf281f020953fbf1f01debd5657c4a383940b020b26507f6076334f91e2366c96e9ab27\
+            9fb5158090352ea1c5b0c9274504f4f0e7053af24802e51e4568d164fe986834f41e55c8e850ce1f98458c0\
+            cfc9ab380b55285a55",
+        ),
+        (
+            "5656565656565656565656565656565656565656565656565656565656565656",
+            "882730e5d03f6b42c3abc26d3372625034e1d871b65a8a6b900a56dae22da98abbe1b68f85e49fe7652a55e\
+            c3d0591c20767677e33e5cbb1207315c41a9ac03be39c2e7668edc043d6cb1d9fd93033caa8a1c5b0e84bed\
+            aeb6c64972503a43eb",
+        ),
+        (
+            "abababababababababababababababababababababababababababababababab",
+            "91347bccf740d859038fcdcaf233eeceb2a436bcaaee9b2aa3bfb70efe29dfb2677562ccbea1c8e061fb997\
+            1b0753c240622fab78489ce96768259fc01360346da5b9f579e5da0d941e4c6ba18a0e64906082375394f33\
+            7fa1af2b7127b0d121",
+        ),
+    ];
+
+    #[test]
+    fn bls12_381_test_vectors() {
+        let scheme = signature_scheme(SchemeId::Bls12381.into()).unwrap();
+        let private_key = hex::decode(BLS12_381_PRIVATE_KEY).unwrap();
+        let public_key = scheme.derive_public_key(&private_key).unwrap();
+        assert_eq!(hex::encode(&public_key), BLS12_381_PUBLIC_KEY);
+
+        for (msg, signature) in BLS12_381_VECTORS.iter() {
+            let msg = hex::decode(msg).unwrap();
+            assert_eq!(
+                hex::encode(scheme.sign(&private_key, &msg).unwrap()),
+                *signature
+            );
+            scheme
+                .verify(&hex::decode(signature).unwrap(), &msg, &public_key)
+                .unwrap();
+        }
+
+        // The key must be a point of the subgroup, and the signature is in the domain of the
+        // ciphersuite.
+        let mut infinity = vec![0; 48];
+        infinity[0] = 0xc0;
+        let signature = hex::decode(BLS12_381_VECTORS[0].1).unwrap();
+        let msg = hex::decode(BLS12_381_VECTORS[0].0).unwrap();
+        assert_eq!(
+            scheme.verify(&signature, &msg, &infinity),
+            Err(SchemeError::Invalid)
+        );
+        assert_eq!(
+            Bn256Scheme.verify(&signature, &msg, &public_key),
+            Err(SchemeError::Invalid)
+        );
+    }
+
+    #[test]
+    fn bls12_381_scheme_test() {
+        let scheme = signature_scheme(SchemeId::Bls12381.into()).unwrap();
+        assert_eq!(scheme.id(), SchemeId::Bls12381);
+
+        let private_keys: Vec<Vec<u8>> = (0..3).map(|_| scheme.generate_private_key()).collect();
+        let public_keys: Vec<Vec<u8>> = private_keys
+            .iter()
+            .map(|k| scheme.derive_public_key(k).unwrap())
+            .collect();
+        let signatures: Vec<Vec<u8>> = private_keys
+            .iter()
+            .map(|k| scheme.sign(k, b"root").unwrap())
+            .collect();
+        assert!(public_keys.iter().all(|k| k.len() == 48));
+        assert!(signatures.iter().all(|s| s.len() == 96));
+
+        let signature_refs: Vec<&[u8]> = signatures.iter().map(|s| s.as_slice()).collect();
+        let public_key_refs: Vec<&[u8]> = public_keys.iter().map(|k| k.as_slice()).collect();
+        let aggregate = scheme.aggregate_signatures(&signature_refs).unwrap();
+        scheme
+            .verify_aggregate(&aggregate, b"root", &public_key_refs)
+            .unwrap();
+        // The aggregate also verifies under the aggregated key, as votes are checked.
+        let aggregate_public_key = scheme.aggregate_public_keys(&public_key_refs).unwrap();
+        scheme
+            .verify(&aggregate, b"root", &aggregate_public_key)
+            .unwrap();
+        assert_eq!(
+            scheme.verify_aggregate(&aggregate, b"root", &public_key_refs[..2]),
+            Err(SchemeError::Invalid)
+        );
+
+        let proof = scheme.prove_possession(&private_keys[0]).unwrap();
+        scheme.verify_possession(&public_keys[0], &proof).unwrap();
+        assert!(scheme.verify_possession(&public_keys[1], &proof).is_err());
+        // A proof of possession is not a signature over the key.
+        let signature = scheme.sign(&private_keys[0], &public_keys[0]).unwrap();
+        assert!(scheme
+            .verify_possession(&public_keys[0], &signature)
+            .is_err());
+
+        assert!(matches!(
+            scheme.aggregate_signatures(&[b"garbage"]),
+            Err(SchemeError::Malformed(_))
+        ));
+        assert!(matches!(
+            scheme.derive_public_key(&[0xff; 32]),
+            Err(SchemeError::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,307 @@
+use crate::{Member, SchemeError, SignatureScheme};
+use pkcs11::errors::Error as Pkcs11Error;
+use pkcs11::types::*;
+use pkcs11::Ctx;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq)]
+pub enum SignerError {
+    // The scheme refused the key or failed to sign with it.
+    Scheme(SchemeError),
+    // The PKCS#11 module or token failed.
+    Token(String),
+    // The token has no key with this label.
+    KeyNotFound(String),
+}
+
+impl fmt::Display for SignerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignerError::Scheme(e) => write!(f, "{}", e),
+            SignerError::Token(e) => write!(f, "token failure: {}", e),
+            SignerError::KeyNotFound(label) => write!(f, "no key labeled {} in the token", label),
+        }
+    }
+}
+
+impl std::error::Error for SignerError {}
+
+impl From<SchemeError> for SignerError {
+    fn from(e: SchemeError) -> Self {
+        SignerError::Scheme(e)
+    }
+}
+
+impl From<Pkcs11Error> for SignerError {
+    fn from(e: Pkcs11Error) -> Self {
+        SignerError::Token(e.to_string())
+    }
+}
+
+/// Holds the private key of a Bounce unit and signs with it in the scheme of the flock, so that
+/// the unit never handles the key itself.
+pub trait Signer: Send {
+    fn public_key(&self) -> &[u8];
+
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, SignerError>;
+
+    fn prove_possession(&self) -> Result<Vec<u8>, SignerError>;
+}
+
+/// Registers the Bounce unit `id` signing with `signer`.
+pub fn signer_member(signer: &dyn Signer, id: u32) -> Result<Member, SignerError> {
+    Ok(Member {
+        id,
+        public_key: signer.public_key().to_vec(),
+        proof_of_possession: signer.prove_possession()?,
+        address: String::new(),
+    })
+}
+
+/// A private key held in memory.
+pub struct KeySigner {
+    scheme: &'static dyn SignatureScheme,
+    private_key: Vec<u8>,
+    public_key: Vec<u8>,
+}
+
+impl KeySigner {
+    pub fn new(
+        scheme: &'static dyn SignatureScheme,
+        private_key: Vec<u8>,
+    ) -> Result<Self, SchemeError> {
+        let public_key = scheme.derive_public_key(&private_key)?;
+        Ok(KeySigner {
+            scheme,
+            private_key,
+            public_key,
+        })
+    }
+}
+
+impl Signer for KeySigner {
+    fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, SignerError> {
+        Ok(self.scheme.sign(&self.private_key, msg)?)
+    }
+
+    fn prove_possession(&self) -> Result<Vec<u8>, SignerError> {
+        Ok(self.scheme.prove_possession(&self.private_key)?)
+    }
+}
+
+/// Keys stored in a PKCS#11 token, e.g. a SoftHSM2 token or a hardware security module, logged in
+/// as its user.
+///
+/// This is a key store, not a hardware signer: it gives none of the protection of an HSM. Tokens
+/// have no mechanism for the pairing-based and hash-based schemes of the flock, so keys are stored
+/// as private secret key objects that are neither sensitive nor unextractable. The token keeps
+/// them at rest and only releases them to a session logged in with the PIN, but every signature is
+/// made in the memory of this process, which reads the key for each signature and wipes it right
+/// after. Anyone holding the PIN can read the keys out of the token.
+#[derive(Clone)]
+pub struct Pkcs11KeyStore {
+    ctx: Arc<Ctx>,
+    slot: CK_SLOT_ID,
+    pin: String,
+}
+
+impl Pkcs11KeyStore {
+    /// Loads the PKCS#11 `module` and finds the initialized token labeled `label`.
+    pub fn open(module: impl AsRef<Path>, label: &str, pin: &str) -> Result<Self, SignerError> {
+        let ctx = Ctx::new_and_initialize(module.as_ref())?;
+        for slot in ctx.get_slot_list(true)? {
+            if String::from(ctx.get_token_info(slot)?.label) == label {
+                return Ok(Pkcs11KeyStore {
+                    ctx: Arc::new(ctx),
+                    slot,
+                    pin: pin.to_string(),
+                });
+            }
+        }
+        Err(SignerError::Token(format!("no token labeled {}", label)))
+    }
+
+    // Opens a read/write session. The login is shared by every session of the application.
+    fn session(&self) -> Result<CK_SESSION_HANDLE, SignerError> {
+        let session =
+            self.ctx
+                .open_session(self.slot, CKF_SERIAL_SESSION | CKF_RW_SESSION, None, None)?;
+        match self.ctx.login(session, CKU_USER, Some(&self.pin)) {
+            Ok(()) | Err(Pkcs11Error::Pkcs11(CKR_USER_ALREADY_LOGGED_IN)) => Ok(session),
+            Err(e) => {
+                let _ = self.ctx.close_session(session);
+                Err(e.into())
+            }
+        }
+    }
+
+    fn find_key(
+        &self,
+        session: CK_SESSION_HANDLE,
+        label: &str,
+    ) -> Result<CK_OBJECT_HANDLE, SignerError> {
+        let class = CKO_SECRET_KEY;
+        let template = vec![
+            CK_ATTRIBUTE::new(CKA_CLASS).with_ck_ulong(&class),
+            CK_ATTRIBUTE::new(CKA_LABEL).with_string(label),
+        ];
+        self.ctx.find_objects_init(session, &template)?;
+        let found = self.ctx.find_objects(session, 1);
+        self.ctx.find_objects_final(session)?;
+        found?
+            .first()
+            .copied()
+            .ok_or_else(|| SignerError::KeyNotFound(label.to_string()))
+    }
+
+    /// Stores `private_key` in the token under `label`, readable by any session logged in as the
+    /// user.
+    pub fn import_key(&self, label: &str, private_key: &[u8]) -> Result<(), SignerError> {
+        let session = self.session()?;
+        let class = CKO_SECRET_KEY;
+        let key_type = CKK_GENERIC_SECRET;
+        let template = vec![
+            CK_ATTRIBUTE::new(CKA_CLASS).with_ck_ulong(&class),
+            CK_ATTRIBUTE::new(CKA_KEY_TYPE).with_ck_ulong(&key_type),
+            CK_ATTRIBUTE::new(CKA_LABEL).with_string(label),
+            CK_ATTRIBUTE::new(CKA_TOKEN).with_bool(&CK_TRUE),
+            CK_ATTRIBUTE::new(CKA_PRIVATE).with_bool(&CK_TRUE),
+            CK_ATTRIBUTE::new(CKA_SENSITIVE).with_bool(&CK_FALSE),
+            CK_ATTRIBUTE::new(CKA_EXTRACTABLE).with_bool(&CK_TRUE),
+            CK_ATTRIBUTE::new(CKA_VALUE).with_bytes(private_key),
+        ];
+        let created = self.ctx.create_object(session, &template);
+        self.ctx.close_session(session)?;
+        created?;
+        Ok(())
+    }
+
+    /// Signs in `scheme` with the key labeled `label`.
+    pub fn signer(
+        &self,
+        scheme: &'static dyn SignatureScheme,
+        label: &str,
+    ) -> Result<Pkcs11KeySigner, SignerError> {
+        let session = self.session()?;
+        let mut signer = Pkcs11KeySigner {
+            token: self.clone(),
+            scheme,
+            session,
+            key: CK_INVALID_HANDLE,
+            public_key: Vec::new(),
+        };
+        signer.key = self.find_key(session, label)?;
+        signer.public_key = signer.with_private_key(|k| scheme.derive_public_key(k))?;
+        Ok(signer)
+    }
+}
+
+/// A private key kept in a PKCS#11 token, read into memory to sign.
+pub struct Pkcs11KeySigner {
+    token: Pkcs11KeyStore,
+    scheme: &'static dyn SignatureScheme,
+    session: CK_SESSION_HANDLE,
+    key: CK_OBJECT_HANDLE,
+    public_key: Vec<u8>,
+}
+
+impl Pkcs11KeySigner {
+    // Reads the key from the token for the duration of `f`.
+    fn with_private_key<T>(
+        &self,
+        f: impl FnOnce(&[u8]) -> Result<T, SchemeError>,
+    ) -> Result<T, SignerError> {
+        let ctx = &self.token.ctx;
+        let mut template = vec![CK_ATTRIBUTE::new(CKA_VALUE)];
+        ctx.get_attribute_value(self.session, self.key, &mut template)?;
+        let mut private_key: Vec<u8> = vec![0; template[0].ulValueLen as usize];
+        // The module writes the value into the buffer.
+        template[0].pValue = private_key.as_mut_ptr() as CK_VOID_PTR;
+        let (rv, _) = ctx.get_attribute_value(self.session, self.key, &mut template)?;
+        let result = if rv == CKR_OK {
+            f(&private_key).map_err(SignerError::from)
+        } else {
+            Err(Pkcs11Error::Pkcs11(rv).into())
+        };
+        private_key.iter_mut().for_each(|b| *b = 0);
+        result
+    }
+}
+
+impl Signer for Pkcs11KeySigner {
+    fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, SignerError> {
+        self.with_private_key(|k| self.scheme.sign(k, msg))
+    }
+
+    fn prove_possession(&self) -> Result<Vec<u8>, SignerError> {
+        self.with_private_key(|k| self.scheme.prove_possession(k))
+    }
+}
+
+impl Drop for Pkcs11KeySigner {
+    fn drop(&mut self) {
+        let _ = self.token.ctx.close_session(self.session);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bls12381Scheme, Registry};
+
+    #[test]
+    fn key_signer_test() {
+        let signer =
+            KeySigner::new(&Bls12381Scheme, Bls12381Scheme.generate_private_key()).unwrap();
+        let signature = signer.sign(b"root").unwrap();
+        Bls12381Scheme
+            .verify(&signature, b"root", signer.public_key())
+            .unwrap();
+
+        let member = signer_member(&signer, 0).unwrap();
+        assert!(Registry::new(&Bls12381Scheme, &[member]).is_ok());
+    }
+
+    // Runs with the softhsm feature. Expects a SoftHSM2 token labeled "bounce-test" with user PIN
+    // 1234, e.g. created with
+    // `softhsm2-util --init-token --free --label bounce-test --so-pin 1234 --pin 1234`, and the
+    // module in PKCS11_SOFTHSM2_MODULE.
+    #[test]
+    #[cfg(feature = "softhsm")]
+    fn pkcs11_key_signer_test() {
+        use crate::Bn256Scheme;
+
+        let module = std::env::var("PKCS11_SOFTHSM2_MODULE")
+            .unwrap_or_else(|_| "/usr/lib/softhsm/libsofthsm2.so".to_string());
+        let token = Pkcs11KeyStore::open(module, "bounce-test", "1234").unwrap();
+
+        let label = format!("bounce-unit-{}", hex::encode(rand::random::<[u8; 8]>()));
+        assert_eq!(
+            token.signer(&Bn256Scheme, &label).err(),
+            Some(SignerError::KeyNotFound(label.clone()))
+        );
+        let private_key = Bn256Scheme.generate_private_key();
+        token.import_key(&label, &private_key).unwrap();
+
+        let signer = token.signer(&Bn256Scheme, &label).unwrap();
+        let in_memory = KeySigner::new(&Bn256Scheme, private_key).unwrap();
+        assert_eq!(signer.public_key(), in_memory.public_key());
+        assert_eq!(
+            signer.sign(b"root").unwrap(),
+            in_memory.sign(b"root").unwrap()
+        );
+
+        let member = signer_member(&signer, 0).unwrap();
+        assert!(Registry::new(&Bn256Scheme, &[member]).is_ok());
+    }
+}
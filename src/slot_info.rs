@@ -1,16 +1,153 @@
-use crate::Commit;
+use crate::{Commit, Registry};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub enum Phase {
+    #[default]
     Stop,
     First,
     Second,
     Third,
 }
 
-impl Default for Phase {
-    fn default() -> Phase {
-        Phase::Stop
+#[derive(Debug, PartialEq)]
+pub enum VoteError {
+    // The signer has already voted for this type of commit in this slot.
+    Duplicate,
+    // The signer is not a registered member of the flock.
+    UnknownSigner,
+    // The vote was signed with another scheme than the one of the flock.
+    SchemeMismatch,
+    // The vote signs a different message than the votes already aggregated.
+    MessageMismatch,
+    // The signature does not match the message and the public key of the vote.
+    BadSignature,
+}
+
+impl fmt::Display for VoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoteError::Duplicate => write!(f, "the signer has already voted"),
+            VoteError::UnknownSigner => write!(f, "the signer is not a member of the flock"),
+            VoteError::SchemeMismatch => write!(f, "the vote uses another signature scheme"),
+            VoteError::MessageMismatch => write!(f, "the vote signs a different message"),
+            VoteError::BadSignature => write!(f, "the vote signature is invalid"),
+        }
+    }
+}
+
+impl std::error::Error for VoteError {}
+
+/// Running aggregate of the votes of one type in a slot.
+///
+/// Each vote is checked and folded into the aggregate signature and public key as it arrives, so
+/// that only the ids of the signers are kept and the aggregate is ready once a supermajority has
+/// voted. With a scheme whose aggregate depends on the order, the votes are kept instead and the
+/// aggregate is rebuilt in the order of the ids, so that a certificate checks against the members
+/// its signer bitmap selects.
+#[derive(Clone, Debug, Default)]
+pub struct VoteAggregate {
+    // The message every vote signs, taken from the first vote.
+    msg: Vec<u8>,
+    signature: Vec<u8>,
+    public_key: Vec<u8>,
+    // The ids of the members who voted.
+    signers: BTreeSet<u32>,
+    // The public key and signature of each vote by the id of its signer, only kept when the order
+    // of the aggregate matters.
+    ordered: BTreeMap<u32, (Vec<u8>, Vec<u8>)>,
+}
+
+impl VoteAggregate {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.signers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signers.is_empty()
+    }
+
+    pub fn msg(&self) -> &[u8] {
+        &self.msg
+    }
+
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Verifies a single signature vote and adds it to the aggregate. Only votes of registered
+    /// members are aggregated, since their keys come with a proof of possession.
+    pub fn add(&mut self, vote: &Commit, registry: &Registry) -> Result<(), VoteError> {
+        let scheme = registry.scheme();
+        if vote.scheme != scheme.id() as i32 {
+            return Err(VoteError::SchemeMismatch);
+        }
+        let id = registry
+            .id_of(&vote.public_key)
+            .ok_or(VoteError::UnknownSigner)?;
+        if self.signers.contains(&id) {
+            return Err(VoteError::Duplicate);
+        }
+        if !self.is_empty() && vote.msg != self.msg {
+            return Err(VoteError::MessageMismatch);
+        }
+        scheme
+            .verify(&vote.signature, &vote.msg, &vote.public_key)
+            .map_err(|_| VoteError::BadSignature)?;
+
+        // Every signature and key aggregated was just verified, so they are valid.
+        if scheme.is_aggregate_ordered() {
+            self.ordered
+                .insert(id, (vote.public_key.clone(), vote.signature.clone()));
+            let (public_keys, signatures): (Vec<&[u8]>, Vec<&[u8]>) = self
+                .ordered
+                .values()
+                .map(|(k, s)| (k.as_slice(), s.as_slice()))
+                .unzip();
+            self.signature = scheme.aggregate_signatures(&signatures).unwrap();
+            self.public_key = scheme.aggregate_public_keys(&public_keys).unwrap();
+        } else if self.is_empty() {
+            self.signature = vote.signature.clone();
+            self.public_key = vote.public_key.clone();
+        } else {
+            self.signature = scheme
+                .aggregate_signatures(&[&self.signature, &vote.signature])
+                .unwrap();
+            self.public_key = scheme
+                .aggregate_public_keys(&[&self.public_key, &vote.public_key])
+                .unwrap();
+        }
+        if self.is_empty() {
+            self.msg = vote.msg.clone();
+        }
+        self.signers.insert(id);
+        Ok(())
+    }
+
+    /// The members whose votes are aggregated, member k in bit k % 8 of byte k / 8.
+    pub fn signer_bitmap(&self) -> Vec<u8> {
+        let mut bitmap = Vec::new();
+        for &id in &self.signers {
+            let byte = id as usize / 8;
+            if bitmap.len() <= byte {
+                bitmap.resize(byte + 1, 0);
+            }
+            bitmap[byte] |= 1 << (id % 8);
+        }
+        bitmap
+    }
+
+    pub fn clear(&mut self) {
+        *self = VoteAggregate::new();
     }
 }
 
@@ -25,9 +162,12 @@ pub struct SlotInfo {
     pub signed: bool,
     // Whether this cubesat has aggregated signatures of at least supermajority of num_cubesats
     pub aggregated: bool,
-    // (id, signature) of precommtis or noncommits received for this slot.
-    pub precommits: Vec<Commit>,
-    pub noncommits: Vec<Commit>,
+    // The root of the proposal for this slot that passed the checks of this cubesat, the only
+    // precommit it signs.
+    pub proposal: Option<Vec<u8>>,
+    // Precommits and noncommits received for this slot.
+    pub precommits: VoteAggregate,
+    pub noncommits: VoteAggregate,
 }
 
 impl SlotInfo {
@@ -36,10 +176,16 @@ impl SlotInfo {
     }
 
     pub fn next(&mut self) {
-        self.i += 1;
+        self.advance_to(self.i + 1);
+    }
+
+    // Moves to the first phase of slot i, discarding everything recorded for the current slot.
+    pub fn advance_to(&mut self, i: u32) {
+        self.i = i;
         self.phase = Phase::First;
         self.signed = false;
         self.aggregated = false;
+        self.proposal = None;
         self.precommits.clear();
         self.noncommits.clear();
     }
@@ -49,6 +195,25 @@ impl SlotInfo {
 mod tests {
     use super::*;
     use crate::commit::CommitType;
+    use crate::{new_lms_private_key, new_member, Bn256Scheme, SignatureScheme, LMS_SCHEME};
+    use crate::{SchemeId, LMS_SHA256_M32_H5};
+
+    fn registry(n: u8) -> Registry {
+        let members: Vec<_> = (1..=n)
+            .map(|k| new_member(&Bn256Scheme, k as u32, &[k; 32]))
+            .collect();
+        Registry::new(&Bn256Scheme, &members).unwrap()
+    }
+
+    fn vote(private_key: &[u8], msg: &[u8]) -> Commit {
+        Commit {
+            typ: CommitType::Precommit.into(),
+            msg: msg.to_vec(),
+            public_key: Bn256Scheme.derive_public_key(private_key).unwrap(),
+            signature: Bn256Scheme.sign(private_key, msg).unwrap(),
+            ..Default::default()
+        }
+    }
 
     #[test]
     fn slot_info_init_test() {
@@ -69,16 +234,10 @@ mod tests {
         slot_info.phase = Phase::Second;
         slot_info.signed = true;
         slot_info.aggregated = true;
-        slot_info.noncommits.push(Commit {
-            typ: CommitType::Noncommit.into(),
-            i: 0,
-            j: 0,
-            msg: "test".to_owned().into_bytes(),
-            public_key: "dummy key".to_owned().into_bytes(),
-            signature: "dummy signature".to_owned().into_bytes(),
-            aggregated: false,
-            signer_id: 0,
-        });
+        slot_info
+            .noncommits
+            .add(&vote(&[1; 32], b"test"), &registry(1))
+            .unwrap();
 
         slot_info.next();
         assert_eq!(slot_info.i, 1);
@@ -87,4 +246,138 @@ mod tests {
         assert!(!slot_info.aggregated);
         assert!(slot_info.noncommits.is_empty());
     }
+
+    #[test]
+    fn slot_info_advance_to_test() {
+        let mut slot_info = SlotInfo::new();
+        slot_info.j = 3;
+        slot_info.signed = true;
+
+        slot_info.advance_to(7);
+        assert_eq!(slot_info.i, 7);
+        assert_eq!(slot_info.j, 3);
+        assert_eq!(slot_info.phase, Phase::First);
+        assert!(!slot_info.signed);
+    }
+
+    #[test]
+    fn vote_aggregate_test() {
+        let private_keys: Vec<Vec<u8>> = (1..=4).map(|k| vec![k; 32]).collect();
+        let votes: Vec<Commit> = private_keys.iter().map(|k| vote(k, b"root")).collect();
+
+        let registry = registry(4);
+        let mut aggregate = VoteAggregate::new();
+        for vote in &votes {
+            aggregate.add(vote, &registry).unwrap();
+        }
+        assert_eq!(aggregate.len(), 4);
+        assert_eq!(aggregate.msg(), b"root");
+
+        // The running aggregate is the same as aggregating all votes at once.
+        let signatures: Vec<&[u8]> = votes.iter().map(|v| v.signature.as_slice()).collect();
+        let public_keys: Vec<&[u8]> = votes.iter().map(|v| v.public_key.as_slice()).collect();
+        assert_eq!(
+            aggregate.signature(),
+            Bn256Scheme
+                .aggregate_signatures(&signatures)
+                .unwrap()
+                .as_slice()
+        );
+        assert_eq!(
+            aggregate.public_key(),
+            Bn256Scheme
+                .aggregate_public_keys(&public_keys)
+                .unwrap()
+                .as_slice()
+        );
+        Bn256Scheme
+            .verify(aggregate.signature(), b"root", aggregate.public_key())
+            .unwrap();
+        assert_eq!(aggregate.signer_bitmap(), vec![0b0001_1110]);
+
+        aggregate.clear();
+        assert!(aggregate.is_empty());
+    }
+
+    #[test]
+    fn vote_aggregate_rejects_invalid_votes_test() {
+        let registry = registry(3);
+        let mut aggregate = VoteAggregate::new();
+        aggregate.add(&vote(&[1; 32], b"root"), &registry).unwrap();
+
+        assert_eq!(
+            aggregate.add(&vote(&[1; 32], b"root"), &registry),
+            Err(VoteError::Duplicate)
+        );
+        assert_eq!(
+            aggregate.add(&vote(&[2; 32], b"other"), &registry),
+            Err(VoteError::MessageMismatch)
+        );
+        let mut forged = vote(&[2; 32], b"root");
+        forged.signature = vote(&[3; 32], b"root").signature;
+        assert_eq!(
+            aggregate.add(&forged, &registry),
+            Err(VoteError::BadSignature)
+        );
+        assert_eq!(
+            aggregate.add(&vote(&[7; 32], b"root"), &registry),
+            Err(VoteError::UnknownSigner)
+        );
+        let mut other_scheme = vote(&[2; 32], b"root");
+        other_scheme.scheme = 42;
+        assert_eq!(
+            aggregate.add(&other_scheme, &registry),
+            Err(VoteError::SchemeMismatch)
+        );
+
+        assert_eq!(aggregate.len(), 1);
+        Bn256Scheme
+            .verify(aggregate.signature(), b"root", aggregate.public_key())
+            .unwrap();
+    }
+
+    #[test]
+    fn ordered_vote_aggregate_test() {
+        LMS_SCHEME
+            .set_state_dir(std::env::temp_dir().join("bounce-lms-state"))
+            .unwrap();
+        let private_keys: Vec<Vec<u8>> = (0..3)
+            .map(|_| new_lms_private_key(&[LMS_SHA256_M32_H5]))
+            .collect();
+        let members: Vec<_> = (0..3)
+            .map(|id| new_member(&LMS_SCHEME, id, &private_keys[id as usize]))
+            .collect();
+        let registry = Registry::new(&LMS_SCHEME, &members).unwrap();
+        let votes: Vec<Commit> = private_keys
+            .iter()
+            .map(|k| Commit {
+                msg: b"root".to_vec(),
+                public_key: LMS_SCHEME.derive_public_key(k).unwrap(),
+                signature: LMS_SCHEME.sign(k, b"root").unwrap(),
+                scheme: SchemeId::Lms.into(),
+                ..Default::default()
+            })
+            .collect();
+
+        // Votes arriving out of order are concatenated in the order of the ids of their signers.
+        let mut aggregate = VoteAggregate::new();
+        for k in [2, 0] {
+            aggregate.add(&votes[k], &registry).unwrap();
+        }
+        assert_eq!(
+            aggregate.signature(),
+            LMS_SCHEME
+                .aggregate_signatures(&[&votes[0].signature, &votes[2].signature])
+                .unwrap()
+                .as_slice()
+        );
+        assert_eq!(aggregate.signer_bitmap(), vec![0b101]);
+        LMS_SCHEME
+            .verify_aggregate(
+                aggregate.signature(),
+                b"root",
+                &[&members[0].public_key, &members[2].public_key],
+            )
+            .unwrap();
+    }
 }
@@ -0,0 +1,145 @@
+use crate::{BounceResponse, RequestResult, RequestState};
+use std::collections::BTreeMap;
+use tokio::sync::watch;
+
+/// The results of the latest `capacity` ground requests admitted by the space station, by ticket,
+/// so that a ground station can collect them after losing contact. Each result can be watched
+/// until it settles.
+pub struct TicketBook {
+    next: u64,
+    capacity: usize,
+    // A receiver is kept with each sender, so that results are updated while nobody watches.
+    results: BTreeMap<u64, (watch::Sender<RequestResult>, watch::Receiver<RequestResult>)>,
+}
+
+impl RequestResult {
+    /// Whether the request will not change state anymore.
+    pub fn is_settled(&self) -> bool {
+        !self.known || matches!(self.state(), RequestState::Finalized | RequestState::Failed)
+    }
+}
+
+impl TicketBook {
+    pub fn new(capacity: usize) -> Self {
+        TicketBook {
+            next: 0,
+            capacity,
+            results: BTreeMap::new(),
+        }
+    }
+
+    /// Records a newly queued request and returns its ticket. The oldest ticket is forgotten
+    /// beyond the capacity, which ends its watches.
+    pub fn issue(&mut self) -> u64 {
+        let ticket = self.next;
+        self.next += 1;
+        let result = watch::channel(RequestResult {
+            ticket,
+            known: true,
+            state: RequestState::Queued.into(),
+            slot: 0,
+            response: None,
+        });
+        self.results.insert(ticket, result);
+        while self.results.len() > self.capacity {
+            let oldest = *self.results.keys().next().unwrap();
+            self.results.remove(&oldest);
+        }
+        ticket
+    }
+
+    fn update(&mut self, ticket: u64, f: impl FnOnce(&mut RequestResult)) {
+        if let Some((result_tx, result_rx)) = self.results.get(&ticket) {
+            let mut result = result_rx.borrow().clone();
+            f(&mut result);
+            result_tx
+                .send(result)
+                .expect("the book keeps a receiver of each result");
+        }
+    }
+
+    /// Forgets a ticket issued for a request that was not admitted after all.
+    pub fn forget(&mut self, ticket: u64) {
+        self.results.remove(&ticket);
+    }
+
+    pub fn propose(&mut self, ticket: u64, slot: u32) {
+        self.update(ticket, |result| {
+            result.state = RequestState::Proposed.into();
+            result.slot = slot;
+        });
+    }
+
+    pub fn finalize(&mut self, ticket: u64, response: BounceResponse) {
+        self.update(ticket, |result| {
+            result.state = RequestState::Finalized.into();
+            result.response = Some(response);
+        });
+    }
+
+    pub fn fail(&mut self, ticket: u64) {
+        self.update(ticket, |result| result.state = RequestState::Failed.into());
+    }
+
+    pub fn result(&self, ticket: u64) -> RequestResult {
+        match self.results.get(&ticket) {
+            Some((_, result_rx)) => result_rx.borrow().clone(),
+            None => RequestResult {
+                ticket,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Follows the result of `ticket`, None if it is not known.
+    pub fn watch(&self, ticket: u64) -> Option<watch::Receiver<RequestResult>> {
+        self.results
+            .get(&ticket)
+            .map(|(_, result_rx)| result_rx.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Commit;
+
+    #[tokio::test]
+    async fn ticket_book_test() {
+        let mut book = TicketBook::new(2);
+        let ticket = book.issue();
+        assert_eq!(book.result(ticket).state(), RequestState::Queued);
+
+        let mut result_rx = book.watch(ticket).unwrap();
+        book.propose(ticket, 7);
+        result_rx.changed().await.unwrap();
+        assert_eq!(result_rx.borrow().state(), RequestState::Proposed);
+        assert_eq!(result_rx.borrow().slot, 7);
+
+        let response = BounceResponse {
+            certificate: Some(Commit {
+                i: 7,
+                aggregated: true,
+                ..Default::default()
+            }),
+            proof: None,
+        };
+        book.finalize(ticket, response.clone());
+        result_rx.changed().await.unwrap();
+        let result = result_rx.borrow().clone();
+        assert!(result.is_settled());
+        assert_eq!(result.response, Some(response));
+        assert_eq!(book.result(ticket), result);
+
+        let failed = book.issue();
+        book.fail(failed);
+        assert_eq!(book.result(failed).state(), RequestState::Failed);
+
+        // The oldest ticket is forgotten, and its watch ends.
+        book.issue();
+        assert!(!book.result(ticket).known);
+        assert!(book.result(ticket).is_settled());
+        assert!(book.watch(ticket).is_none());
+        assert!(result_rx.changed().await.is_err());
+    }
+}
@@ -0,0 +1,455 @@
+use crate::{BounceConfig, Phase};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, watch};
+use tokio::time::{sleep_until, Instant};
+
+/// A phase transition broadcast by the timer, tagged with the slot index it belongs to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tick {
+    pub i: u32,
+    pub phase: Phase,
+}
+
+impl Tick {
+    /// Position of this transition among all transitions since genesis, or `None` for `Stop`.
+    pub fn ordinal(&self) -> Option<u64> {
+        let phase = match self.phase {
+            Phase::First => 0,
+            Phase::Second => 1,
+            Phase::Third => 2,
+            Phase::Stop => return None,
+        };
+        Some(self.i as u64 * 3 + phase)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ScheduleError {
+    // One of the slot or phase durations is zero.
+    ZeroDuration,
+    // Phase 1 and phase 2 together do not leave any room for phase 3 in a slot.
+    PhasesExceedSlot {
+        slot_duration: Duration,
+        phase1_duration: Duration,
+        phase2_duration: Duration,
+    },
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::ZeroDuration => write!(f, "slot and phase durations must be non-zero"),
+            ScheduleError::PhasesExceedSlot {
+                slot_duration,
+                phase1_duration,
+                phase2_duration,
+            } => write!(
+                f,
+                "phase 1 ({:?}) and phase 2 ({:?}) must end before the slot ({:?}) does",
+                phase1_duration, phase2_duration, slot_duration
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+/// Slot schedule anchored to a genesis timestamp, so that every process observing the same
+/// configuration agrees on the slot index: i = (now - genesis) / slot_duration.
+#[derive(Clone, Debug)]
+pub struct SlotSchedule {
+    genesis: SystemTime,
+    slot_duration: Duration,
+    phase1_duration: Duration,
+    phase2_duration: Duration,
+}
+
+impl SlotSchedule {
+    pub fn new(
+        genesis: SystemTime,
+        slot_duration: Duration,
+        phase1_duration: Duration,
+        phase2_duration: Duration,
+    ) -> Result<Self, ScheduleError> {
+        if slot_duration.as_millis() == 0
+            || phase1_duration.as_millis() == 0
+            || phase2_duration.as_millis() == 0
+        {
+            return Err(ScheduleError::ZeroDuration);
+        }
+        if phase1_duration + phase2_duration >= slot_duration {
+            return Err(ScheduleError::PhasesExceedSlot {
+                slot_duration,
+                phase1_duration,
+                phase2_duration,
+            });
+        }
+
+        Ok(SlotSchedule {
+            genesis,
+            slot_duration,
+            phase1_duration,
+            phase2_duration,
+        })
+    }
+
+    pub fn from_config(bounce_config: &BounceConfig) -> Result<Self, ScheduleError> {
+        SlotSchedule::new(
+            UNIX_EPOCH + Duration::from_millis(bounce_config.genesis_ms),
+            Duration::from_millis(bounce_config.slot_duration_ms as u64),
+            Duration::from_millis(bounce_config.phase1_duration_ms as u64),
+            Duration::from_millis(bounce_config.phase2_duration_ms as u64),
+        )
+    }
+
+    pub fn genesis(&self) -> SystemTime {
+        self.genesis
+    }
+
+    pub fn slot_duration(&self) -> Duration {
+        self.slot_duration
+    }
+
+    /// Returns the slot and phase in effect `elapsed` after genesis.
+    pub fn tick_at(&self, elapsed: Duration) -> Tick {
+        let slot_ms = self.slot_duration.as_millis();
+        let elapsed_ms = elapsed.as_millis();
+        let i = (elapsed_ms / slot_ms) as u32;
+        let offset = Duration::from_millis((elapsed_ms % slot_ms) as u64);
+
+        let phase = if offset < self.phase1_duration {
+            Phase::First
+        } else if offset < self.phase1_duration + self.phase2_duration {
+            Phase::Second
+        } else {
+            Phase::Third
+        };
+
+        Tick { i, phase }
+    }
+
+    /// Returns the slot and phase in effect at wall-clock time `now`, or `None` before genesis.
+    pub fn tick_at_time(&self, now: SystemTime) -> Option<Tick> {
+        now.duration_since(self.genesis)
+            .ok()
+            .map(|elapsed| self.tick_at(elapsed))
+    }
+
+    /// Returns how long after genesis the given phase of the given slot starts.
+    pub fn start_of(&self, tick: &Tick) -> Duration {
+        let slot_start = self.slot_duration * tick.i;
+        match tick.phase {
+            Phase::First | Phase::Stop => slot_start,
+            Phase::Second => slot_start + self.phase1_duration,
+            Phase::Third => slot_start + self.phase1_duration + self.phase2_duration,
+        }
+    }
+
+    /// Returns the phase transition that follows `tick`.
+    pub fn next_tick(&self, tick: &Tick) -> Tick {
+        match tick.phase {
+            Phase::First => Tick {
+                i: tick.i,
+                phase: Phase::Second,
+            },
+            Phase::Second => Tick {
+                i: tick.i,
+                phase: Phase::Third,
+            },
+            Phase::Third | Phase::Stop => Tick {
+                i: tick.i + 1,
+                phase: Phase::First,
+            },
+        }
+    }
+}
+
+/// Milliseconds since the Unix epoch, the unit of genesis and of ground request timestamps.
+pub fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Operator control over the timer, e.g. to pause the flock for a maintenance window or an
+/// eclipse and to resume it afterwards.
+pub struct FlockControl {
+    paused_tx: watch::Sender<bool>,
+    paused_rx: watch::Receiver<bool>,
+}
+
+impl Default for FlockControl {
+    fn default() -> Self {
+        let (paused_tx, paused_rx) = watch::channel(false);
+        FlockControl {
+            paused_tx,
+            paused_rx,
+        }
+    }
+}
+
+impl FlockControl {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.paused_rx.clone()
+    }
+
+    /// Stops the flock at the next slot boundary.
+    pub fn pause(&self) {
+        // The receiver held by self keeps the channel open.
+        let _ = self.paused_tx.send(true);
+    }
+
+    /// Starts the flock again at the first slot boundary after this call.
+    pub fn resume(&self) {
+        let _ = self.paused_tx.send(false);
+    }
+
+    /// Whether a pause was requested, the flock stops only once the current slot ends.
+    pub fn is_paused(&self) -> bool {
+        *self.paused_rx.borrow()
+    }
+}
+
+// Timer thread which broadcasts phase transitions.
+//
+// The wall clock is read only once to place this process on the schedule, after which deadlines
+// are tracked with the monotonic clock so that the ticks do not drift with clock adjustments.
+//
+// When paused, the timer broadcasts a Stop for the slot that would have started and nothing
+// else until it is resumed, after which it continues with the slot index given by the schedule.
+pub async fn timer(
+    timer_tx: broadcast::Sender<Tick>,
+    schedule: SlotSchedule,
+    mut paused_rx: watch::Receiver<bool>,
+) {
+    // `anchor` is the monotonic instant that corresponds to `elapsed` after genesis.
+    let now = Instant::now();
+    let (anchor, elapsed) = match SystemTime::now().duration_since(schedule.genesis()) {
+        Ok(elapsed) => (now, elapsed),
+        // Genesis is still in the future, so wait for slot 0 to begin.
+        Err(e) => (now + e.duration(), Duration::from_secs(0)),
+    };
+    sleep_until(anchor).await;
+
+    let mut tick = schedule.tick_at(elapsed);
+    loop {
+        if tick.phase == Phase::First && *paused_rx.borrow() {
+            let stop = Tick {
+                i: tick.i,
+                phase: Phase::Stop,
+            };
+            if timer_tx.send(stop).is_err() {
+                return;
+            }
+
+            while *paused_rx.borrow() {
+                if paused_rx.changed().await.is_err() {
+                    // Nobody can resume the flock anymore, so do it now.
+                    break;
+                }
+            }
+
+            let now = schedule.tick_at(elapsed + (Instant::now() - anchor));
+            tick = Tick {
+                i: now.i + 1,
+                phase: Phase::First,
+            };
+            sleep_until(anchor + (schedule.start_of(&tick) - elapsed)).await;
+            continue;
+        }
+
+        if timer_tx.send(tick.clone()).is_err() {
+            // Nobody is listening to phase transitions anymore.
+            return;
+        }
+
+        tick = schedule.next_tick(&tick);
+        sleep_until(anchor + (schedule.start_of(&tick) - elapsed)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SchemeId;
+
+    fn schedule(genesis: SystemTime) -> SlotSchedule {
+        SlotSchedule::new(
+            genesis,
+            Duration::from_millis(1000),
+            Duration::from_millis(400),
+            Duration::from_millis(300),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn schedule_validation_test() {
+        let ms = Duration::from_millis;
+
+        assert_eq!(
+            SlotSchedule::new(UNIX_EPOCH, ms(0), ms(400), ms(300)).unwrap_err(),
+            ScheduleError::ZeroDuration
+        );
+        assert_eq!(
+            SlotSchedule::new(UNIX_EPOCH, ms(1000), ms(0), ms(300)).unwrap_err(),
+            ScheduleError::ZeroDuration
+        );
+        assert!(matches!(
+            SlotSchedule::new(UNIX_EPOCH, ms(1000), ms(700), ms(300)),
+            Err(ScheduleError::PhasesExceedSlot { .. })
+        ));
+        assert!(SlotSchedule::new(UNIX_EPOCH, ms(1000), ms(700), ms(299)).is_ok());
+
+        let bounce_config = BounceConfig {
+            num_cubesats: 5,
+            genesis_ms: 0,
+            slot_duration_ms: 250,
+            phase1_duration_ms: 100,
+            phase2_duration_ms: 100,
+            ground_station_keys: Vec::new(),
+            request_window_ms: 60_000,
+            replay_cache_size: 1024,
+            members: Vec::new(),
+            scheme: SchemeId::Bn256.into(),
+            space_station: String::new(),
+            space_station_key: Vec::new(),
+        };
+        assert!(SlotSchedule::from_config(&bounce_config).is_ok());
+    }
+
+    #[test]
+    fn tick_at_test() {
+        let s = schedule(UNIX_EPOCH);
+        let ms = Duration::from_millis;
+
+        assert_eq!(
+            s.tick_at(ms(0)),
+            Tick {
+                i: 0,
+                phase: Phase::First
+            }
+        );
+        assert_eq!(
+            s.tick_at(ms(399)),
+            Tick {
+                i: 0,
+                phase: Phase::First
+            }
+        );
+        assert_eq!(
+            s.tick_at(ms(400)),
+            Tick {
+                i: 0,
+                phase: Phase::Second
+            }
+        );
+        assert_eq!(
+            s.tick_at(ms(700)),
+            Tick {
+                i: 0,
+                phase: Phase::Third
+            }
+        );
+        assert_eq!(
+            s.tick_at(ms(12_345)),
+            Tick {
+                i: 12,
+                phase: Phase::First
+            }
+        );
+
+        assert_eq!(
+            s.tick_at_time(UNIX_EPOCH + ms(1500)),
+            Some(s.tick_at(ms(1500)))
+        );
+        assert_eq!(schedule(UNIX_EPOCH + ms(1)).tick_at_time(UNIX_EPOCH), None);
+    }
+
+    #[test]
+    fn next_tick_test() {
+        let s = schedule(UNIX_EPOCH);
+        let mut tick = Tick {
+            i: 3,
+            phase: Phase::First,
+        };
+
+        for (i, phase, start_ms) in [
+            (3, Phase::Second, 3400),
+            (3, Phase::Third, 3700),
+            (4, Phase::First, 4000),
+        ] {
+            tick = s.next_tick(&tick);
+            assert_eq!(tick, Tick { i, phase });
+            assert_eq!(s.start_of(&tick), Duration::from_millis(start_ms));
+            assert_eq!(s.tick_at(s.start_of(&tick)), tick);
+        }
+    }
+
+    #[tokio::test]
+    async fn timer_test() {
+        tokio::time::pause();
+
+        // Genesis was 2.5 slots ago, so the timer starts in the middle of the second phase of
+        // slot 2.
+        let s = schedule(SystemTime::now() - Duration::from_millis(2500));
+        let (timer_tx, mut timer_rx) = broadcast::channel(15);
+        let control = FlockControl::new();
+        let paused_rx = control.subscribe();
+        tokio::spawn(async move {
+            timer(timer_tx, s, paused_rx).await;
+        });
+
+        let expected = [
+            (2, Phase::Second),
+            (2, Phase::Third),
+            (3, Phase::First),
+            (3, Phase::Second),
+        ];
+        for (i, phase) in expected {
+            assert_eq!(timer_rx.recv().await.unwrap(), Tick { i, phase });
+        }
+    }
+
+    #[tokio::test]
+    async fn timer_pause_resume_test() {
+        tokio::time::pause();
+
+        let s = schedule(SystemTime::now());
+        let (timer_tx, mut timer_rx) = broadcast::channel(15);
+        let control = FlockControl::new();
+        let paused_rx = control.subscribe();
+        tokio::spawn(async move {
+            timer(timer_tx, s, paused_rx).await;
+        });
+
+        assert_eq!(
+            timer_rx.recv().await.unwrap(),
+            Tick {
+                i: 0,
+                phase: Phase::First
+            }
+        );
+
+        // The current slot runs to completion before the flock stops.
+        control.pause();
+        assert!(control.is_paused());
+        for (i, phase) in [(0, Phase::Second), (0, Phase::Third), (1, Phase::Stop)] {
+            assert_eq!(timer_rx.recv().await.unwrap(), Tick { i, phase });
+        }
+
+        // Nothing is broadcast while paused, and the flock picks up at the slot given by the
+        // schedule when it is resumed.
+        tokio::time::sleep(Duration::from_millis(3_500)).await;
+        assert!(timer_rx.try_recv().is_err());
+        control.resume();
+        for (i, phase) in [(5, Phase::First), (5, Phase::Second)] {
+            assert_eq!(timer_rx.recv().await.unwrap(), Tick { i, phase });
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use crate::link::send_to_all;
+use crate::{Commit, Link, LinkError};
+use log::warn;
+use prost::Message;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use tokio::net::{lookup_host, UdpSocket};
+
+// The largest payload of a UDP datagram over IPv4, each commit is sent in a single datagram.
+const MAX_DATAGRAM: usize = 65_507;
+
+/// A link over UDP, one datagram per commit, e.g. for a radio modem that forwards datagrams. Lost
+/// datagrams are not sent again, as the protocol already copes with lost votes.
+pub struct UdpLink {
+    socket: UdpSocket,
+    peers: BTreeMap<u32, SocketAddr>,
+}
+
+impl UdpLink {
+    /// Receives the datagrams sent to `address`, e.g. "0.0.0.0:50061".
+    pub async fn bind(address: &str) -> Result<Self, LinkError> {
+        let socket = UdpSocket::bind(address)
+            .await
+            .map_err(|e| LinkError::Io(e.to_string()))?;
+        Ok(UdpLink {
+            socket,
+            peers: BTreeMap::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, LinkError> {
+        self.socket
+            .local_addr()
+            .map_err(|e| LinkError::Io(e.to_string()))
+    }
+
+    /// Sends the commits for peer `id` to `address`, e.g. "10.0.0.7:50061", resolved once here.
+    pub async fn add_peer(&mut self, id: u32, address: &str) -> Result<(), LinkError> {
+        let addr = lookup_host(address)
+            .await
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| LinkError::Address(address.to_string()))?;
+        self.peers.insert(id, addr);
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl Link for UdpLink {
+    async fn send(&self, to: u32, commit: Commit) -> Result<(), LinkError> {
+        let addr = self.peers.get(&to).ok_or(LinkError::UnknownPeer(to))?;
+        let mut datagram = Vec::with_capacity(commit.encoded_len());
+        commit
+            .encode(&mut datagram)
+            .expect("a vector grows to fit the commit");
+        if datagram.len() > MAX_DATAGRAM {
+            return Err(LinkError::TooLarge(datagram.len()));
+        }
+        self.socket
+            .send_to(&datagram, addr)
+            .await
+            .map_err(|e| LinkError::Io(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn broadcast(&self, commit: Commit) -> Result<(), LinkError> {
+        send_to_all(self, self.peers.keys().copied(), &commit).await
+    }
+
+    async fn recv(&self) -> Option<Commit> {
+        let mut datagram = vec![0; MAX_DATAGRAM];
+        loop {
+            match self.socket.recv_from(&mut datagram).await {
+                Ok((n, from)) => match Commit::decode(&datagram[..n]) {
+                    Ok(commit) => return Some(commit),
+                    Err(_) => warn!("Peer {}\tDropped a malformed datagram", from),
+                },
+                Err(e) => warn!("Failed to receive a datagram: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn udp_link_test() {
+        let receiver = UdpLink::bind("127.0.0.1:0").await.unwrap();
+        let mut sender = UdpLink::bind("127.0.0.1:0").await.unwrap();
+        let receiver_addr = receiver.local_addr().unwrap().to_string();
+        sender.add_peer(1, &receiver_addr).await.unwrap();
+
+        // Garbage is dropped, and the next commit still arrives.
+        sender
+            .socket
+            .send_to(&[0xff; 3], &receiver_addr)
+            .await
+            .unwrap();
+        sender
+            .broadcast(Commit {
+                i: 7,
+                msg: b"root".to_vec(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let commit = receiver.recv().await.unwrap();
+        assert_eq!((commit.i, commit.msg.as_slice()), (7, &b"root"[..]));
+
+        let large = Commit {
+            msg: vec![0; MAX_DATAGRAM],
+            ..Default::default()
+        };
+        assert!(matches!(
+            sender.send(1, large).await,
+            Err(LinkError::TooLarge(_))
+        ));
+        assert_eq!(
+            sender.send(2, Commit::default()).await,
+            Err(LinkError::UnknownPeer(2))
+        );
+    }
+}